@@ -1,6 +1,6 @@
 use clap::Parser;
 use console::style;
-use sqlx_cli::Opt;
+use sqlx_cli::{ExitStatus, Opt};
 use std::process;
 
 // cargo invokes this binary as `cargo-sqlx sqlx <args>`
@@ -17,6 +17,10 @@ async fn main() {
     let Cli::Sqlx(opt) = Cli::parse();
 
     if let Err(error) = sqlx_cli::run(opt).await {
+        if let Some(ExitStatus(code)) = error.downcast_ref() {
+            process::exit(*code);
+        }
+
         println!("{} {}", style("error:").bold().red(), error);
         process::exit(1);
     }
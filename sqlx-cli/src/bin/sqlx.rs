@@ -1,12 +1,16 @@
 use clap::Parser;
 use console::style;
-use sqlx_cli::Opt;
+use sqlx_cli::{ExitStatus, Opt};
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
     // no special handling here
     if let Err(error) = sqlx_cli::run(Opt::parse()).await {
+        if let Some(ExitStatus(code)) = error.downcast_ref() {
+            std::process::exit(*code);
+        }
+
         println!("{} {}", style("error:").bold().red(), error);
         std::process::exit(1);
     }
@@ -0,0 +1,408 @@
+//! A best-effort plan to drop the objects a migration created, for `sqlx migrate cleanup-dirty`.
+//!
+//! The plan is derived from the `CREATE` statements of the migration alone, without parsing SQL
+//! properly or looking at the database: anything else the migration did, e.g. `ALTER TABLE` or
+//! inserting rows, is not undone.
+
+/// The kinds of objects whose `CREATE` statements are recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    View,
+    MaterializedView,
+    Index,
+    Sequence,
+    Type,
+    Function,
+    Trigger,
+}
+
+impl ObjectKind {
+    fn keyword(self) -> &'static str {
+        match self {
+            ObjectKind::Table => "TABLE",
+            ObjectKind::View => "VIEW",
+            ObjectKind::MaterializedView => "MATERIALIZED VIEW",
+            ObjectKind::Index => "INDEX",
+            ObjectKind::Sequence => "SEQUENCE",
+            ObjectKind::Type => "TYPE",
+            ObjectKind::Function => "FUNCTION",
+            ObjectKind::Trigger => "TRIGGER",
+        }
+    }
+}
+
+/// An object created by a statement of the migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedObject {
+    pub kind: ObjectKind,
+    /// The name as written in the migration, quotes and schema included.
+    pub name: String,
+    /// The table of an index or trigger.
+    pub table: Option<String>,
+}
+
+/// A statement of the migration which the plan does not undo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    /// The first line of the statement.
+    pub statement: String,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CleanupPlan {
+    /// The `DROP` statements to execute, in order.
+    pub drops: Vec<String>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// Plan dropping the objects created by `statements`, in reverse order of creation.
+///
+/// Objects created with `IF NOT EXISTS` or `OR REPLACE` may predate the migration and are never
+/// dropped. Indexes and triggers on a table created by the migration go away with the table.
+pub fn plan(dialect: &str, statements: &[&str]) -> CleanupPlan {
+    let mut created = Vec::new();
+    let mut plan = CleanupPlan::default();
+
+    for statement in statements {
+        match parse_create(statement) {
+            Parsed::NotCreate => {}
+            Parsed::Created(object) => created.push(object),
+            Parsed::Skipped(reason) => plan.skipped.push(Skipped {
+                statement: first_line(statement),
+                reason,
+            }),
+        }
+    }
+
+    let tables: Vec<String> = created
+        .iter()
+        .filter(|object| object.kind == ObjectKind::Table)
+        .map(|object| normalize(&object.name))
+        .collect();
+
+    for object in created.iter().rev() {
+        if let Some(table) = &object.table {
+            if tables.contains(&normalize(table)) {
+                continue;
+            }
+        }
+
+        let drop = match (object.kind, &object.table) {
+            (ObjectKind::Index, Some(table)) if dialect == "mysql" => {
+                plan.skipped.push(Skipped {
+                    statement: format!("CREATE INDEX {} ON {table}", object.name),
+                    reason: "MySQL has no DROP INDEX IF EXISTS; drop the index by hand",
+                });
+                continue;
+            }
+            (ObjectKind::Index, Some(table)) if dialect == "postgres" => {
+                // an index lives in the schema of its table
+                match table.rsplit_once('.') {
+                    Some((schema, _)) if !object.name.contains('.') => {
+                        format!("DROP INDEX IF EXISTS {schema}.{};", object.name)
+                    }
+                    _ => format!("DROP INDEX IF EXISTS {};", object.name),
+                }
+            }
+            (ObjectKind::Trigger, Some(table)) if dialect == "postgres" => {
+                format!("DROP TRIGGER IF EXISTS {} ON {table};", object.name)
+            }
+            (kind, _) => format!("DROP {} IF EXISTS {};", kind.keyword(), object.name),
+        };
+
+        plan.drops.push(drop);
+    }
+
+    plan
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+    NotCreate,
+    Created(CreatedObject),
+    Skipped(&'static str),
+}
+
+fn parse_create(statement: &str) -> Parsed {
+    let mut words = Words {
+        rest: skip_comments(statement),
+    };
+
+    if !words.keyword("CREATE") {
+        return Parsed::NotCreate;
+    }
+
+    let or_replace = words.keyword("OR") && words.keyword("REPLACE");
+
+    // modifiers, including MySQL's `ALGORITHM = ...` and `DEFINER = ...` of views and triggers
+    while words.keyword("UNIQUE")
+        || words.keyword("TEMP")
+        || words.keyword("TEMPORARY")
+        || words.keyword("UNLOGGED")
+        || words.keyword("GLOBAL")
+        || words.keyword("LOCAL")
+        || (words.keyword("SQL") && words.keyword("SECURITY") && words.next().is_some())
+        || words.assignment()
+    {}
+
+    let kind = if words.keyword("TABLE") {
+        ObjectKind::Table
+    } else if words.keyword("VIEW") {
+        ObjectKind::View
+    } else if words.keyword("MATERIALIZED") && words.keyword("VIEW") {
+        ObjectKind::MaterializedView
+    } else if words.keyword("INDEX") {
+        ObjectKind::Index
+    } else if words.keyword("SEQUENCE") {
+        ObjectKind::Sequence
+    } else if words.keyword("TYPE") {
+        ObjectKind::Type
+    } else if words.keyword("FUNCTION") {
+        ObjectKind::Function
+    } else if words.keyword("TRIGGER") {
+        ObjectKind::Trigger
+    } else {
+        return Parsed::Skipped("unsupported kind of object");
+    };
+
+    if or_replace {
+        return Parsed::Skipped("created with OR REPLACE; it may predate the migration");
+    }
+
+    if kind == ObjectKind::Index {
+        words.keyword("CONCURRENTLY");
+    }
+
+    if words.keyword("IF") {
+        return Parsed::Skipped("created with IF NOT EXISTS; it may predate the migration");
+    }
+
+    if kind == ObjectKind::Index && words.keyword("ON") {
+        return Parsed::Skipped("unnamed index");
+    }
+
+    let Some(name) = words.name() else {
+        return Parsed::Skipped("could not find the name of the object");
+    };
+
+    let table = match kind {
+        ObjectKind::Index | ObjectKind::Trigger => {
+            // `ON [ONLY] table`, after the timing and events of a trigger
+            while !words.keyword("ON") {
+                if words.next().is_none() {
+                    return Parsed::Skipped("could not find the table of the object");
+                }
+            }
+
+            words.keyword("ONLY");
+
+            let Some(table) = words.name() else {
+                return Parsed::Skipped("could not find the table of the object");
+            };
+
+            Some(table)
+        }
+        _ => None,
+    };
+
+    Parsed::Created(CreatedObject { kind, name, table })
+}
+
+/// Skip the comments and whitespace leading `sql`.
+fn skip_comments(mut sql: &str) -> &str {
+    loop {
+        sql = sql.trim_start();
+
+        if sql.starts_with("--") {
+            sql = sql.find('\n').map_or("", |end| &sql[end..]);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.find("*/").map_or("", |end| &rest[end + 2..]);
+        } else {
+            return sql;
+        }
+    }
+}
+
+fn first_line(statement: &str) -> String {
+    skip_comments(statement)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(';')
+        .to_owned()
+}
+
+/// A name compared regardless of quotes and case, e.g. `"Users"` and `users`.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '"' | '`' | '[' | ']'))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+struct Words<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Words<'a> {
+    fn peek(&self) -> &'a str {
+        let rest = self.rest.trim_start();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ';')
+            .unwrap_or(rest.len());
+
+        &rest[..end]
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest.trim_start();
+        let word = self.peek();
+
+        if word.is_empty() {
+            // step over punctuation, e.g. the parameter list of a trigger function
+            let c = rest.chars().next()?;
+            self.rest = &rest[c.len_utf8()..];
+            return Some(&rest[..c.len_utf8()]);
+        }
+
+        self.rest = &rest[word.len()..];
+        Some(word)
+    }
+
+    /// Consume the next word if it is `keyword`, regardless of case.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek().eq_ignore_ascii_case(keyword) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume a `KEY = value` modifier, e.g. `DEFINER = CURRENT_USER`.
+    fn assignment(&mut self) -> bool {
+        let mut words = Words { rest: self.rest };
+        let Some(key) = words.next() else {
+            return false;
+        };
+
+        // `KEY=value`, `KEY= value`, `KEY =value` or `KEY = value`
+        let value = match key.split_once('=') {
+            Some((_, value)) => value,
+            None => match words.peek().strip_prefix('=') {
+                Some(value) => {
+                    words.next();
+                    value
+                }
+                None => return false,
+            },
+        };
+
+        if value.is_empty() {
+            words.next();
+        }
+
+        self.rest = words.rest;
+        true
+    }
+
+    /// Consume a possibly quoted and schema-qualified name.
+    fn name(&mut self) -> Option<String> {
+        let rest = self.rest.trim_start();
+        let mut end = 0;
+
+        loop {
+            let part = &rest[end..];
+            let len = match part.chars().next()? {
+                quote @ ('"' | '`') => part[1..].find(quote)? + 2,
+                '[' => part.find(']')? + 1,
+                _ => part
+                    .find(|c: char| c.is_whitespace() || matches!(c, '(' | ';' | '.'))
+                    .unwrap_or(part.len()),
+            };
+
+            end += len;
+
+            if rest[end..].starts_with('.') {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+
+        if end == 0 {
+            return None;
+        }
+
+        self.rest = &rest[end..];
+        Some(rest[..end].to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_created_objects_in_reverse_order() {
+        let statements = [
+            "-- users\nCREATE TABLE users (id BIGINT PRIMARY KEY, email TEXT);",
+            "CREATE UNIQUE INDEX users_email ON users (email);",
+            "CREATE TABLE \"Audit Log\"(id BIGINT);",
+            "CREATE INDEX audit_id ON public.audit (id);",
+            "INSERT INTO users VALUES (1, 'a');",
+            "CREATE OR REPLACE VIEW active_users AS SELECT * FROM users;",
+            "CREATE TABLE IF NOT EXISTS settings (key TEXT);",
+            "CREATE TRIGGER audit_users AFTER INSERT ON users FOR EACH ROW EXECUTE FUNCTION f();",
+            "CREATE EXTENSION pgcrypto;",
+        ];
+
+        let plan = plan("postgres", &statements);
+
+        assert_eq!(
+            plan.drops,
+            [
+                "DROP INDEX IF EXISTS public.audit_id;",
+                "DROP TABLE IF EXISTS \"Audit Log\";",
+                "DROP TABLE IF EXISTS users;",
+            ]
+        );
+        assert_eq!(
+            plan.skipped
+                .iter()
+                .map(|skipped| skipped.statement.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "CREATE OR REPLACE VIEW active_users AS SELECT * FROM users",
+                "CREATE TABLE IF NOT EXISTS settings (key TEXT)",
+                "CREATE EXTENSION pgcrypto",
+            ]
+        );
+    }
+
+    #[test]
+    fn mysql_modifiers_and_indexes() {
+        let statements = [
+            "CREATE ALGORITHM = MERGE DEFINER=`admin`@`%` VIEW `recent` AS SELECT 1;",
+            "CREATE INDEX created_at ON orders (created_at);",
+            "CREATE TRIGGER orders_insert BEFORE INSERT ON orders FOR EACH ROW SET NEW.x = 1;",
+        ];
+
+        let plan = plan("mysql", &statements);
+
+        assert_eq!(
+            plan.drops,
+            [
+                "DROP TRIGGER IF EXISTS orders_insert;",
+                "DROP VIEW IF EXISTS `recent`;",
+            ]
+        );
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(
+            plan.skipped[0].statement,
+            "CREATE INDEX created_at ON orders"
+        );
+    }
+}
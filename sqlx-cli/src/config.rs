@@ -0,0 +1,153 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Name of the optional config file, read from the current directory.
+pub const CONFIG_FILE: &str = ".sqlx-migrate.toml";
+
+/// Default path to the folder containing migrations.
+pub const DEFAULT_SOURCE: &str = "migrations";
+
+/// Settings read from [`CONFIG_FILE`].
+///
+/// Every key is optional; command-line flags and environment variables take precedence.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Path to the folder containing migrations.
+    pub source: Option<String>,
+
+    /// Table used to track applied migrations.
+    pub migration_table: Option<String>,
+
+    /// Whether `add` creates reversible migrations without `-r`.
+    pub default_reversible: Option<bool>,
+
+    /// File name template for migrations created by `add`.
+    pub filename_template: Option<String>,
+}
+
+/// Environment variable overriding [`Config::default_reversible`].
+pub const DEFAULT_REVERSIBLE_ENV: &str = "SQLX_DEFAULT_REVERSIBLE";
+
+impl Config {
+    /// Load [`CONFIG_FILE`] from the current directory, if it exists.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Path::new(CONFIG_FILE);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("failed to read {CONFIG_FILE}"))?;
+
+        toml::from_str(&contents).with_context(|| format!("failed to parse {CONFIG_FILE}"))
+    }
+
+    /// Resolve the migrations folder from the `--source` flag, the config file or the default.
+    pub fn resolve_source(&self, flag: Option<&str>) -> Resolved<String> {
+        match (flag, &self.source) {
+            (Some(source), _) => Resolved::new(source, Provenance::Flag),
+            (None, Some(source)) => Resolved::new(source, Provenance::Config),
+            (None, None) => Resolved::new(DEFAULT_SOURCE, Provenance::Default),
+        }
+    }
+
+    /// Resolve the migrations table from the `--migration-table` flag, the config file or the default.
+    pub fn resolve_migration_table(&self, flag: Option<&str>) -> Resolved<String> {
+        match (flag, &self.migration_table) {
+            (Some(table), _) => Resolved::new(table, Provenance::Flag),
+            (None, Some(table)) => Resolved::new(table, Provenance::Config),
+            (None, None) => Resolved::new(
+                sqlx::migrate::DEFAULT_MIGRATION_TABLE,
+                Provenance::Default,
+            ),
+        }
+    }
+
+    /// Resolve whether `add` creates a reversible migration from the `-r`/`--no-reversible`
+    /// flags, the [`DEFAULT_REVERSIBLE_ENV`] variable, the config file or the default.
+    ///
+    /// Only decides the type of the first migration; later ones always match it.
+    pub fn resolve_reversible(&self, flag: Option<bool>) -> anyhow::Result<Resolved<bool>> {
+        let (value, provenance) = match (flag, std::env::var(DEFAULT_REVERSIBLE_ENV)) {
+            (Some(reversible), _) => (reversible, Provenance::Flag),
+            (None, Ok(var)) => {
+                let reversible = match var.to_ascii_lowercase().as_str() {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => anyhow::bail!(
+                        "{DEFAULT_REVERSIBLE_ENV} must be `true` or `false`, got {var:?}"
+                    ),
+                };
+                (reversible, Provenance::Env(DEFAULT_REVERSIBLE_ENV))
+            }
+            (None, Err(_)) => match self.default_reversible {
+                Some(reversible) => (reversible, Provenance::Config),
+                None => (false, Provenance::Default),
+            },
+        };
+
+        Ok(Resolved { value, provenance })
+    }
+
+    /// Prefer the `--migration-table` flag, falling back to the config file.
+    ///
+    /// `None` means the library default applies.
+    pub fn migration_table(&self, flag: Option<String>) -> Option<String> {
+        flag.or_else(|| self.migration_table.clone())
+    }
+
+    /// Prefer the `--filename-template` flag, falling back to the config file.
+    pub fn filename_template(&self, flag: Option<String>) -> Option<String> {
+        flag.or_else(|| self.filename_template.clone())
+    }
+}
+
+/// Where a resolved setting came from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Provenance {
+    Flag,
+    Env(&'static str),
+    Config,
+    /// The versioning scheme recorded in the migrations directory.
+    Marker,
+    /// The database URL stored in the OS keyring.
+    #[cfg(feature = "keyring")]
+    Keyring,
+    Default,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provenance::Flag => f.write_str("flag"),
+            Provenance::Env(var) => write!(f, "env: {var}"),
+            Provenance::Config => write!(f, "config: {CONFIG_FILE}"),
+            Provenance::Marker => write!(f, "marker: {}", crate::migrate::ORDERING_MARKER),
+            #[cfg(feature = "keyring")]
+            Provenance::Keyring => f.write_str("keyring"),
+            Provenance::Default => f.write_str("default"),
+        }
+    }
+}
+
+/// A setting together with where it came from.
+#[derive(Debug, Clone)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub provenance: Provenance,
+}
+
+impl Resolved<String> {
+    fn new(value: &str, provenance: Provenance) -> Self {
+        Self {
+            value: value.to_owned(),
+            provenance,
+        }
+    }
+}
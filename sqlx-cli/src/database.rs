@@ -1,11 +1,17 @@
 use crate::migrate;
-use crate::opt::ConnectOpts;
+use crate::opt::{ConnectOpts, RunOpts};
 use console::style;
-use promptly::{prompt, ReadlineError};
 use sqlx::any::Any;
 use sqlx::migrate::MigrateDatabase;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::SqliteConnectOptions;
+use std::path::PathBuf;
+#[cfg(feature = "sqlite")]
+use std::{path::Component, str::FromStr};
 
 pub async fn create(connect_opts: &ConnectOpts) -> anyhow::Result<()> {
+    print_sqlite_file(connect_opts)?;
+
     // NOTE: only retry the idempotent action.
     // We're assuming that if this succeeds, then any following operations should also succeed.
     let exists = crate::retry_connect_errors(connect_opts, Any::database_exists).await?;
@@ -24,6 +30,8 @@ pub async fn create(connect_opts: &ConnectOpts) -> anyhow::Result<()> {
 }
 
 pub async fn drop(connect_opts: &ConnectOpts, confirm: bool, force: bool) -> anyhow::Result<()> {
+    print_sqlite_file(connect_opts)?;
+
     if confirm && !ask_to_continue_drop(connect_opts.required_db_url()?) {
         return Ok(());
     }
@@ -56,30 +64,59 @@ pub async fn reset(
 
 pub async fn setup(migration_source: &str, connect_opts: &ConnectOpts, migration_table: Option<String>) -> anyhow::Result<()> {
     create(connect_opts).await?;
-    migrate::run(migration_source, connect_opts, false, false, None, migration_table).await
+    migrate::run(
+        migration_source,
+        connect_opts,
+        migration_table,
+        &RunOpts::default(),
+    )
+    .await
 }
 
-fn ask_to_continue_drop(db_url: &str) -> bool {
-    loop {
-        let r: Result<String, ReadlineError> =
-            prompt(format!("Drop database at {}? (y/n)", style(db_url).cyan()));
-        match r {
-            Ok(response) => {
-                if response == "n" || response == "N" {
-                    return false;
-                } else if response == "y" || response == "Y" {
-                    return true;
-                } else {
-                    println!(
-                        "Response not recognized: {}\nPlease type 'y' or 'n' and press enter.",
-                        response
-                    );
-                }
-            }
-            Err(e) => {
-                println!("{e}");
-                return false;
-            }
-        }
+/// Print the absolute path of the SQLite database file, if the URL names one.
+///
+/// Relative paths are resolved against the current directory, so the same URL can refer to
+/// different files depending on where the command is run from.
+fn print_sqlite_file(connect_opts: &ConnectOpts) -> anyhow::Result<()> {
+    if let Some(path) = sqlite_file(connect_opts.required_db_url()?)? {
+        println!(
+            "Using SQLite database file {}",
+            style(path.display()).cyan()
+        );
+    }
+
+    Ok(())
+}
+
+/// The absolute path of the SQLite database file named by `db_url`.
+///
+/// Returns `None` for in-memory databases and URLs of other databases.
+#[cfg(feature = "sqlite")]
+fn sqlite_file(db_url: &str) -> anyhow::Result<Option<PathBuf>> {
+    if !db_url.starts_with("sqlite:") {
+        return Ok(None);
     }
+
+    let opts = SqliteConnectOptions::from_str(db_url)?;
+
+    if opts.is_in_memory() {
+        return Ok(None);
+    }
+
+    let path = std::env::current_dir()?.join(opts.get_filename());
+
+    Ok(Some(
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect(),
+    ))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_file(_db_url: &str) -> anyhow::Result<Option<PathBuf>> {
+    Ok(None)
+}
+
+fn ask_to_continue_drop(db_url: &str) -> bool {
+    crate::ask_to_continue(&format!("Drop database at {}?", style(db_url).cyan()))
 }
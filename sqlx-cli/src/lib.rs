@@ -1,13 +1,18 @@
+use std::fmt;
 use std::io;
 use std::time::Duration;
 
 use anyhow::Result;
 use futures::{Future, TryFutureExt};
+use promptly::{prompt, ReadlineError};
 
-use sqlx::{AnyConnection, Connection};
+use sqlx::{AnyConnection, Connection, Executor};
 
+use crate::config::Config;
 use crate::opt::{Command, ConnectOpts, DatabaseCommand, MigrateCommand};
 
+mod cleanup;
+mod config;
 mod database;
 mod metadata;
 // mod migration;
@@ -15,64 +20,466 @@ mod metadata;
 #[cfg(feature = "completions")]
 mod completions;
 mod migrate;
+mod notify;
 mod opt;
 mod prepare;
+mod schema;
 
-pub use crate::opt::Opt;
+pub use crate::migrate::{info_with_conn, revert_with_conn, run_with_conn};
+pub use crate::opt::{ColorMode, DisplayTimeZone, InfoOpts, Opt, RevertOpts, RunOpts};
 
 pub async fn run(opt: Opt) -> Result<()> {
+    opt.color.apply();
+
+    // before anything reads the URL, including `migrate --print-config`
+    #[cfg(feature = "keyring")]
+    let opt = {
+        let mut opt = opt;
+        if let Some(connect_opts) = opt.command.connect_opts_mut() {
+            connect_opts.resolve_keyring()?;
+        }
+        opt
+    };
+
+    let config = Config::load()?;
+
     match opt.command {
+        Command::Migrate(migrate) if migrate.print_config => {
+            migrate::print_config(&config, &migrate.command)?
+        }
         Command::Migrate(migrate) => match migrate.command {
+            MigrateCommand::Init {
+                source,
+                config: write_config,
+                force,
+            } => migrate::init(&source.resolve(&config), write_config, force)?,
+            MigrateCommand::Add {
+                source,
+                dir,
+                description,
+                sequential,
+                timestamp,
+                from_template_dir: Some(template_dir),
+                migration_table,
+                ..
+            } => {
+                migrate::add_from_template(
+                    &source.resolve(&config),
+                    dir.as_deref(),
+                    &description,
+                    &template_dir,
+                    sequential,
+                    timestamp,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
             MigrateCommand::Add {
                 source,
+                dir,
                 description,
                 reversible,
+                no_reversible,
                 sequential,
                 timestamp,
+                with_seed,
+                filename_template,
+                seeds,
+                from_template_dir: None,
+                with_build_script,
+                edit,
                 migration_table,
-            } => migrate::add(&source, &description, reversible, sequential, timestamp, migration_table).await?,
+            } => {
+                let flag = reversible
+                    .then_some(true)
+                    .or(no_reversible.then_some(false));
+                let reversible = config.resolve_reversible(flag)?.value;
+
+                migrate::add(
+                    &source.resolve(&config),
+                    dir.as_deref(),
+                    &description,
+                    reversible,
+                    sequential,
+                    timestamp,
+                    with_seed.then_some(seeds.seed_source.as_str()),
+                    config.filename_template(filename_template).as_deref(),
+                    with_build_script,
+                    edit,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::NextVersion {
+                source,
+                timestamp,
+                sequential,
+                verbose,
+            } => {
+                migrate::next_version(&source.resolve(&config), sequential, timestamp, verbose)
+                    .await?
+            }
+            MigrateCommand::ListVersions {
+                source,
+                applied,
+                pending,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::list_versions(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    applied,
+                    pending,
+                )
+                .await?
+            }
             MigrateCommand::Run {
                 source,
-                dry_run,
-                ignore_missing,
                 connect_opts,
-                target_version,
                 migration_table,
+                run_opts,
             } => {
                 migrate::run(
-                    &source,
+                    &source.resolve(&config),
                     &connect_opts,
-                    dry_run,
-                    *ignore_missing,
-                    target_version,
-                    migration_table,
+                    config.migration_table(migration_table),
+                    &run_opts,
                 )
                 .await?
             }
             MigrateCommand::Revert {
                 source,
-                dry_run,
-                ignore_missing,
                 connect_opts,
-                target_version,
                 migration_table,
+                revert_opts,
             } => {
                 migrate::revert(
-                    &source,
+                    &source.resolve(&config),
                     &connect_opts,
+                    config.migration_table(migration_table),
+                    &revert_opts,
+                )
+                .await?
+            }
+            MigrateCommand::Info {
+                source,
+                connect_opts,
+                migration_table,
+                info_opts,
+            } => {
+                migrate::info(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    &info_opts,
+                )
+                .await?
+            }
+            MigrateCommand::Seed {
+                seeds,
+                version,
+                all,
+                dry_run,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::seed(
+                    &seeds.seed_source,
+                    version,
+                    all,
                     dry_run,
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::Snapshot {
+                name,
+                connect_opts,
+                migration_table,
+                force,
+            } => {
+                migrate::snapshot(
+                    &name,
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    force,
+                )
+                .await?
+            }
+            MigrateCommand::RollbackToSnapshot {
+                name,
+                source,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::rollback_to_snapshot(
+                    &name,
+                    &source.resolve(&config),
+                    &connect_opts,
+                    dry_run,
+                    !confirmation.yes,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::Rerun {
+                source,
+                version,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::rerun(
+                    &source.resolve(&config),
+                    version,
+                    &connect_opts,
+                    dry_run,
+                    !confirmation.yes,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::ApplyFile {
+                path,
+                dry_run,
+                no_record,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::apply_file(
+                    &path,
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    dry_run,
+                    !no_record,
+                )
+                .await?
+            }
+            MigrateCommand::Audit {
+                source,
+                connect_opts,
+                migration_table,
+                json,
+            } => {
+                migrate::audit(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    json,
+                )
+                .await?
+            }
+            MigrateCommand::Verify {
+                source,
+                connect_opts,
+                migration_table,
+                ignore_missing,
+            } => {
+                migrate::verify(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
                     *ignore_missing,
-                    target_version,
-                    migration_table,
                 )
                 .await?
             }
-            MigrateCommand::Info {
+            MigrateCommand::RepairChecksums {
                 source,
                 connect_opts,
                 migration_table,
-            } => migrate::info(&source, &connect_opts, migration_table).await?,
-            MigrateCommand::BuildScript { source, force } => migrate::build_script(&source, force)?,
+                checksum_ignore_comments,
+                dry_run,
+            } => {
+                migrate::repair_checksums(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    checksum_ignore_comments,
+                    dry_run,
+                )
+                .await?
+            }
+            MigrateCommand::FixChecksums {
+                source,
+                checksum_ignore_comments,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::fix_checksums(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    checksum_ignore_comments,
+                    dry_run,
+                    !confirmation.yes,
+                )
+                .await?
+            }
+            MigrateCommand::Fingerprint {
+                connect_opts,
+                migration_table,
+                json,
+            } => {
+                migrate::fingerprint(&connect_opts, config.migration_table(migration_table), json)
+                    .await?
+            }
+            MigrateCommand::PrintTableDdl {
+                connect_opts,
+                migration_table,
+                index_installed_on,
+            } => migrate::print_table_ddl(
+                &connect_opts,
+                config.migration_table(migration_table),
+                index_installed_on,
+            )?,
+            MigrateCommand::ExportHistory {
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::export_history(&connect_opts, config.migration_table(migration_table))
+                    .await?
+            }
+            MigrateCommand::Stats {
+                connect_opts,
+                migration_table,
+                top,
+                json,
+            } => {
+                migrate::stats(
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    top,
+                    json,
+                )
+                .await?
+            }
+            MigrateCommand::FindTables {
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::find_tables(&connect_opts, config.migration_table(migration_table)).await?
+            }
+            MigrateCommand::DiffDirs { dir_a, dir_b, json } => {
+                migrate::diff_dirs(&dir_a, &dir_b, json).await?
+            }
+            MigrateCommand::Generate {
+                description,
+                source,
+                from_database,
+                timestamp,
+                sequential,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::generate(
+                    &source.resolve(&config),
+                    &description,
+                    from_database,
+                    &connect_opts,
+                    sequential,
+                    timestamp,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::Ack {
+                version,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::ack(
+                    version,
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::CleanupDirty {
+                source,
+                version,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::cleanup_dirty(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                    version,
+                    dry_run,
+                    !confirmation.yes,
+                )
+                .await?
+            }
+            MigrateCommand::DropSchema {
+                confirmation,
+                connect_opts,
+            } => migrate::drop_schema(&connect_opts, !confirmation.yes).await?,
+            MigrateCommand::TestReversibility {
+                source,
+                schema_equal,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::test_reversibility(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    schema_equal,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::Convert {
+                source,
+                to,
+                files_only,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::convert(
+                    &source.resolve(&config),
+                    to,
+                    (!files_only).then_some(&connect_opts),
+                    dry_run,
+                    !confirmation.yes,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::Squash {
+                source,
+                before,
+                description,
+                dry_run,
+                confirmation,
+                connect_opts,
+                migration_table,
+            } => {
+                migrate::squash(
+                    &source.resolve(&config),
+                    before,
+                    &description,
+                    &connect_opts,
+                    dry_run,
+                    !confirmation.yes,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
+            MigrateCommand::BuildScript { source, force } => {
+                migrate::build_script(&source.resolve(&config), force)?
+            }
         },
 
         Command::Database(database) => match database.command {
@@ -88,12 +495,28 @@ pub async fn run(opt: Opt) -> Result<()> {
                 connect_opts,
                 force,
                 migration_table,
-            } => database::reset(&source, &connect_opts, !confirmation.yes, force, migration_table).await?,
+            } => {
+                database::reset(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    !confirmation.yes,
+                    force,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
             DatabaseCommand::Setup {
                 source,
                 connect_opts,
                 migration_table,
-            } => database::setup(&source, &connect_opts, migration_table).await?,
+            } => {
+                database::setup(
+                    &source.resolve(&config),
+                    &connect_opts,
+                    config.migration_table(migration_table),
+                )
+                .await?
+            }
         },
 
         Command::Prepare {
@@ -110,9 +533,95 @@ pub async fn run(opt: Opt) -> Result<()> {
     Ok(())
 }
 
+/// Returned by a command which succeeded, but whose outcome is reported through a non-zero
+/// exit status, e.g. `sqlx migrate info --exit-code`. Nothing is printed for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitStatus {}
+
+/// Ask the user a yes/no question on the terminal, re-prompting until the answer is recognized.
+fn ask_to_continue(question: &str) -> bool {
+    loop {
+        let r: Result<String, ReadlineError> = prompt(format!("{question} (y/n)"));
+        match r {
+            Ok(response) => {
+                if response == "n" || response == "N" {
+                    return false;
+                } else if response == "y" || response == "Y" {
+                    return true;
+                } else {
+                    println!(
+                        "Response not recognized: {}\nPlease type 'y' or 'n' and press enter.",
+                        response
+                    );
+                }
+            }
+            Err(e) => {
+                println!("{e}");
+                return false;
+            }
+        }
+    }
+}
+
 /// Attempt to connect to the database server, retrying up to `ops.connect_timeout`.
+///
+/// With `--target-schema`, the connection is then moved to that schema.
 async fn connect(opts: &ConnectOpts) -> anyhow::Result<AnyConnection> {
-    retry_connect_errors(opts, AnyConnection::connect).await
+    connect_to_target_schema(opts, true).await
+}
+
+/// Like [`connect`], but without creating the `--target-schema`, for commands which must not
+/// change the database.
+async fn connect_read_only(opts: &ConnectOpts) -> anyhow::Result<AnyConnection> {
+    connect_to_target_schema(opts, false).await
+}
+
+async fn connect_to_target_schema(
+    opts: &ConnectOpts,
+    create_schema: bool,
+) -> anyhow::Result<AnyConnection> {
+    let mut conn = retry_connect_errors(opts, AnyConnection::connect).await?;
+
+    if let Some(schema) = &opts.target_schema {
+        if let Err(e) = use_schema(&mut conn, schema, create_schema).await {
+            let _ = conn.close().await;
+            return Err(e);
+        }
+    }
+
+    Ok(conn)
+}
+
+/// Make `schema` the only schema on the `search_path`, so that unqualified names, including the
+/// migrations table, refer to it. The schema is created first if `create` is set and it does not
+/// exist yet.
+async fn use_schema(conn: &mut AnyConnection, schema: &str, create: bool) -> anyhow::Result<()> {
+    if conn.backend_name() != "PostgreSQL" {
+        anyhow::bail!("--target-schema is only supported on PostgreSQL");
+    }
+
+    let schema = quote_ident(schema);
+    if create {
+        conn.execute(&*format!("CREATE SCHEMA IF NOT EXISTS {schema}"))
+            .await?;
+    }
+    conn.execute(&*format!("SET search_path TO {schema}"))
+        .await?;
+
+    Ok(())
+}
+
+/// Quote a PostgreSQL identifier.
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
 }
 
 /// Attempt an operation that may return errors like `ConnectionRefused`,
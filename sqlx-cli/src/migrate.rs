@@ -1,38 +1,163 @@
-use crate::opt::ConnectOpts;
+use crate::cleanup;
+use crate::config::{Config, Provenance, CONFIG_FILE};
+use crate::notify::Notifier;
+use crate::opt::{
+    ConnectOpts, DisplayTimeZone, InfoOpts, MaintenanceFlag, MigrateCommand, NotifyEvent,
+    RevertOpts, RunOpts, TargetVersion, Versioning,
+};
+use crate::schema;
 use anyhow::{bail, Context};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
 use console::style;
-use sqlx::migrate::{AppliedMigration, Migrate, MigrateError, MigrationType, Migrator};
-use sqlx::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::migrate::{
+    AppliedMigration, DirtyMigration, DirtyRecord, Migrate, MigrateDatabase, MigrateError,
+    Migration, MigrationType, Migrator, DIALECTS,
+};
+use sqlx::{AnyConnection, Connection, Executor};
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Write;
 use std::fs::{self, File};
-use std::path::Path;
-use std::time::Duration;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-fn create_file(
-    migration_source: &str,
+/// File name of a migration created by `add`, unless `--filename-template` says otherwise.
+const DEFAULT_FILENAME_TEMPLATE: &str = "{version}_{description}{type}.sql";
+
+/// The placeholders of a filename template.
+const FILENAME_PLACEHOLDERS: [&str; 6] = [
+    "{version}",
+    "{description}",
+    "{slug}",
+    "{date}",
+    "{type}",
+    "{suffix}",
+];
+
+/// `description` in lowercase, with every run of other characters than letters and digits
+/// replaced by a single underscore.
+fn slug(description: &str) -> String {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Render a migration file name from `template`, and check that the migrator reads it back as
+/// the same version and type.
+///
+/// `{version}` is `file_prefix`. `{description}` is the description with spaces replaced by
+/// underscores, and `{slug}` its [`slug`]. `{date}` is today's UTC date as `YYYYMMDD`. `{type}`
+/// is the type suffix without the extension, e.g. `.up`, while `{suffix}` includes it, e.g.
+/// `.up.sql`.
+fn render_file_name(
+    template: &str,
     file_prefix: &str,
     description: &str,
     migration_type: MigrationType,
-) -> anyhow::Result<()> {
-    use std::path::PathBuf;
+) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        template.starts_with("{version}_"),
+        "filename template {template:?} must start with `{{version}}_`"
+    );
+
+    let mut file_name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        file_name.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            bail!("filename template {template:?} has an unclosed `{{`");
+        };
+        let value = match &rest[start + 1..end] {
+            "version" => file_prefix.to_owned(),
+            "description" => description.replace(' ', "_"),
+            "slug" => slug(description),
+            "date" => now().format("%Y%m%d").to_string(),
+            "type" => migration_type.suffix().trim_end_matches(".sql").to_owned(),
+            "suffix" => migration_type.suffix().to_owned(),
+            name => bail!(
+                "filename template {template:?} has an unknown placeholder `{{{name}}}`; \
+                 expected one of {}",
+                FILENAME_PLACEHOLDERS.join(", ")
+            ),
+        };
+        file_name.push_str(&value);
+
+        rest = &rest[end + 1..];
+    }
+    file_name.push_str(rest);
+
+    // mirrors how the migrator resolves the migrations directory
+    let round_trips = !file_name.contains(['/', '\\'])
+        && file_name.ends_with(".sql")
+        && file_name.split_once('_').is_some_and(|(version, rest)| {
+            version.parse::<i64>().ok() == file_prefix.parse().ok()
+                && MigrationType::from_filename(rest) == migration_type
+        });
 
-    let mut file_name = file_prefix.to_string();
-    file_name.push_str("_");
-    file_name.push_str(&description.replace(' ', "_"));
-    file_name.push_str(migration_type.suffix());
+    anyhow::ensure!(
+        round_trips,
+        "filename template {template:?} produces {file_name:?}, which would not be read back \
+         as version {file_prefix} ending in `{}`",
+        migration_type.suffix()
+    );
+
+    Ok(file_name)
+}
+
+/// The migrations directories `migration_source` lists. Several may be given separated like in
+/// `PATH` (by `:`, or `;` on Windows), e.g. the per-module migrations of a monorepo, whose
+/// migrations are applied in one global version order.
+fn source_dirs(migration_source: &str) -> Vec<PathBuf> {
+    std::env::split_paths(migration_source).collect()
+}
+
+/// Fail if `migration_source` lists several directories, for commands which rewrite the
+/// migrations of a single one.
+fn ensure_single_source(migration_source: &str, command: &str) -> anyhow::Result<()> {
+    if source_dirs(migration_source).len() > 1 {
+        bail!(
+            "`migrate {command}` works on a single migrations directory, but the source lists \
+             several: {migration_source}"
+        );
+    }
+
+    Ok(())
+}
+
+/// The directory `add` creates a migration in: `--dir`, which must be one of the directories
+/// `migration_source` lists, or else its only directory.
+fn add_target_dir<'a>(migration_source: &'a str, dir: Option<&'a str>) -> anyhow::Result<&'a str> {
+    let dirs = source_dirs(migration_source);
+
+    match dir {
+        Some(dir) if dirs.iter().any(|d| d == Path::new(dir)) => Ok(dir),
+        Some(dir) => {
+            bail!("--dir {dir} is not one of the migrations directories in {migration_source}")
+        }
+        None if dirs.len() > 1 => bail!(
+            "the source lists {} migrations directories; pass --dir to choose the one to create \
+             the migration in",
+            dirs.len()
+        ),
+        None => Ok(migration_source),
+    }
+}
 
-    let mut path = PathBuf::new();
-    path.push(migration_source);
-    path.push(&file_name);
+fn create_file(migration_source: &str, file_name: &str, contents: &str) -> anyhow::Result<()> {
+    let path = Path::new(migration_source).join(file_name);
 
     println!("Creating {}", style(path.display()).cyan());
 
     let mut file = File::create(&path).context("Failed to create migration file")?;
 
-    std::io::Write::write_all(&mut file, migration_type.file_content().as_bytes())?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())?;
 
     Ok(())
 }
@@ -42,9 +167,25 @@ enum MigrationOrdering {
     Sequential(String),
 }
 
+/// Environment variable overriding the current time used for timestamp versions, as an
+/// RFC 3339 timestamp. Only honored in debug builds, to make tests deterministic.
+const FAKE_NOW_VAR: &str = "SQLX_MIGRATE_FAKE_NOW";
+
+fn now() -> DateTime<Utc> {
+    if cfg!(debug_assertions) {
+        if let Ok(fake_now) = std::env::var(FAKE_NOW_VAR) {
+            return DateTime::parse_from_rfc3339(&fake_now)
+                .unwrap_or_else(|e| panic!("invalid {FAKE_NOW_VAR} {fake_now:?}: {e}"))
+                .with_timezone(&Utc);
+        }
+    }
+
+    Utc::now()
+}
+
 impl MigrationOrdering {
     fn timestamp() -> MigrationOrdering {
-        Self::Timestamp(Utc::now().format("%Y%m%d%H%M%S").to_string())
+        Self::Timestamp(now().format("%Y%m%d%H%M%S").to_string())
     }
 
     fn sequential(version: i64) -> MigrationOrdering {
@@ -58,19 +199,30 @@ impl MigrationOrdering {
         }
     }
 
-    fn infer(sequential: bool, timestamp: bool, migrator: &Migrator) -> Self {
+    fn mode(&self) -> &'static str {
+        self.versioning().name()
+    }
+
+    fn versioning(&self) -> Versioning {
+        match self {
+            MigrationOrdering::Timestamp(_) => Versioning::Timestamp,
+            MigrationOrdering::Sequential(_) => Versioning::Sequential,
+        }
+    }
+
+    fn infer(sequential: bool, timestamp: bool, migrations: &[Migration]) -> Self {
         match (timestamp, sequential) {
             (true, true) => panic!("Impossible to specify both timestamp and sequential mode"),
             (true, false) => MigrationOrdering::timestamp(),
             (false, true) => MigrationOrdering::sequential(
-                migrator
+                migrations
                     .iter()
                     .last()
                     .map_or(1, |last_migration| last_migration.version + 1),
             ),
             (false, false) => {
                 // inferring the naming scheme
-                let migrations = migrator
+                let migrations = migrations
                     .iter()
                     .filter(|migration| migration.migration_type.is_up_migration())
                     .rev()
@@ -100,75 +252,166 @@ impl MigrationOrdering {
     }
 }
 
-pub async fn add(
-    migration_source: &str,
-    description: &str,
-    reversible: bool,
-    sequential: bool,
-    timestamp: bool,
-    migration_table: Option<String>,
-) -> anyhow::Result<()> {
-    fs::create_dir_all(migration_source).context("Unable to create migrations directory")?;
-
-    let migrator = Migrator::new(Path::new(migration_source), migration_table).await?;
-    // Type of newly created migration will be the same as the first one
-    // or reversible flag if this is the first migration
-    let migration_type = MigrationType::infer(&migrator, reversible);
+/// A file in the migrations directory recording the versioning scheme, written by the first
+/// `add` so that later migrations keep using it instead of inferring it again.
+pub const ORDERING_MARKER: &str = ".sqlx-ordering";
 
-    let ordering = MigrationOrdering::infer(sequential, timestamp, &migrator);
-    let file_prefix = ordering.file_prefix();
+/// The versioning scheme recorded in the migrations directory, if any.
+pub fn read_ordering_marker(migration_source: &str) -> anyhow::Result<Option<Versioning>> {
+    let path = Path::new(migration_source).join(ORDERING_MARKER);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
 
-    if migration_type.is_reversible() {
-        create_file(
-            migration_source,
-            &file_prefix,
-            description,
-            MigrationType::ReversibleUp,
-        )?;
-        create_file(
-            migration_source,
-            &file_prefix,
-            description,
-            MigrationType::ReversibleDown,
-        )?;
-    } else {
-        create_file(
-            migration_source,
-            &file_prefix,
-            description,
-            MigrationType::Simple,
-        )?;
+    match contents.trim() {
+        "timestamp" => Ok(Some(Versioning::Timestamp)),
+        "sequential" => Ok(Some(Versioning::Sequential)),
+        other => bail!(
+            "{} must contain `timestamp` or `sequential`, got {other:?}",
+            path.display()
+        ),
     }
+}
 
-    // if the migrations directory is empty
-    let has_existing_migrations = fs::read_dir(migration_source)
-        .map(|mut dir| dir.next().is_some())
-        .unwrap_or(false);
+fn write_ordering_marker(migration_source: &str, versioning: Versioning) -> anyhow::Result<()> {
+    let path = Path::new(migration_source).join(ORDERING_MARKER);
+    fs::write(&path, format!("{}\n", versioning.name()))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
 
-    if !has_existing_migrations {
-        let quoted_source = if migration_source != "migrations" {
-            format!("{migration_source:?}")
-        } else {
-            "".to_string()
-        };
+/// A file in the migrations directory pre-approving edits made to migrations after they were
+/// applied, mapping each version to the checksum(s) a database may have recorded for it.
+const CHECKSUM_ALLOWLIST: &str = "checksum-allowlist.toml";
 
-        // Provide a link to the current version in case the details change.
-        // Patch version is deliberately omitted.
-        let version = if let (Some(major), Some(minor)) = (
-            // Don't fail if we're not being built by Cargo
-            option_env!("CARGO_PKG_VERSION_MAJOR"),
-            option_env!("CARGO_PKG_VERSION_MINOR"),
-        ) {
-            format!("{major}.{minor}")
-        } else {
-            // If a version isn't available, "latest" is fine.
-            "latest".to_string()
+/// The contents of [`CHECKSUM_ALLOWLIST`], or empty if there is no such file.
+#[derive(Debug, Default)]
+struct ChecksumAllowlist(HashMap<i64, Vec<String>>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AllowedChecksums {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ChecksumAllowlist {
+    /// Load and merge the allowlists of every migrations directory in `migration_source`.
+    fn load(migration_source: &str) -> anyhow::Result<Self> {
+        let mut allowlist = Self::default();
+        for dir in source_dirs(migration_source) {
+            allowlist.load_dir(&dir)?;
+        }
+
+        Ok(allowlist)
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(CHECKSUM_ALLOWLIST);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
         };
 
-        print!(
-            r#"
-Congratulations on creating your first migration!
+        let entries: BTreeMap<String, AllowedChecksums> = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        for (version, checksums) in entries {
+            let Ok(version) = version.parse::<i64>() else {
+                bail!("{}: {version:?} is not a migration version", path.display());
+            };
+
+            let checksums = match checksums {
+                AllowedChecksums::One(checksum) => vec![checksum],
+                AllowedChecksums::Many(checksums) => checksums,
+            };
+
+            let checksums = checksums
+                .into_iter()
+                .map(|checksum| {
+                    if checksum.is_empty() || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+                        bail!(
+                            "{}: checksum {checksum:?} for version {version} is not hex",
+                            path.display()
+                        );
+                    }
+                    Ok(checksum.to_ascii_lowercase())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
 
+            self.0.entry(version).or_default().extend(checksums);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the applied migration's checksum was approved in place of the local one.
+    fn allows(&self, version: i64, applied_checksum: &[u8]) -> bool {
+        self.0
+            .get(&version)
+            .is_some_and(|checksums| checksums.contains(&short_checksum(applied_checksum)))
+    }
+
+    /// Whether the local migration differs from the applied one without being allowlisted.
+    ///
+    /// An empty applied checksum is unknown rather than different: it was recorded before the
+    /// migrations table had checksums.
+    fn is_mismatch(&self, migration: &Migration, applied: &AppliedMigration) -> bool {
+        !applied.checksum.is_empty()
+            && migration.checksum != applied.checksum
+            && !self.allows(migration.version, &applied.checksum)
+    }
+}
+
+/// The `--sequential` and `--timestamp` flags, with the scheme recorded in the migrations
+/// directory applied; it is an error for a flag to contradict it.
+fn ordering_flags(
+    migration_source: &str,
+    sequential: bool,
+    timestamp: bool,
+) -> anyhow::Result<(bool, bool)> {
+    match read_ordering_marker(migration_source)? {
+        Some(Versioning::Sequential) if timestamp => bail!(
+            "--timestamp conflicts with the sequential versioning recorded in {}; remove the \
+             file to change the scheme",
+            Path::new(migration_source).join(ORDERING_MARKER).display()
+        ),
+        Some(Versioning::Timestamp) if sequential => bail!(
+            "--sequential conflicts with the timestamp versioning recorded in {}; remove the \
+             file to change the scheme",
+            Path::new(migration_source).join(ORDERING_MARKER).display()
+        ),
+        Some(Versioning::Sequential) => Ok((true, false)),
+        Some(Versioning::Timestamp) => Ok((false, true)),
+        None => Ok((sequential, timestamp)),
+    }
+}
+
+/// Advice on embedding migrations, shared by the `init` and first `add` banners.
+fn embedding_guidance(migration_source: &str) -> String {
+    let quoted_source = if migration_source != "migrations" {
+        format!("{migration_source:?}")
+    } else {
+        "".to_string()
+    };
+
+    // Provide a link to the current version in case the details change.
+    // Patch version is deliberately omitted.
+    let version = if let (Some(major), Some(minor)) = (
+        // Don't fail if we're not being built by Cargo
+        option_env!("CARGO_PKG_VERSION_MAJOR"),
+        option_env!("CARGO_PKG_VERSION_MINOR"),
+    ) {
+        format!("{major}.{minor}")
+    } else {
+        // If a version isn't available, "latest" is fine.
+        "latest".to_string()
+    };
+
+    format!(
+        r#"
 Did you know you can embed your migrations in your application binary?
 On startup, after creating your database connection or pool, add:
 
@@ -179,298 +422,5210 @@ You can create a Cargo build script to work around this with `sqlx migrate build
 
 See: https://docs.rs/sqlx/{version}/sqlx/macro.migrate.html
 "#,
-        );
-    }
-
-    Ok(())
+    )
 }
 
-fn short_checksum(checksum: &[u8]) -> String {
-    let mut s = String::with_capacity(checksum.len() * 2);
-    for b in checksum {
-        write!(&mut s, "{b:02x?}").expect("should not fail to write to str");
-    }
-    s
-}
+pub fn init(migration_source: &str, write_config: bool, force: bool) -> anyhow::Result<()> {
+    ensure_single_source(migration_source, "init")?;
+    let source_path = Path::new(migration_source);
 
-pub async fn info(migration_source: &str, connect_opts: &ConnectOpts, migration_table: Option<String>) -> anyhow::Result<()> {
-    let migrator = Migrator::new(Path::new(migration_source), migration_table).await?;
-    let mut conn = crate::connect(&connect_opts).await?;
+    let is_non_empty = fs::read_dir(source_path)
+        .map(|mut dir| dir.next().is_some())
+        .unwrap_or(false);
 
-    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    if is_non_empty && !force {
+        bail!(
+            "migrations directory `{migration_source}` already exists and is not empty; \
+             use --force to continue anyway"
+        );
+    }
 
-    conn.ensure_migrations_table(migration_table.to_owned()).await?;
+    fs::create_dir_all(source_path).context("Unable to create migrations directory")?;
+    println!("Created {}", style(source_path.display()).cyan());
 
-    let applied_migrations: HashMap<_, _> = conn
-        .list_applied_migrations(migration_table)
-        .await?
-        .into_iter()
-        .map(|m| (m.version, m))
-        .collect();
+    if write_config {
+        anyhow::ensure!(
+            force || !Path::new(CONFIG_FILE).exists(),
+            "{CONFIG_FILE} already exists; use --force to overwrite"
+        );
 
-    for migration in migrator.iter() {
-        if migration.migration_type.is_down_migration() {
-            // Skipping down migrations
-            continue;
-        }
+        let contents = format!(
+            r#"# Settings for `sqlx migrate`; generated by `sqlx migrate init`.
 
-        let applied = applied_migrations.get(&migration.version);
+# Path to the folder containing migrations.
+source = {migration_source:?}
 
-        let (status_msg, mismatched_checksum) = if let Some(applied) = applied {
-            if applied.checksum != migration.checksum {
-                (style("installed (different checksum)").red(), true)
-            } else {
-                (style("installed").green(), false)
-            }
-        } else {
-            (style("pending").yellow(), false)
-        };
+# Table used to track applied migrations.
+migration_table = {table:?}
 
-        println!(
-            "{}/{} {}",
-            style(migration.version).cyan(),
-            status_msg,
-            migration.description
+# Create reversible migrations with `sqlx migrate add` without passing `-r`.
+# default_reversible = true
+
+# File name of migrations created by `sqlx migrate add`. Placeholders: {{version}},
+# {{description}}, {{slug}}, {{date}}, {{type}} and {{suffix}}.
+# filename_template = "{{version}}_{{description}}{{type}}.sql"
+"#,
+            table = sqlx::migrate::DEFAULT_MIGRATION_TABLE,
         );
 
-        if mismatched_checksum {
-            println!(
-                "applied migration had checksum {}",
-                short_checksum(
-                    &applied
-                        .map(|a| a.checksum.clone())
-                        .unwrap_or_else(|| Cow::Owned(vec![]))
-                ),
-            );
-            println!(
-                "local migration has checksum {}",
-                short_checksum(&migration.checksum)
-            )
-        }
+        fs::write(CONFIG_FILE, contents).context("Unable to write config file")?;
+        println!("Created {}", style(CONFIG_FILE).cyan());
     }
 
-    let _ = conn.close().await;
+    print!(
+        r#"
+Your project is ready for migrations!
+
+Create your first migration with:
+
+sqlx migrate add <DESCRIPTION>
+{}"#,
+        embedding_guidance(migration_source)
+    );
 
     Ok(())
 }
 
-fn validate_applied_migrations(
-    applied_migrations: &[AppliedMigration],
-    migrator: &Migrator,
-    ignore_missing: bool,
-) -> Result<(), MigrateError> {
-    if ignore_missing {
-        return Ok(());
+/// Print the settings a migrate subcommand would use, along with where each came from.
+pub fn print_config(config: &Config, command: &MigrateCommand) -> anyhow::Result<()> {
+    let print = |name: &str, value: &str, provenance: Provenance| {
+        println!(
+            "{:<16} {} {}",
+            name,
+            style(value).cyan(),
+            style(format!("({provenance})")).dim()
+        );
+    };
+
+    if let Some(source) = command.source() {
+        let source = config.resolve_source(source.source.as_deref());
+        print("source", &source.value, source.provenance);
     }
 
-    let migrations: HashSet<_> = migrator.iter().map(|m| m.version).collect();
+    if let Some(flag) = command.migration_table() {
+        let table = config.resolve_migration_table(flag);
+        print("migration_table", &table.value, table.provenance);
+    }
 
-    for applied_migration in applied_migrations {
-        if !migrations.contains(&applied_migration.version) {
-            return Err(MigrateError::VersionMissing(applied_migration.version));
+    if let Some(connect_opts) = command.connect_opts() {
+        match &connect_opts.database_url {
+            Some(url) => {
+                let provenance = if std::env::var("DATABASE_URL").as_deref() == Ok(url) {
+                    Provenance::Env("DATABASE_URL")
+                } else {
+                    Provenance::Flag
+                };
+                #[cfg(feature = "keyring")]
+                let provenance = if connect_opts.database_url_from_keyring.is_some() {
+                    Provenance::Keyring
+                } else {
+                    provenance
+                };
+                print("database_url", &mask_password(url), provenance);
+            }
+            None => println!("{:<16} {}", "database_url", style("(not set)").red()),
         }
     }
 
+    if let MigrateCommand::Add {
+        reversible,
+        no_reversible,
+        sequential,
+        timestamp,
+        ..
+    } = command
+    {
+        let flag = reversible
+            .then_some(true)
+            .or(no_reversible.then_some(false));
+        let reversible = config.resolve_reversible(flag)?;
+        print(
+            "reversible",
+            &reversible.value.to_string(),
+            reversible.provenance,
+        );
+
+        let source = command
+            .source()
+            .map(|source| config.resolve_source(source.source.as_deref()).value);
+        let marker = match &source {
+            Some(source) => read_ordering_marker(source)?,
+            None => None,
+        };
+        let (ordering, provenance) = match (sequential, timestamp, marker) {
+            (true, _, _) => ("sequential", Provenance::Flag),
+            (_, true, _) => ("timestamp", Provenance::Flag),
+            (_, _, Some(versioning)) => (versioning.name(), Provenance::Marker),
+            _ => ("inferred", Provenance::Default),
+        };
+        print("ordering", ordering, provenance);
+    }
+
     Ok(())
 }
 
+/// Replace the password in a database URL, if any, so it can be printed.
+fn mask_password(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("********"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
 
-pub async fn run(
+#[allow(clippy::too_many_arguments)]
+pub async fn add(
     migration_source: &str,
-    connect_opts: &ConnectOpts,
-    dry_run: bool,
-    ignore_missing: bool,
-    target_version: Option<i64>,
+    dir: Option<&str>,
+    description: &str,
+    reversible: bool,
+    sequential: bool,
+    timestamp: bool,
+    seed_source: Option<&str>,
+    filename_template: Option<&str>,
+    with_build_script: bool,
+    edit: bool,
     migration_table: Option<String>,
 ) -> anyhow::Result<()> {
-    let migrator = Migrator::new(Path::new(migration_source), migration_table).await?;
-    if let Some(target_version) = target_version {
-        if !migrator.version_exists(target_version) {
-            bail!(MigrateError::VersionNotPresent(target_version));
-        }
+    let target = add_target_dir(migration_source, dir)?;
+    fs::create_dir_all(target).context("Unable to create migrations directory")?;
+
+    // the version follows the migrations of every directory
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table).await?;
+    let is_first_migration = migrator.migrations.is_empty();
+    // Type of newly created migration will be the same as the first one
+    // or reversible flag if this is the first migration
+    let migration_type = MigrationType::infer(&migrator, reversible);
+
+    let (sequential, timestamp) = ordering_flags(target, sequential, timestamp)?;
+    let ordering = MigrationOrdering::infer(sequential, timestamp, &migrator.migrations);
+    let file_prefix = ordering.file_prefix();
+
+    let types: &[MigrationType] = if migration_type.is_reversible() {
+        &[MigrationType::ReversibleUp, MigrationType::ReversibleDown]
+    } else {
+        &[MigrationType::Simple]
+    };
+
+    // render every name first so a bad template leaves nothing behind
+    let template = filename_template.unwrap_or(DEFAULT_FILENAME_TEMPLATE);
+    let files = types
+        .iter()
+        .map(|ty| render_file_name(template, file_prefix, description, *ty).map(|name| (name, ty)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    for (file_name, ty) in &files {
+        create_file(target, file_name, ty.file_content())?;
     }
 
-    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    if is_first_migration && read_ordering_marker(target)?.is_none() {
+        write_ordering_marker(target, ordering.versioning())?;
+    }
 
-    let mut conn = crate::connect(connect_opts).await?;
-    conn.ensure_migrations_table(migration_table.to_owned()).await?;
+    if let Some(seed_source) = seed_source {
+        fs::create_dir_all(seed_source).context("Unable to create seeds directory")?;
 
-    let version = conn.dirty_version(migration_table.to_owned()).await?;
-    if let Some(version) = version {
-        bail!(MigrateError::Dirty(version));
+        let path = Path::new(seed_source).join(format!(
+            "{}_{}.sql",
+            file_prefix,
+            description.replace(' ', "_")
+        ));
+
+        println!("Creating {}", style(path.display()).cyan());
+
+        fs::write(&path, "-- Add seed data here\n").context("Failed to create seed file")?;
     }
 
-    let applied_migrations = conn.list_applied_migrations(migration_table.to_owned()).await?;
-    validate_applied_migrations(&applied_migrations, &migrator, ignore_missing)?;
+    if is_first_migration {
+        print!(
+            "\nCongratulations on creating your first migration!\n{}",
+            embedding_guidance(target)
+        );
 
-    let latest_version = applied_migrations
-        .iter()
-        .max_by(|x, y| x.version.cmp(&y.version))
-        .and_then(|migration| Some(migration.version))
-        .unwrap_or(0);
-    if let Some(target_version) = target_version {
-        if target_version < latest_version {
-            bail!(MigrateError::VersionTooOld(target_version, latest_version));
-        }
+        offer_build_script(target, with_build_script)?;
     }
 
-    let applied_migrations: HashMap<_, _> = applied_migrations
-        .into_iter()
-        .map(|m| (m.version, m))
-        .collect();
+    if edit {
+        // the up migration comes first, as in `types`
+        let paths: Vec<_> = files
+            .iter()
+            .map(|(file_name, _)| Path::new(target).join(file_name))
+            .collect();
 
-    for migration in migrator.iter() {
-        if migration.migration_type.is_down_migration() {
-            // Skipping down migrations
-            continue;
-        }
+        open_in_editor(&paths)?;
+    }
 
-        match applied_migrations.get(&migration.version) {
-            Some(applied_migration) => {
-                if migration.checksum != applied_migration.checksum {
-                    bail!(MigrateError::VersionMismatch(migration.version));
+    Ok(())
+}
+
+/// Open the given files in `$EDITOR`, for `add --edit`, waiting for it to exit.
+///
+/// `$EDITOR` may include arguments, e.g. `code --wait`. If it is not set, only a warning is
+/// printed.
+fn open_in_editor(paths: &[PathBuf]) -> anyhow::Result<()> {
+    let editor = std::env::var("EDITOR").unwrap_or_default();
+    let mut args = editor.split_whitespace();
+
+    let Some(program) = args.next() else {
+        println!(
+            "{} $EDITOR is not set, so the migration was not opened for editing",
+            style("warning:").bold().yellow()
+        );
+        return Ok(());
+    };
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .args(paths)
+        .status()
+        .with_context(|| format!("failed to run the editor {editor:?}"))?;
+
+    if !status.success() {
+        bail!("the editor {editor:?} exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// After the first migration was added to a Cargo project without a build script, create one
+/// if `--with-build-script` was passed, or else offer to when running interactively.
+fn offer_build_script(migration_source: &str, with_build_script: bool) -> anyhow::Result<()> {
+    if !Path::new("Cargo.toml").exists() || Path::new("build.rs").exists() {
+        return Ok(());
+    }
+
+    if with_build_script
+        || (console::user_attended()
+            && crate::ask_to_continue("\nCreate the build script `build.rs` now?"))
+    {
+        build_script(migration_source, false)?;
+    }
+
+    Ok(())
+}
+
+/// Create a migration by copying the files of a template directory into the migrations
+/// directory, replacing `{version}` and `{description}` in their names and contents.
+pub async fn add_from_template(
+    migration_source: &str,
+    dir: Option<&str>,
+    description: &str,
+    template_dir: &str,
+    sequential: bool,
+    timestamp: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let mut templates = Vec::new();
+    collect_template_files(Path::new(template_dir), Path::new(""), &mut templates)
+        .with_context(|| format!("failed to read template directory {template_dir}"))?;
+
+    if !templates.iter().any(|path| is_up_template(path)) {
+        bail!(
+            "template directory {template_dir} must contain an up migration named \
+             `{{version}}_{{description}}.up.sql` or `{{version}}_{{description}}.sql`"
+        );
+    }
+
+    let target = add_target_dir(migration_source, dir)?;
+    fs::create_dir_all(target).context("Unable to create migrations directory")?;
+
+    // the version follows the migrations of every directory
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table).await?;
+    let (sequential, timestamp) = ordering_flags(target, sequential, timestamp)?;
+    let ordering = MigrationOrdering::infer(sequential, timestamp, &migrator.migrations);
+    let version = ordering.file_prefix();
+
+    let substitute = |s: &str, description: &str| {
+        s.replace("{version}", &version)
+            .replace("{description}", description)
+    };
+
+    let files: Vec<_> = templates
+        .iter()
+        .map(|template| {
+            let name = substitute(&template.to_string_lossy(), &description.replace(' ', "_"));
+            (
+                Path::new(template_dir).join(template),
+                Path::new(target).join(name),
+            )
+        })
+        .collect();
+
+    // don't leave a partial bundle behind
+    if let Some((_, path)) = files.iter().find(|(_, path)| path.exists()) {
+        bail!("{} already exists", path.display());
+    }
+
+    for (template, path) in &files {
+        let contents = fs::read_to_string(template)
+            .with_context(|| format!("failed to read template {}", template.display()))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        println!("Creating {}", style(path.display()).cyan());
+
+        fs::write(path, substitute(&contents, description))
+            .context("Failed to create migration file")?;
+    }
+
+    if migrator.migrations.is_empty() && read_ordering_marker(target)?.is_none() {
+        write_ordering_marker(target, ordering.versioning())?;
+    }
+
+    Ok(())
+}
+
+/// Collect the paths of all files below `dir`, relative to the template directory.
+fn collect_template_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_template_files(&entry.path(), &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(())
+}
+
+// Only files at the top of the template directory end up as migrations.
+fn is_up_template(path: &Path) -> bool {
+    let Some(name) = path.to_str() else {
+        return false;
+    };
+
+    path.components().count() == 1
+        && name.starts_with("{version}_")
+        && name.ends_with(".sql")
+        && !name.ends_with(MigrationType::ReversibleDown.suffix())
+}
+
+pub async fn next_version(
+    migration_source: &str,
+    sequential: bool,
+    timestamp: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    // like `add`, treat a missing directory as one without migrations
+    let dirs: Vec<_> = source_dirs(migration_source)
+        .into_iter()
+        .filter(|dir| dir.exists())
+        .collect();
+    let migrations = if !dirs.is_empty() {
+        Migrator::new(dirs, None).await?.migrations.into_owned()
+    } else {
+        Vec::new()
+    };
+
+    let (sequential, timestamp) = ordering_flags(migration_source, sequential, timestamp)?;
+    let ordering = MigrationOrdering::infer(sequential, timestamp, &migrations);
+
+    if verbose {
+        println!("{} {}", ordering.file_prefix(), ordering.mode());
+    } else {
+        println!("{}", ordering.file_prefix());
+    }
+
+    Ok(())
+}
+
+/// Print `<VERSION>\t<DESCRIPTION>` for each local up migration, optionally only those which
+/// have or have not been applied. Used by shell completion scripts; keep the format stable.
+pub async fn list_versions(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    applied: bool,
+    pending: bool,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+
+    let applied_versions: Option<HashSet<_>> = if applied || pending {
+        let migration_table =
+            migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+        // completion must never change the database, so a missing table means nothing applied
+        let mut conn = crate::connect_read_only(connect_opts).await?;
+        let applied_migrations = async {
+            let exists = conn
+                .migrations_table_exists(migration_table.clone())
+                .await?;
+            if !exists {
+                return Ok(Vec::new());
+            }
+
+            conn.list_applied_migrations(migration_table).await
+        }
+        .await;
+        let _ = conn.close().await;
+
+        Some(applied_migrations?.into_iter().map(|m| m.version).collect())
+    } else {
+        None
+    };
+
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+
+        if let Some(applied_versions) = &applied_versions {
+            if applied_versions.contains(&migration.version) != applied {
+                continue;
+            }
+        }
+
+        println!("{}\t{}", migration.version, migration.description);
+    }
+
+    Ok(())
+}
+
+fn short_checksum(checksum: &[u8]) -> String {
+    let mut s = String::with_capacity(checksum.len() * 2);
+    for b in checksum {
+        write!(&mut s, "{b:02x?}").expect("should not fail to write to str");
+    }
+    s
+}
+
+pub async fn info(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    opts: &InfoOpts,
+) -> anyhow::Result<()> {
+    let mut conn = crate::connect(&connect_opts).await?;
+
+    let res = info_with_conn(&mut conn, migration_source, migration_table, opts).await;
+
+    let _ = conn.close().await;
+
+    res
+}
+
+/// Like [`info`], but on an existing connection, e.g. one checked out from a pool.
+///
+/// The connection is left open.
+pub async fn info_with_conn(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &InfoOpts,
+) -> anyhow::Result<()> {
+    if opts.applied_only_json {
+        let migration_table = migration_table
+            .unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+        return print_applied_json(conn, &migration_table, opts.time_zone).await;
+    }
+
+    if let Some(manifest) = &opts.diff_against {
+        let migration_table = migration_table
+            .unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+        return diff_against_manifest(conn, &migration_table, Path::new(manifest), opts.json)
+            .await;
+    }
+
+    let mut migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    migrator.set_checksum_ignore_comments(opts.checksum_ignore_comments);
+    let allowlist = ChecksumAllowlist::load(migration_source)?;
+
+    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    conn.ensure_migrations_table(migration_table.to_owned()).await?;
+
+    if opts.watch_until_clean {
+        return watch_until_clean(conn, &migrator, &migration_table, opts).await;
+    }
+
+    if opts.validate_table {
+        // before listing, which would collapse duplicates or fail on missing values
+        let problems = validate_table(conn, &migration_table).await?;
+        if !problems.is_empty() {
+            for problem in &problems {
+                println!("{} {}", style("corrupt:").bold().red(), problem);
+            }
+
+            bail!(
+                "migrations table {migration_table} is corrupt ({} problems found)",
+                problems.len()
+            );
+        }
+
+        println!("Migrations table {migration_table} is consistent");
+    }
+
+    // a warning only: the listing matters most when the database is broken
+    let dirty = migrator.dirty_migration_direct(conn).await?;
+    match &dirty {
+        // stderr, so that stdout stays valid JSON
+        Some(dirty) if opts.json => eprintln!(
+            "{} the database is dirty: migration {} failed part-way through being applied",
+            style("warning:").bold().yellow(),
+            style(dirty.version).cyan()
+        ),
+        Some(dirty) => print_dirty_migration(dirty, migration_source),
+        None => {}
+    }
+    let is_dirty = dirty.is_some();
+
+    let followups = outstanding_followups(conn, &migration_table).await?;
+    let applied_migrations = conn.list_applied_migrations(migration_table).await?;
+
+    if opts.verbose && !opts.json {
+        // never an error here, as if `--ignore-missing` was always passed
+        let missing = validate_applied_migrations(&applied_migrations, &migrator, true)?;
+        print_missing_migrations(&missing, migration_source);
+    }
+
+    let applied_migrations: HashMap<_, _> = applied_migrations
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    let mut json_entries = Vec::new();
+    let mut any_pending = false;
+    let mut any_mismatched = false;
+
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            // Skipping down migrations
+            continue;
+        }
+
+        if !is_selected(migration, opts) {
+            continue;
+        }
+
+        let applied = applied_migrations.get(&migration.version);
+
+        match applied {
+            Some(applied) => any_mismatched |= allowlist.is_mismatch(migration, applied),
+            None => any_pending = true,
+        }
+
+        if opts.json {
+            let status = match applied {
+                Some(applied) if allowlist.is_mismatch(migration, applied) => {
+                    "installed_different_checksum"
                 }
+                Some(_) => "installed",
+                None => "pending",
+            };
+
+            let mut entry = serde_json::json!({
+                "version": migration.version,
+                "description": migration.description,
+                "status": status,
+            });
+
+            if let Some(applied) = applied.filter(|a| allowlist.is_mismatch(migration, a)) {
+                entry["applied_checksum"] = short_checksum(&applied.checksum).into();
+                entry["local_checksum"] = short_checksum(&migration.checksum).into();
             }
-            None => {
-                let skip = match target_version {
-                    Some(target_version) if migration.version > target_version => true,
-                    _ => false,
-                };
 
-                let elapsed = if dry_run || skip {
-                    Duration::new(0, 0)
-                } else {
-                    conn.apply(migration, migration_table.to_owned()).await?
-                };
-                let text = if skip {
-                    "Skipped"
-                } else if dry_run {
-                    "Can apply"
-                } else {
-                    "Applied"
-                };
+            if followups.contains(&migration.version) {
+                entry["manual_followup"] = migration.manual_followup(dialect(conn))?.into();
+            }
+
+            json_entries.push(entry);
+            continue;
+        }
+
+        let (status_msg, mismatched_checksum) = if let Some(applied) = applied {
+            if allowlist.is_mismatch(migration, applied) {
+                (style("installed (different checksum)").red(), true)
+            } else if applied.checksum.is_empty() {
+                (style("installed (checksum not recorded)").green(), false)
+            } else if applied.checksum != migration.checksum {
+                (style("installed (allowlisted checksum)").green(), false)
+            } else {
+                (style("installed").green(), false)
+            }
+        } else {
+            (style("pending").yellow(), false)
+        };
+
+        println!(
+            "{}/{} {}",
+            style(migration.version).cyan(),
+            status_msg,
+            migration.description
+        );
+
+        if mismatched_checksum {
+            println!(
+                "applied migration had checksum {}",
+                short_checksum(
+                    &applied
+                        .map(|a| a.checksum.clone())
+                        .unwrap_or_else(|| Cow::Owned(vec![]))
+                ),
+            );
+            println!(
+                "local migration has checksum {}",
+                short_checksum(&migration.checksum)
+            )
+        }
+    }
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&json_entries)?);
+    } else if !followups.is_empty() {
+        print_outstanding_followups(&migrator, dialect(conn), &followups)?;
+    }
+
+    if opts.exit_code {
+        if is_dirty && !opts.allow_dirty {
+            bail!(crate::ExitStatus(3));
+        } else if any_mismatched {
+            bail!(crate::ExitStatus(2));
+        } else if any_pending {
+            bail!(crate::ExitStatus(1));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_outstanding_followups(
+    migrator: &Migrator,
+    dialect: &str,
+    followups: &[i64],
+) -> anyhow::Result<()> {
+    println!(
+        "{} {} applied migrations have an outstanding manual follow-up:",
+        style("warning:").bold().yellow(),
+        followups.len()
+    );
+
+    for &version in followups {
+        let migration = migrator
+            .iter()
+            .find(|m| m.version == version && m.migration_type.is_up_migration());
+
+        match migration {
+            Some(migration) => println!(
+                "  {}/{}: {}",
+                style(version).cyan(),
+                migration.description,
+                migration
+                    .manual_followup(dialect)?
+                    .unwrap_or("(the directive was removed)")
+            ),
+            None => println!(
+                "  {}: (the migration no longer exists locally)",
+                style(version).cyan()
+            ),
+        }
+    }
+
+    println!("Acknowledge each with `sqlx migrate ack --version <VERSION>` once done.");
+
+    Ok(())
+}
+
+fn print_dirty_migration(dirty: &DirtyMigration<'_>, migration_source: &str) {
+    println!(
+        "{} the database is dirty: migration {} failed part-way through being applied",
+        style("warning:").bold().yellow(),
+        style(dirty.version).cyan()
+    );
+
+    match dirty.migration {
+        Some(migration) => {
+            println!("  description: {}", migration.description);
+
+            if let Some(path) = migration_file(migration_source, dirty.version) {
+                println!("  file:        {}", path.display());
+            }
+        }
+        None => println!("  the migration no longer exists in {migration_source}"),
+    }
+}
+
+/// The path of the up migration file with the given version, if it can be found.
+fn migration_file(migration_source: &str, version: i64) -> Option<PathBuf> {
+    let prefix = format!("{version}_");
+
+    let mut paths: Vec<_> = source_dirs(migration_source)
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    name.starts_with(&prefix)
+                        && name.ends_with(".sql")
+                        && !name.ends_with(".down.sql")
+                })
+        })
+        .collect();
+
+    // with dialect variants, there is more than one
+    paths.sort();
+    paths.into_iter().next()
+}
+
+/// Print the successfully applied migrations as a JSON array, for `info --applied-only-json`.
+///
+/// [`AppliedMigration`](sqlx::migrate::AppliedMigration) only carries the version and
+/// checksum, so the rest of the history is read from the migrations table directly.
+async fn print_applied_json(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+    time_zone: DisplayTimeZone,
+) -> anyhow::Result<()> {
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    let installed_on = match conn.backend_name() {
+        "MySQL" => "CAST(installed_on AS CHAR)",
+        _ => "CAST(installed_on AS TEXT)",
+    };
+
+    let rows: Vec<(i64, String, Vec<u8>, String, i64)> = sqlx::query_as(&format!(
+        "SELECT version, description, checksum, {installed_on}, execution_time \
+         FROM {migration_table} WHERE success ORDER BY version"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let output: Vec<_> = rows
+        .into_iter()
+        .map(
+            |(version, description, checksum, applied_at, execution_time)| {
+                serde_json::json!({
+                    "version": version,
+                    "description": description,
+                    "checksum": short_checksum(&checksum),
+                    "applied_at": parse_installed_on(&applied_at)
+                        .map_or(applied_at, |applied_at| time_zone.format(applied_at)),
+                    // nanoseconds, as recorded
+                    "execution_time": execution_time,
+                })
+            },
+        )
+        .collect();
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+/// Parse an `installed_on` timestamp as read by `CAST(installed_on AS TEXT)`.
+///
+/// PostgreSQL includes the offset; SQLite, and MySQL on a connection from sqlx, use UTC.
+fn parse_installed_on(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z") {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|time| Utc.from_utc_datetime(&time))
+}
+
+/// Read the `version` and `checksum` of every migration in a manifest: either the output of
+/// `fingerprint --json` or the array printed by `info --applied-only-json`.
+fn read_manifest(path: &Path) -> anyhow::Result<BTreeMap<i64, String>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest {}", path.display()))?;
+    let manifest: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse manifest {}", path.display()))?;
+
+    let entries = match &manifest {
+        serde_json::Value::Array(entries) => entries,
+        serde_json::Value::Object(object) => match object.get("migrations") {
+            Some(serde_json::Value::Array(entries)) => entries,
+            _ => bail!("manifest {} has no `migrations` array", path.display()),
+        },
+        _ => bail!("manifest {} is not a JSON array or object", path.display()),
+    };
+
+    let mut migrations = BTreeMap::new();
+    for entry in entries {
+        let version = entry.get("version").and_then(|v| v.as_i64());
+        let checksum = entry.get("checksum").and_then(|c| c.as_str());
+        let (Some(version), Some(checksum)) = (version, checksum) else {
+            bail!(
+                "manifest {} has an entry without a `version` and `checksum`: {entry}",
+                path.display()
+            );
+        };
+        if migrations
+            .insert(version, checksum.to_ascii_lowercase())
+            .is_some()
+        {
+            bail!("manifest {} lists version {version} twice", path.display());
+        }
+    }
+
+    Ok(migrations)
+}
+
+async fn diff_against_manifest(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+    manifest: &Path,
+    json: bool,
+) -> anyhow::Result<()> {
+    let expected = read_manifest(manifest)?;
+
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let applied: BTreeMap<i64, String> = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?
+        .into_iter()
+        .map(|m| (m.version, short_checksum(&m.checksum)))
+        .collect();
+
+    let differ: Vec<i64> = applied
+        .iter()
+        .filter(|(version, checksum)| expected.get(version).is_some_and(|c| c != *checksum))
+        .map(|(version, _)| *version)
+        .collect();
+    let extra: Vec<i64> = applied
+        .keys()
+        .filter(|version| !expected.contains_key(version))
+        .copied()
+        .collect();
+    let missing: Vec<i64> = expected
+        .keys()
+        .filter(|version| !applied.contains_key(version))
+        .copied()
+        .collect();
+
+    if json {
+        let output = serde_json::json!({
+            "differ": differ
+                .iter()
+                .map(|version| serde_json::json!({
+                    "version": version,
+                    "applied_checksum": applied[version],
+                    "manifest_checksum": expected[version],
+                }))
+                .collect::<Vec<_>>(),
+            "extra": extra,
+            "missing": missing,
+        });
+
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        for version in &differ {
+            println!(
+                "{} {} (applied {}, manifest {})",
+                style("differs:").bold().red(),
+                style(version).cyan(),
+                applied[version],
+                expected[version]
+            );
+        }
+        for version in &extra {
+            println!(
+                "{} {} (applied, not in the manifest)",
+                style("extra:").bold().yellow(),
+                style(version).cyan()
+            );
+        }
+        for version in &missing {
+            println!(
+                "{} {} (in the manifest, not applied)",
+                style("missing:").bold().yellow(),
+                style(version).cyan()
+            );
+        }
+
+        if differ.is_empty() && extra.is_empty() && missing.is_empty() {
+            println!(
+                "The applied migrations match {} ({} migrations)",
+                manifest.display(),
+                expected.len()
+            );
+        }
+    }
+
+    if !(differ.is_empty() && extra.is_empty() && missing.is_empty()) {
+        bail!(crate::ExitStatus(1));
+    }
+
+    Ok(())
+}
+
+/// Problems with the rows of the migrations table, as opposed to the migrations they record.
+///
+/// None of these can be caused by sqlx itself, but they can by a manual edit, or by a migrations
+/// table created without its primary key.
+async fn validate_table(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+) -> anyhow::Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    let without_version: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM {migration_table} WHERE version IS NULL"
+    ))
+    .fetch_one(&mut *conn)
+    .await?;
+    if without_version > 0 {
+        problems.push(format!("{without_version} rows have no version"));
+    }
+
+    let rows_where = |condition: &str| {
+        format!(
+            "SELECT version FROM {migration_table} \
+             WHERE version IS NOT NULL AND {condition} ORDER BY version"
+        )
+    };
+
+    let checks = [
+        (
+            format!(
+                "SELECT version FROM {migration_table} WHERE version IS NOT NULL \
+                 GROUP BY version HAVING COUNT(*) > 1 ORDER BY version"
+            ),
+            "is recorded more than once",
+        ),
+        (rows_where("checksum IS NULL"), "has no checksum"),
+        // SHA-384
+        (
+            rows_where("LENGTH(checksum) <> 48"),
+            "has a malformed checksum",
+        ),
+        (rows_where("success IS NULL"), "has no success flag"),
+    ];
+
+    for (query, problem) in checks {
+        let versions: Vec<i64> = sqlx::query_scalar(&query).fetch_all(&mut *conn).await?;
+        problems.extend(
+            versions
+                .into_iter()
+                .map(|version| format!("version {version} {problem}")),
+        );
+    }
+
+    // `run` stops at the first failed migration, so there can be at most one
+    let failed: Vec<i64> = sqlx::query_scalar(&rows_where("NOT success"))
+        .fetch_all(&mut *conn)
+        .await?;
+    if failed.len() > 1 {
+        let failed: Vec<_> = failed.iter().map(i64::to_string).collect();
+        problems.push(format!(
+            "versions {} are all marked as failed",
+            failed.join(", ")
+        ));
+    }
+
+    Ok(problems)
+}
+
+/// Whether `migration` passes the `--since` and `--versions` filters of `sqlx migrate info`.
+fn is_selected(migration: &Migration, opts: &InfoOpts) -> bool {
+    !matches!(opts.since, Some(since) if migration.version <= since)
+        && !matches!(&opts.versions, Some(versions) if !versions.contains(migration.version))
+}
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+async fn watch_until_clean(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    migration_table: &str,
+    opts: &InfoOpts,
+) -> anyhow::Result<()> {
+    let deadline = opts
+        .timeout
+        .map(|timeout| Instant::now() + Duration::from_secs(timeout));
+
+    loop {
+        let dirty = conn.dirty_version(migration_table.to_owned()).await?;
+        let applied: HashSet<_> = conn
+            .list_applied_migrations(migration_table.to_owned())
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect();
+
+        let pending = migrator
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration() && is_selected(m, opts))
+            .filter(|m| !applied.contains(&m.version))
+            .count();
+
+        if pending == 0 && dirty.is_none() {
+            println!("All migrations applied");
+            return Ok(());
+        }
+
+        match dirty {
+            Some(version) => println!(
+                "Waiting: {} pending migrations, dirty at {}",
+                pending,
+                style(version).cyan()
+            ),
+            None => println!("Waiting: {pending} pending migrations"),
+        }
+
+        if matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+            bail!(
+                "timed out after {}s waiting for migrations to be applied",
+                opts.timeout.unwrap_or_default()
+            );
+        }
+
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+/// Check that every applied migration exists locally.
+///
+/// With `ignore_missing`, return the versions of those that don't instead of failing.
+fn validate_applied_migrations(
+    applied_migrations: &[AppliedMigration],
+    migrator: &Migrator,
+    ignore_missing: bool,
+) -> Result<Vec<i64>, MigrateError> {
+    let migrations: HashSet<_> = migrator.iter().map(|m| m.version).collect();
+
+    let mut missing = Vec::new();
+
+    for applied_migration in applied_migrations {
+        if !migrations.contains(&applied_migration.version) {
+            if !ignore_missing {
+                return Err(MigrateError::VersionMissing(applied_migration.version));
+            }
+
+            missing.push(applied_migration.version);
+        }
+    }
+
+    Ok(missing)
+}
+
+/// For `--verbose`, list the applied migrations without a local file.
+fn print_missing_migrations(missing: &[i64], migration_source: &str) {
+    if missing.is_empty() {
+        return;
+    }
+
+    println!(
+        "Ignoring {} applied migrations not found in {}:",
+        missing.len(),
+        style(migration_source).cyan()
+    );
+    for version in missing {
+        println!("  {}", style(version).cyan());
+    }
+}
+
+/// List applied migrations whose local file no longer matches the recorded checksum.
+fn warn_checksum_mismatches(
+    migrator: &Migrator,
+    applied_migrations: &HashMap<i64, AppliedMigration>,
+    allowlist: &ChecksumAllowlist,
+) {
+    let mismatched: Vec<_> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| {
+            applied_migrations
+                .get(&m.version)
+                .map_or(false, |applied| allowlist.is_mismatch(m, applied))
+        })
+        .collect();
+
+    if mismatched.is_empty() {
+        return;
+    }
+
+    println!(
+        "{} {} applied migrations were modified after being applied:",
+        style("warning:").bold().yellow(),
+        mismatched.len()
+    );
+
+    for migration in mismatched {
+        println!(
+            "  {}/{} {}",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description
+        );
+    }
+}
+
+fn warn_ignoring_dirty(version: i64) {
+    println!(
+        "{} the database is dirty on version {}, continuing anyway because of --no-dirty-check.",
+        style("warning:").bold().yellow(),
+        style(version).cyan()
+    );
+    println!("  A migration failed part-way through, so the schema may not match what any");
+    println!("  migration expects. Continuing is only safe if the failure was fully resolved by");
+    println!("  hand; otherwise later migrations may fail or damage data. The failed migration");
+    println!("  stays recorded as dirty. To re-apply an idempotent dirty migration instead, use");
+    println!("  `sqlx migrate run --auto-recover`.");
+}
+
+/// Check whether a pending migration should be recorded as applied instead of executed.
+///
+/// Only `-- sqlx:baseline` migrations qualify: either unconditionally when `adopt` is set,
+/// or when the directive's probe query returns at least one row.
+async fn is_adoptable_baseline(
+    conn: &mut AnyConnection,
+    migration: &Migration,
+    adopt: bool,
+) -> anyhow::Result<bool> {
+    let Some(probe) = migration.directive(dialect(conn), "baseline") else {
+        return Ok(false);
+    };
+
+    if adopt {
+        return Ok(true);
+    }
+
+    if probe.is_empty() {
+        return Ok(false);
+    }
+
+    let found = conn
+        .fetch_optional(probe)
+        .await
+        .with_context(|| format!("baseline probe for migration {} failed", migration.version))?;
+
+    Ok(found.is_some())
+}
+
+/// Re-apply the migration the database is dirty on, assuming it is safe to run again.
+async fn recover_dirty(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    record: DirtyRecord,
+    migration_table: &str,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let version = record.version;
+    let Some(migration) = migrator
+        .iter()
+        .find(|m| m.version == version && m.migration_type.is_up_migration())
+    else {
+        // nothing to re-apply; the dirty state has to be resolved by hand
+        bail!(MigrateError::DirtyRecord(record));
+    };
+
+    if dry_run {
+        println!(
+            "Can recover {}/{} {}",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+        );
+        return Ok(());
+    }
+
+    conn.clear_dirty_version(version, migration_table.to_owned())
+        .await?;
+
+    match conn.apply(migration, migration_table.to_owned()).await {
+        Ok(elapsed) => {
+            println!(
+                "Recovered {}/{} {} {}",
+                style(migration.version).cyan(),
+                style(migration.migration_type.label()).green(),
+                migration.description,
+                style(format!("({elapsed:?})")).dim()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            // MySQL flags the failed attempt by itself, while databases with transactional DDL
+            // roll it back entirely; either way, leave the database dirty on this version.
+            if conn.dirty_version(migration_table.to_owned()).await? != Some(version) {
+                conn.mark_dirty(migration, migration_table.to_owned())
+                    .await?;
+            }
+
+            Err(anyhow::Error::new(e).context(format!(
+                "failed to re-apply dirty migration {version}; the database is still dirty"
+            )))
+        }
+    }
+}
+
+/// Record a single pending migration as applied without executing it (`run --fake`).
+async fn fake(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    version: i64,
+    applied_migrations: &HashMap<i64, AppliedMigration>,
+    migration_table: &str,
+    opts: &RunOpts,
+) -> anyhow::Result<()> {
+    let Some(migration) = migrator
+        .iter()
+        .find(|m| m.version == version && m.migration_type.is_up_migration())
+    else {
+        bail!(MigrateError::VersionNotPresent(version));
+    };
+
+    if applied_migrations.contains_key(&version) {
+        bail!("migration {version} is already applied");
+    }
+
+    let gaps: Vec<String> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .filter(|m| m.version < version && !applied_migrations.contains_key(&m.version))
+        .map(|m| m.version.to_string())
+        .collect();
+    if !gaps.is_empty() && !opts.allow_gaps {
+        bail!(
+            "earlier migrations are still pending ({}); use --allow-gaps to record {version} anyway",
+            gaps.join(", ")
+        );
+    }
+
+    if opts.dry_run {
+        println!(
+            "Can fake {}/{} {}",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+        );
+        return Ok(());
+    }
+
+    if !opts.yes
+        && !crate::ask_to_continue(&format!(
+            "Record migration {} as applied without executing it?",
+            style(version).cyan()
+        ))
+    {
+        return Ok(());
+    }
+
+    conn.record_applied(migration, migration_table.to_owned())
+        .await?;
+
+    println!(
+        "Faked (recorded without executing) {}/{} {}",
+        style(migration.version).cyan(),
+        style(migration.migration_type.label()).green(),
+        migration.description,
+    );
+
+    Ok(())
+}
+
+pub async fn run(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+) -> anyhow::Result<()> {
+    let notifier = Notifier::new(&opts.notify)?;
+    notify_start(&notifier, migration_source).await;
+
+    let res = match crate::connect(connect_opts).await {
+        Ok(mut conn) => {
+            let res = match &opts.cancel_file {
+                Some(cancel_file) => {
+                    run_cancellable(
+                        &mut conn,
+                        connect_opts,
+                        Path::new(cancel_file),
+                        migration_source,
+                        migration_table,
+                        opts,
+                        &notifier,
+                    )
+                    .await
+                }
+                None => {
+                    run_audited(
+                        &mut conn,
+                        migration_source,
+                        migration_table,
+                        opts,
+                        &notifier,
+                    )
+                    .await
+                }
+            };
+
+            // Close the connection before exiting:
+            // * For MySQL and Postgres this should ensure timely cleanup on the server side,
+            //   including decrementing the open connection count.
+            // * For SQLite this should checkpoint and delete the WAL file to ensure the migrations
+            //   were actually applied to the database file and aren't just sitting in the WAL file.
+            let _ = conn.close().await;
+
+            res
+        }
+        Err(e) => Err(e),
+    };
+
+    notify_result(&notifier, &res).await;
+
+    res
+}
+
+/// Like [`run`], but on an existing connection, e.g. one checked out from a pool.
+///
+/// The connection is left open.
+pub async fn run_with_conn(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+) -> anyhow::Result<()> {
+    // cancelling the statement in flight takes a second connection
+    if opts.cancel_file.is_some() {
+        bail!("--cancel-file is not supported when running on an existing connection");
+    }
+
+    let notifier = Notifier::new(&opts.notify)?;
+    notify_start(&notifier, migration_source).await;
+
+    let res = run_audited(conn, migration_source, migration_table, opts, &notifier).await;
+
+    notify_result(&notifier, &res).await;
+
+    res
+}
+
+async fn notify_start(notifier: &Notifier<'_>, migration_source: &str) {
+    notifier
+        .send(
+            NotifyEvent::Start,
+            serde_json::json!({ "source": migration_source }),
+        )
+        .await;
+}
+
+async fn notify_result(notifier: &Notifier<'_>, res: &anyhow::Result<()>) {
+    match res {
+        Ok(()) => {
+            notifier
+                .send(NotifyEvent::Finish, serde_json::json!({ "status": "success" }))
+                .await
+        }
+        Err(e) => {
+            notifier
+                .send(
+                    NotifyEvent::Error,
+                    serde_json::json!({ "status": "error", "error": format!("{e:#}") }),
+                )
+                .await
+        }
+    }
+}
+
+/// Like [`run_in_maintenance`], but with `--audit-runs`, record the run in the runs table once
+/// it has finished, whether it succeeded or not.
+async fn run_audited(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    if !opts.audit_runs || opts.dry_run {
+        return run_in_maintenance(conn, migration_source, migration_table, opts, notifier).await;
+    }
+
+    if let Some(release_id) = &opts.release_id {
+        validate_release_id(release_id)?;
+    }
+
+    let table = migration_table
+        .clone()
+        .unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    let runs_table = opts
+        .audit_runs_table
+        .clone()
+        .unwrap_or_else(|| runs_table(&table));
+
+    conn.ensure_migrations_table(table.clone()).await?;
+    let applied_before: HashSet<i64> = conn
+        .list_applied_migrations(table.clone())
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    let started_at = now();
+
+    let res = run_in_maintenance(conn, migration_source, migration_table, opts, notifier).await;
+
+    let record = async {
+        record_run(
+            conn,
+            &runs_table,
+            &table,
+            applied_before.len(),
+            started_at,
+            res.is_ok(),
+            opts.release_id.as_deref(),
+        )
+        .await?;
+
+        // even if the run failed, the migrations applied before the failure belong to it
+        if let Some(release_id) = &opts.release_id {
+            record_release(conn, &table, release_id, &applied_before).await?;
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    match res {
+        Ok(()) => record?,
+        // don't hide why the migrations failed
+        Err(e) => {
+            if let Err(record) = record {
+                println!("{} {record:#}", style("warning:").bold().yellow());
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+fn runs_table(migration_table: &str) -> String {
+    format!("{migration_table}_runs")
+}
+
+fn releases_table(migration_table: &str) -> String {
+    format!("{migration_table}_releases")
+}
+
+async fn ensure_releases_table(conn: &mut AnyConnection, table: &str) -> anyhow::Result<()> {
+    conn.execute(&*format!(
+        "CREATE TABLE IF NOT EXISTS {table} ( version BIGINT PRIMARY KEY, \
+         release_id VARCHAR(255) NOT NULL )"
+    ))
+    .await
+    .with_context(|| format!("failed to create {table}"))?;
+
+    Ok(())
+}
+
+/// Record the migrations applied since `applied_before` as belonging to `release_id`, for
+/// `revert --release`.
+async fn record_release(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+    release_id: &str,
+    applied_before: &HashSet<i64>,
+) -> anyhow::Result<()> {
+    let table = releases_table(migration_table);
+    ensure_releases_table(conn, &table).await?;
+
+    let applied = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?;
+
+    for migration in applied
+        .iter()
+        .filter(|m| !applied_before.contains(&m.version))
+    {
+        // a migration reverted and applied again belongs to its latest release
+        conn.execute(&*format!(
+            "DELETE FROM {table} WHERE version = {}",
+            migration.version
+        ))
+        .await?;
+        conn.execute(&*format!(
+            "INSERT INTO {table} ( version, release_id ) VALUES ( {}, '{release_id}' )",
+            migration.version
+        ))
+        .await
+        .with_context(|| format!("failed to record the release in {table}"))?;
+    }
+
+    Ok(())
+}
+
+fn followups_table(migration_table: &str) -> String {
+    format!("{migration_table}_followups")
+}
+
+/// Create the table tracking manual follow-ups, if needed.
+///
+/// Only versions are stored: the messages are read from the local migrations.
+async fn ensure_followups_table(conn: &mut AnyConnection, table: &str) -> anyhow::Result<()> {
+    conn.execute(&*format!(
+        "CREATE TABLE IF NOT EXISTS {table} ( version BIGINT PRIMARY KEY, \
+         applied_at VARCHAR(32) NOT NULL, acknowledged_at VARCHAR(32) )"
+    ))
+    .await
+    .with_context(|| format!("failed to create {table}"))?;
+
+    Ok(())
+}
+
+/// The versions of the applied migrations whose manual follow-up is not acknowledged yet.
+async fn outstanding_followups(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+) -> anyhow::Result<Vec<i64>> {
+    let table = followups_table(migration_table);
+    ensure_followups_table(conn, &table).await?;
+
+    let versions = sqlx::query_scalar(&format!(
+        "SELECT version FROM {table} WHERE acknowledged_at IS NULL \
+         AND version IN ( SELECT version FROM {migration_table} WHERE success ) \
+         ORDER BY version"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(versions)
+}
+
+/// Record the manual follow-ups of the just applied `migrations` and print a notice listing
+/// them, for `run`.
+async fn record_followups(
+    conn: &mut AnyConnection,
+    migration_table: &str,
+    migrations: &[(&Migration, &str)],
+) -> anyhow::Result<()> {
+    let table = followups_table(migration_table);
+    ensure_followups_table(conn, &table).await?;
+
+    for (migration, _) in migrations {
+        // a migration reverted and applied again needs its follow-up again
+        conn.execute(&*format!(
+            "DELETE FROM {table} WHERE version = {}",
+            migration.version
+        ))
+        .await?;
+        conn.execute(&*format!(
+            "INSERT INTO {table} ( version, applied_at, acknowledged_at ) VALUES ( {}, '{}', NULL )",
+            migration.version,
+            now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        ))
+        .await
+        .with_context(|| format!("failed to record the manual follow-up in {table}"))?;
+    }
+
+    println!();
+    println!("{}", style("MANUAL FOLLOW-UP REQUIRED").bold().red());
+    for (migration, message) in migrations {
+        println!(
+            "  {}/{}: {}",
+            style(migration.version).cyan(),
+            migration.description,
+            message
+        );
+    }
+    println!("Acknowledge each with `sqlx migrate ack --version <VERSION>` once done.");
+
+    Ok(())
+}
+
+/// Mark the manual follow-up of the migration with the given version as done, for
+/// `migrate ack`.
+pub async fn ack(
+    version: i64,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    let table = followups_table(&migration_table);
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    let res = async {
+        conn.ensure_migrations_table(migration_table.clone()).await?;
+
+        if !outstanding_followups(&mut conn, &migration_table)
+            .await?
+            .contains(&version)
+        {
+            bail!("migration {version} has no outstanding manual follow-up");
+        }
+
+        conn.execute(&*format!(
+            "UPDATE {table} SET acknowledged_at = '{}' WHERE version = {version}",
+            now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        ))
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    let _ = conn.close().await;
+    res?;
+
+    println!(
+        "Acknowledged the manual follow-up of migration {}",
+        style(version).cyan()
+    );
+
+    Ok(())
+}
+
+/// Drop the objects left behind by the dirty migration with the given version and clear its
+/// dirty flag, for `migrate cleanup-dirty`.
+///
+/// The `DROP` statements are a best-effort guess from the `CREATE` statements of the migration,
+/// see [`cleanup::plan`]. They are printed and have to be confirmed before anything is executed.
+pub async fn cleanup_dirty(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    version: i64,
+    dry_run: bool,
+    confirm: bool,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let Some(migration) = migrator
+        .iter()
+        .find(|m| m.version == version && m.migration_type.is_up_migration())
+    else {
+        bail!(MigrateError::VersionNotPresent(version));
+    };
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    let res = async {
+        conn.ensure_migrations_table(migration_table.clone()).await?;
+
+        match conn.dirty_record(migration_table.clone()).await? {
+            Some(dirty) if dirty.version == version && dirty.reverting => bail!(
+                "migration {version} failed while being reverted; cleanup-dirty only drops the \
+                 objects of a failed application"
+            ),
+            Some(dirty) if dirty.version == version => {}
+            Some(dirty) => bail!(
+                "the database is dirty on migration {}, not {version}",
+                dirty.version
+            ),
+            None => bail!("the database is not dirty"),
+        }
+
+        let Some(statements) = migration.sql_statements(dialect(&conn))? else {
+            bail!("migration {version} cannot be split into statements to plan a cleanup");
+        };
+        let plan = cleanup::plan(dialect(&conn), &statements);
+
+        println!(
+            "Cleanup plan for {}/{} {}",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+        );
+        println!(
+            "{} the plan is guessed from the CREATE statements of the migration; anything else \
+             it changed is not undone. Review it before continuing.",
+            style("note:").bold().yellow(),
+        );
+
+        for statement in &plan.drops {
+            println!("  {statement}");
+        }
+
+        if plan.drops.is_empty() {
+            println!("  (nothing to drop)");
+        }
+
+        for skipped in &plan.skipped {
+            println!(
+                "{} {} ({})",
+                style("not undone:").yellow(),
+                skipped.statement,
+                skipped.reason
+            );
+        }
+
+        if dry_run {
+            return Ok(false);
+        }
+
+        if confirm {
+            if !io::stdin().is_terminal() {
+                bail!(
+                    "stdin is not a terminal, so the cleanup cannot be confirmed; pass --yes to \
+                     confirm it"
+                );
+            }
+
+            if !crate::ask_to_continue(&format!(
+                "Execute these statements and clear the dirty flag of migration {version}?"
+            )) {
+                return Ok(false);
+            }
+        }
+
+        for statement in &plan.drops {
+            conn.execute(&**statement).await.with_context(|| {
+                format!("failed to execute `{statement}`; the database is still dirty")
+            })?;
+        }
+
+        conn.clear_dirty_version(version, migration_table.clone())
+            .await?;
+
+        Ok(true)
+    }
+    .await;
+
+    let _ = conn.close().await;
+
+    if res? {
+        println!(
+            "Cleaned up {}/{} {}; `sqlx migrate run` will apply it again",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+        );
+    }
+
+    Ok(())
+}
+
+fn validate_release_id(release_id: &str) -> anyhow::Result<()> {
+    // The ID is spliced into SQL, so only allow a conservative set of characters.
+    anyhow::ensure!(
+        !release_id.is_empty()
+            && release_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')),
+        "invalid release ID {release_id:?}: only letters, digits, `_`, `-` and `.` are allowed"
+    );
+
+    Ok(())
+}
+
+/// Insert a row describing a finished run into `runs_table`, creating it if needed.
+async fn record_run(
+    conn: &mut AnyConnection,
+    runs_table: &str,
+    migration_table: &str,
+    applied_before: usize,
+    started_at: DateTime<Utc>,
+    success: bool,
+    release_id: Option<&str>,
+) -> anyhow::Result<()> {
+    let applied = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?;
+    let head_version = match applied.iter().map(|m| m.version).max() {
+        Some(version) => version.to_string(),
+        None => "NULL".to_string(),
+    };
+    let release_id = match release_id {
+        Some(release_id) => format!("'{release_id}'"),
+        None => "NULL".to_string(),
+    };
+
+    conn.execute(&*format!(
+        "CREATE TABLE IF NOT EXISTS {runs_table} ( started_at VARCHAR(32) NOT NULL, \
+         finished_at VARCHAR(32) NOT NULL, applied BIGINT NOT NULL, success BOOLEAN NOT NULL, \
+         release_id VARCHAR(255), head_version BIGINT )"
+    ))
+    .await
+    .with_context(|| format!("failed to create {runs_table}"))?;
+
+    conn.execute(&*format!(
+        "INSERT INTO {runs_table} \
+         ( started_at, finished_at, applied, success, release_id, head_version ) \
+         VALUES ( '{}', '{}', {}, {}, {release_id}, {head_version} )",
+        started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        applied.len().saturating_sub(applied_before),
+        if success { "TRUE" } else { "FALSE" },
+    ))
+    .await
+    .with_context(|| format!("failed to record the run in {runs_table}"))?;
+
+    Ok(())
+}
+
+/// Like [`run_pending_logged`], but with `--maintenance-flag`, set the flag before running
+/// migrations and clear it afterwards, even if a migration fails.
+async fn run_in_maintenance(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    let Some(flag) = opts.maintenance_flag.as_ref().filter(|_| !opts.dry_run) else {
+        return run_pending_logged(conn, migration_source, migration_table, opts, notifier).await;
+    };
+
+    set_maintenance_flag(conn, flag, true).await?;
+    println!("Set maintenance flag {}", style(flag).cyan());
+
+    let res = run_pending_logged(conn, migration_source, migration_table, opts, notifier).await;
+
+    let clear = set_maintenance_flag(conn, flag, false).await;
+
+    match res {
+        Ok(()) => clear?,
+        // don't hide why the migrations failed
+        Err(e) => {
+            if let Err(clear) = clear {
+                println!("{} {clear:#}", style("warning:").bold().yellow());
+            }
+            return Err(e);
+        }
+    }
+
+    println!("Cleared maintenance flag {}", style(flag).cyan());
+
+    Ok(())
+}
+
+async fn set_maintenance_flag(
+    conn: &mut AnyConnection,
+    flag: &MaintenanceFlag,
+    value: bool,
+) -> anyhow::Result<()> {
+    let value_sql = if value { "TRUE" } else { "FALSE" };
+
+    let res = conn
+        .execute(&*format!(
+            "UPDATE {} SET {} = {value_sql}",
+            flag.table, flag.column
+        ))
+        .await
+        .with_context(|| {
+            if value {
+                format!("failed to set maintenance flag {flag}")
+            } else {
+                format!("failed to clear maintenance flag {flag}; it must be cleared by hand")
+            }
+        })?;
+
+    if res.rows_affected() == 0 {
+        bail!("maintenance flag table {} has no rows", flag.table);
+    }
+
+    Ok(())
+}
+
+/// Acquire the migration lock, waiting at most `timeout` seconds for it if given, so that only
+/// one process migrates the database at a time.
+async fn lock_migrations(conn: &mut AnyConnection, timeout: Option<u64>) -> anyhow::Result<()> {
+    match timeout {
+        Some(timeout) => conn.lock_with_timeout(Duration::from_secs(timeout)).await?,
+        None => conn.lock().await?,
+    }
+
+    Ok(())
+}
+
+/// Like [`run_pending`], but holding the migration lock, which is released afterwards even if a
+/// migration fails.
+async fn run_pending_locked(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    lock_migrations(conn, opts.migration_lock_timeout).await?;
+
+    let res =
+        run_pending_lock_timeout(conn, migration_source, migration_table, opts, notifier).await;
+
+    let unlock = conn.unlock().await;
+
+    res?;
+    unlock?;
+
+    Ok(())
+}
+
+/// Like [`run_pending`], but with `--lock-timeout`, bound how long each migration may wait for a
+/// lock. The session's `lock_timeout` is reset afterwards, even if a migration fails, as the
+/// connection may outlive the run, e.g. one checked out from a pool.
+async fn run_pending_lock_timeout(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    let Some(lock_timeout) = opts.lock_timeout else {
+        return run_pending(conn, migration_source, migration_table, opts, notifier).await;
+    };
+
+    if conn.backend_name() != "PostgreSQL" {
+        bail!("--lock-timeout is only supported on PostgreSQL");
+    }
+
+    // session-wide; a `-- sqlx:lock-timeout` directive overrides it with `SET LOCAL`
+    conn.execute(&*format!(
+        "SET lock_timeout = {}",
+        Duration::from_secs(lock_timeout).as_millis()
+    ))
+    .await?;
+
+    let res = run_pending(conn, migration_source, migration_table, opts, notifier).await;
+
+    let reset = conn.execute("RESET lock_timeout").await;
+
+    res?;
+    reset.context("failed to reset lock_timeout")?;
+
+    Ok(())
+}
+
+/// Like [`run_pending_locked`], but with `--server-log`, have the server log every statement of the
+/// session while migrations run. Logging is reset afterwards, even if a migration fails.
+async fn run_pending_logged(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    if !opts.server_log {
+        return run_pending_locked(conn, migration_source, migration_table, opts, notifier).await;
+    }
+
+    // MySQL's general log is server-wide; there is no way to enable it for one session
+    if conn.backend_name() != "PostgreSQL" {
+        bail!("--server-log is only supported on PostgreSQL");
+    }
+
+    // only affects this session, but requires superuser
+    conn.execute("SET log_statement = 'all'")
+        .await
+        .context("failed to enable statement logging for --server-log")?;
+
+    let res = run_pending_locked(conn, migration_source, migration_table, opts, notifier).await;
+
+    let reset = conn.execute("RESET log_statement").await;
+
+    res?;
+    reset.context("failed to reset statement logging")?;
+
+    Ok(())
+}
+
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Like [`run_pending_logged`], but with `--cancel-file`, stop as soon as the cancel file is
+/// created.
+///
+/// Dropping the migration future alone would leave the statement in flight running on the
+/// server, so it is cancelled from a second connection.
+async fn run_cancellable(
+    conn: &mut AnyConnection,
+    connect_opts: &ConnectOpts,
+    cancel_file: &Path,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    // a leftover file would cancel every run straight away
+    if cancel_file.exists() {
+        bail!(
+            "cancel file {} already exists; remove it before running migrations",
+            cancel_file.display()
+        );
+    }
+
+    let backend_id = backend_id(conn).await?;
+
+    tokio::select! {
+        res = run_in_maintenance(conn, migration_source, migration_table, opts, notifier) => {
+            return res;
+        }
+        () = wait_for_file(cancel_file) => {}
+    }
+
+    eprintln!(
+        "{} found {}, cancelling the migration in progress",
+        style("warning:").bold().yellow(),
+        cancel_file.display()
+    );
+
+    let postgres = conn.backend_name() == "PostgreSQL";
+    let cancel = if postgres {
+        format!("SELECT pg_cancel_backend({backend_id})")
+    } else {
+        format!("KILL QUERY {backend_id}")
+    };
+
+    let mut cancel_conn = crate::connect(connect_opts).await?;
+    let res = cancel_conn.execute(&*cancel).await;
+    // the cancelled connection is in an unknown state
+    let clear = match &opts.maintenance_flag {
+        Some(flag) if !opts.dry_run => set_maintenance_flag(&mut cancel_conn, flag, false).await,
+        _ => Ok(()),
+    };
+    let _ = cancel_conn.close().await;
+    res.context("failed to cancel the migration in progress")?;
+    clear?;
+
+    if postgres {
+        bail!(
+            "migrations cancelled by {}; the migration in progress was rolled back",
+            cancel_file.display()
+        )
+    } else {
+        bail!(
+            "migrations cancelled by {}; the migration in progress was likely left dirty, \
+             see `sqlx migrate info`",
+            cancel_file.display()
+        )
+    }
+}
+
+/// The server-side id of the session, with which a statement can be cancelled from another
+/// connection.
+async fn backend_id(conn: &mut AnyConnection) -> anyhow::Result<i64> {
+    let query = match conn.backend_name() {
+        "PostgreSQL" => "SELECT CAST(pg_backend_pid() AS BIGINT)",
+        "MySQL" => "SELECT CAST(CONNECTION_ID() AS SIGNED)",
+        // statements run in-process, there is nothing to cancel them from
+        backend => bail!("--cancel-file is not supported on {backend}"),
+    };
+
+    Ok(sqlx::query_scalar(query).fetch_one(&mut *conn).await?)
+}
+
+async fn wait_for_file(path: &Path) {
+    while !path.exists() {
+        tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+    }
+}
+
+/// The version given by `--target-version`, or that of the migration whose description
+/// matches `--target-description`.
+fn resolve_target_version(migrator: &Migrator, opts: &RunOpts) -> anyhow::Result<Option<i64>> {
+    // `run_pending` checks that no earlier migration is pending, so this applies just one
+    if let Some(only) = opts.only {
+        return Ok(Some(only));
+    }
+
+    let Some(pattern) = &opts.target_description else {
+        return Ok(match opts.target_version {
+            Some(TargetVersion::Version(version)) => Some(version),
+            // the same as no target: everything pending is applied
+            Some(TargetVersion::Latest) | None => None,
+        });
+    };
+
+    // descriptions are derived from file names with underscores replaced by spaces
+    let pattern = pattern.replace('_', " ");
+
+    let candidates: Vec<_> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && m.description.contains(&pattern))
+        .collect();
+
+    match candidates.as_slice() {
+        [] => bail!("no migration has a description matching `{pattern}`"),
+        [migration] => Ok(Some(migration.version)),
+        _ => {
+            let list: Vec<_> = candidates
+                .iter()
+                .map(|m| format!("  {}/{}", m.version, m.description))
+                .collect();
+
+            bail!(
+                "`{pattern}` matches more than one migration:\n{}",
+                list.join("\n")
+            )
+        }
+    }
+}
+
+/// [`MigrateError::VersionNotPresent`], listing the local versions closest to the one asked for.
+fn version_not_present(migrator: &Migrator, version: i64) -> anyhow::Error {
+    let mut versions: Vec<i64> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| m.version)
+        .collect();
+    versions.sort_unstable();
+    versions.dedup();
+
+    if versions.is_empty() {
+        return MigrateError::VersionNotPresent(version).into();
+    }
+
+    // up to two on either side of where the version would be
+    let position = versions.partition_point(|v| *v < version);
+    let end = std::cmp::min(position + 2, versions.len());
+    let nearby: Vec<_> = versions[position.saturating_sub(2)..end]
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+
+    anyhow::anyhow!(
+        "{}; the closest available versions are {}",
+        MigrateError::VersionNotPresent(version),
+        nearby.join(", ")
+    )
+}
+
+async fn run_pending(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    let mut migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    migrator.set_checksum_ignore_comments(opts.checksum_ignore_comments);
+
+    if let Err(e) = migrator.validate_version_schemes() {
+        if opts.strict_versions {
+            bail!(e);
+        }
+
+        println!("{} {e}", style("warning:").bold().yellow());
+    }
+
+    let hooks = Hooks::load(migration_source)?;
+    let allowlist = ChecksumAllowlist::load(migration_source)?;
+
+    if opts.fail_on_empty_source
+        && !migrator
+            .iter()
+            .any(|m| !m.migration_type.is_down_migration())
+    {
+        bail!("no migrations found in {migration_source}; is `--source` correct?");
+    }
+
+    let target_version = resolve_target_version(&migrator, opts)?;
+    if let Some(target_version) = target_version {
+        if !migrator.version_exists(target_version) {
+            return Err(version_not_present(&migrator, target_version));
+        }
+    }
+
+    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    if opts.single_transaction && conn.backend_name() != "PostgreSQL" {
+        bail!(
+            "--single-transaction requires transactional DDL, which {} does not support",
+            conn.backend_name()
+        );
+    }
+
+    conn.ensure_migrations_table(migration_table.to_owned()).await?;
+
+    if opts.index_installed_on && !opts.dry_run {
+        conn.ensure_installed_on_index(migration_table.to_owned())
+            .await?;
+    }
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        if opts.no_dirty_check {
+            warn_ignoring_dirty(record.version);
+        } else if !opts.auto_recover || record.reverting {
+            // re-applying the up migration does not resolve a failed revert
+            bail!(MigrateError::DirtyRecord(record));
+        } else {
+            recover_dirty(conn, &migrator, record, &migration_table, opts.dry_run).await?;
+        }
+    }
+
+    let applied_migrations = conn.list_applied_migrations(migration_table.to_owned()).await?;
+    let missing =
+        validate_applied_migrations(&applied_migrations, &migrator, *opts.ignore_missing)?;
+    if opts.verbose {
+        print_missing_migrations(&missing, migration_source);
+    }
+
+    let latest_version = conn
+        .latest_applied_migration(migration_table.to_owned())
+        .await?
+        .map_or(0, |migration| migration.version);
+    if let (Some(description), Some(target_version)) = (&opts.target_description, target_version) {
+        if applied_migrations
+            .iter()
+            .any(|m| m.version == target_version)
+        {
+            println!(
+                "Migration {} matching `{description}` is already applied; nothing to do",
+                style(target_version).cyan(),
+            );
+            return Ok(());
+        }
+    }
+    // a later migration may already be applied out of order
+    if let (Some(target_version), None) = (target_version, opts.only) {
+        if target_version < latest_version {
+            bail!(MigrateError::VersionTooOld(target_version, latest_version));
+        }
+    }
+
+    let applied_migrations: HashMap<_, _> = applied_migrations
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    if let (true, Some(version)) = (opts.fake, opts.version) {
+        return fake(
+            conn,
+            &migrator,
+            version,
+            &applied_migrations,
+            &migration_table,
+            opts,
+        )
+        .await;
+    }
+
+    if let Some(only) = opts.only {
+        if applied_migrations.contains_key(&only) {
+            bail!("migration {only} is already applied");
+        }
+
+        if let Some(blocking) = migrator
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration())
+            .find(|m| m.version < only && !applied_migrations.contains_key(&m.version))
+        {
+            bail!(
+                "cannot apply only migration {only}: earlier migration {} ({}) is still pending",
+                blocking.version,
+                blocking.description
+            );
+        }
+    }
+
+    if let Some(versions) = &opts.versions {
+        let pending = || {
+            migrator
+                .iter()
+                .filter(|m| m.migration_type.is_up_migration())
+                .filter(|m| !applied_migrations.contains_key(&m.version))
+        };
+
+        if let Some(last_selected) = pending()
+            .filter(|m| versions.contains(m.version))
+            .map(|m| m.version)
+            .max()
+        {
+            let gaps: Vec<String> = pending()
+                .filter(|m| m.version < last_selected && !versions.contains(m.version))
+                .map(|m| m.version.to_string())
+                .collect();
+            if !gaps.is_empty() && !opts.allow_gaps {
+                bail!(
+                    "earlier migrations are still pending ({}); include them in --versions \
+                     or use --allow-gaps",
+                    gaps.join(", ")
+                );
+            }
+        }
+    }
+
+    if opts.single_transaction {
+        // `apply()` nests its own transaction inside this one as a savepoint, so the
+        // migrations table is updated together with the schema. Any failure drops the
+        // outer transaction, rolling back every migration in the batch.
+        let mut tx = conn.begin().await?;
+        let res = apply_pending(
+            &mut tx,
+            &migrator,
+            &applied_migrations,
+            &migration_table,
+            latest_version,
+            &hooks,
+            &allowlist,
+            opts,
+            notifier,
+        )
+        .await;
+
+        if let Err(e) = res {
+            if let Some(rolled_back) = e.downcast_ref::<RolledBackToSavepoint>() {
+                tx.commit().await?;
+                println!(
+                    "{} migration {} failed and was rolled back; committed the {} migrations \
+                     applied before it",
+                    style("warning:").bold().yellow(),
+                    style(rolled_back.version).cyan(),
+                    rolled_back.committed
+                );
+            }
+            return Err(e);
+        }
+        tx.commit().await?;
+    } else {
+        apply_pending(
+            conn,
+            &migrator,
+            &applied_migrations,
+            &migration_table,
+            latest_version,
+            &hooks,
+            &allowlist,
+            opts,
+            notifier,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Apply a single migration for `run`, as selected by `--server-timing` and `--progress`.
+async fn apply_migration(
+    conn: &mut AnyConnection,
+    migration: &Migration,
+    migration_table: &str,
+    opts: &RunOpts,
+) -> Result<Duration, MigrateError> {
+    if opts.server_timing {
+        conn.apply_server_timed(migration, migration_table.to_owned())
+            .await
+    } else if opts.progress {
+        let mut progress = |statement: usize, total: usize| {
+            println!(
+                "{} [statement {}/{}]",
+                style(migration.version).cyan(),
+                statement,
+                total
+            )
+        };
+        conn.apply_with_progress(migration, migration_table.to_owned(), &mut progress)
+            .await
+    } else {
+        conn.apply(migration, migration_table.to_owned()).await
+    }
+}
+
+const BEFORE_EACH_HOOK: &str = "before_each.sql";
+const AFTER_EACH_HOOK: &str = "after_each.sql";
+
+/// The optional SQL files in the migrations directory which `run` executes on the same
+/// connection before and after each migration it applies, e.g. to `SET statement_timeout`.
+///
+/// They are not migrations: they are neither recorded nor checksummed, and skipped in a dry run.
+struct Hooks {
+    before_each: Option<String>,
+    after_each: Option<String>,
+}
+
+impl Hooks {
+    /// Load the hooks from the migrations directories in `migration_source`; each hook may be
+    /// in at most one of them.
+    fn load(migration_source: &str) -> anyhow::Result<Self> {
+        let dirs = source_dirs(migration_source);
+        let read = |name: &str| {
+            let mut found: Option<(PathBuf, String)> = None;
+
+            for dir in &dirs {
+                let path = dir.join(name);
+                let sql = match fs::read_to_string(&path) {
+                    Ok(sql) => sql,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => {
+                        return Err(e).with_context(|| format!("failed to read {}", path.display()))
+                    }
+                };
+
+                if let Some((other, _)) = &found {
+                    bail!(
+                        "{name} is in more than one migrations directory: {} and {}",
+                        other.display(),
+                        path.display()
+                    );
+                }
+                found = Some((path, sql));
+            }
+
+            Ok(found.map(|(_, sql)| sql))
+        };
+
+        Ok(Hooks {
+            before_each: read(BEFORE_EACH_HOOK)?,
+            after_each: read(AFTER_EACH_HOOK)?,
+        })
+    }
+}
+
+async fn run_hook(conn: &mut AnyConnection, name: &str, sql: Option<&str>) -> anyhow::Result<()> {
+    if let Some(sql) = sql {
+        conn.execute(sql)
+            .await
+            .with_context(|| format!("failed to run {name}"))?;
+    }
+
+    Ok(())
+}
+
+/// Apply a single migration for `run` between the before and after hooks.
+async fn apply_with_hooks(
+    conn: &mut AnyConnection,
+    migration: &Migration,
+    migration_table: &str,
+    hooks: &Hooks,
+    opts: &RunOpts,
+) -> anyhow::Result<Duration> {
+    run_hook(conn, BEFORE_EACH_HOOK, hooks.before_each.as_deref()).await?;
+    let res = apply_migration(conn, migration, migration_table, opts).await;
+    // reset what the before hook set, even if the migration failed
+    let after = run_hook(conn, AFTER_EACH_HOOK, hooks.after_each.as_deref()).await;
+    let elapsed = res?;
+    after?;
+    Ok(elapsed)
+}
+
+/// A migration which failed under `--savepoint-per-migration`, after the transaction was
+/// rolled back to the savepoint set before it. Displayed as the error of the migration.
+#[derive(Debug)]
+struct RolledBackToSavepoint {
+    version: i64,
+    /// The number of migrations applied before it in the transaction, which are committed.
+    committed: usize,
+    error: anyhow::Error,
+}
+
+impl std::fmt::Display for RolledBackToSavepoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for RolledBackToSavepoint {}
+
+async fn apply_pending(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    applied_migrations: &HashMap<i64, AppliedMigration>,
+    migration_table: &str,
+    latest_version: i64,
+    hooks: &Hooks,
+    allowlist: &ChecksumAllowlist,
+    opts: &RunOpts,
+    notifier: &Notifier<'_>,
+) -> anyhow::Result<()> {
+    if opts.warn_on_mismatch {
+        warn_checksum_mismatches(migrator, applied_migrations, allowlist);
+    }
+
+    let target_version = resolve_target_version(migrator, opts)?;
+
+    let mut applied_count = 0;
+    let mut skipped_count = 0;
+    let mut total_elapsed = Duration::ZERO;
+    let mut planned = Vec::new();
+    let mut followups = Vec::new();
+
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            // Skipping down migrations
+            continue;
+        }
+
+        match applied_migrations.get(&migration.version) {
+            Some(applied_migration) => {
+                if allowlist.is_mismatch(migration, applied_migration) && !opts.warn_on_mismatch {
+                    bail!(MigrateError::VersionMismatch(migration.version));
+                }
+            }
+            None => {
+                let skip = match (target_version, &opts.versions) {
+                    (Some(target_version), _) if migration.version > target_version => true,
+                    (_, Some(versions)) if !versions.contains(migration.version) => true,
+                    _ => false,
+                };
+
+                if opts.strict_ordering && !skip && migration.version < latest_version {
+                    bail!(MigrateError::VersionTooOld(migration.version, latest_version));
+                }
+
+                // its batches would only be committed together with everything else
+                if opts.single_transaction
+                    && !skip
+                    && migration.batch_commit(dialect(conn))?.is_some()
+                {
+                    bail!(
+                        "migration {} has a `-- sqlx:batch-commit` directive, which cannot be \
+                         used with --single-transaction",
+                        migration.version
+                    );
+                }
+
+                if opts.single_transaction && !skip && migration.no_tx(dialect(conn)) {
+                    bail!(
+                        "migration {} has a `-- sqlx:no-transaction` directive, which cannot be \
+                         used with --single-transaction",
+                        migration.version
+                    );
+                }
+
+                if skip {
+                    skipped_count += 1;
+                } else {
+                    applied_count += 1;
+                    planned.push(migration);
+                }
+
+                let baseline =
+                    !skip && is_adoptable_baseline(conn, migration, opts.adopt).await?;
+
+                // an adopted baseline was not actually run, so there is nothing to follow up
+                if !opts.dry_run && !skip && !baseline {
+                    if let Some(message) = migration.manual_followup(dialect(conn))? {
+                        followups.push((migration, message));
+                    }
+                }
+
+                let elapsed = if opts.dry_run || skip {
+                    Duration::new(0, 0)
+                } else if baseline {
+                    conn.record_applied(migration, migration_table.to_owned())
+                        .await?;
+                    Duration::new(0, 0)
+                } else if opts.savepoint_per_migration {
+                    let savepoint = format!("sqlx_migration_{}", migration.version);
+                    conn.execute(&*format!("SAVEPOINT {savepoint}")).await?;
+
+                    match apply_with_hooks(conn, migration, migration_table, hooks, opts).await {
+                        Ok(elapsed) => {
+                            conn.execute(&*format!("RELEASE SAVEPOINT {savepoint}"))
+                                .await?;
+                            elapsed
+                        }
+                        Err(error) => {
+                            conn.execute(&*format!("ROLLBACK TO SAVEPOINT {savepoint}"))
+                                .await?;
+
+                            // the caller commits the migrations before this one
+                            followups.retain(|(m, _)| m.version != migration.version);
+                            if !followups.is_empty() {
+                                record_followups(conn, migration_table, &followups).await?;
+                            }
+
+                            bail!(RolledBackToSavepoint {
+                                version: migration.version,
+                                committed: applied_count - 1,
+                                error,
+                            });
+                        }
+                    }
+                } else {
+                    apply_with_hooks(conn, migration, migration_table, hooks, opts).await?
+                };
+                total_elapsed += elapsed;
+
+                let text = if skip {
+                    "Skipped"
+                } else if baseline && opts.dry_run {
+                    "Can adopt"
+                } else if baseline {
+                    "Adopted"
+                } else if opts.dry_run {
+                    "Can apply"
+                } else {
+                    "Applied"
+                };
+
+                println!(
+                    "{} {}/{} {} {}",
+                    text,
+                    style(migration.version).cyan(),
+                    style(migration.migration_type.label()).green(),
+                    migration.description,
+                    style(format!("({elapsed:?})")).dim()
+                );
+
+                if opts.show_sql && !skip && !baseline {
+                    print_migration_sql(migration, dialect(conn))?;
+                }
+
+                if !opts.dry_run && !skip {
+                    notifier
+                        .send(
+                            NotifyEvent::Apply,
+                            serde_json::json!({
+                                "status": text.to_lowercase(),
+                                "version": migration.version,
+                                "description": migration.description,
+                                "elapsed_ms": elapsed.as_millis() as u64,
+                            }),
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+
+    if opts.versions.is_some() {
+        println!(
+            "{} {} migrations, skipped {} pending migrations outside --versions",
+            if opts.dry_run { "Can apply" } else { "Applied" },
+            applied_count,
+            skipped_count
+        );
+    } else if applied_count > 0 {
+        if opts.dry_run {
+            println!("{applied_count} migrations can be applied");
+        } else {
+            println!(
+                "Applied {} migrations in {:.2}s",
+                applied_count,
+                total_elapsed.as_secs_f64()
+            );
+        }
+    }
+
+    if let Some(path) = &opts.emit_undo {
+        write_undo_script(migrator, &planned, migration_table, path)?;
+    }
+
+    if !followups.is_empty() {
+        record_followups(conn, migration_table, &followups).await?;
+    }
+
+    Ok(())
+}
+
+/// Print the SQL `migration` would execute, for `run --dry-run --show-sql`.
+fn print_migration_sql(migration: &Migration, dialect: &str) -> anyhow::Result<()> {
+    let header = format!(
+        "-- {}_{} ",
+        migration.version,
+        migration.description.replace(' ', "_")
+    );
+
+    println!("{}", style(format!("{header:-<80}")).dim());
+    println!("{}", migration.sql_for(dialect)?.trim_end());
+    println!("{}", style("-".repeat(80)).dim());
+
+    Ok(())
+}
+
+/// The migration dialect matching the database `conn` is connected to.
+fn dialect(conn: &AnyConnection) -> &'static str {
+    match conn.backend_name() {
+        "PostgreSQL" => "postgres",
+        "MySQL" => "mysql",
+        _ => "sqlite",
+    }
+}
+
+/// Write a script reverting the `planned` migrations to `path`, for `run --emit-undo`.
+///
+/// Migrations without a down migration cannot be reverted by the script; they are flagged in
+/// both the script and the output, and their rows are left in the migrations table.
+fn write_undo_script(
+    migrator: &Migrator,
+    planned: &[&Migration],
+    migration_table: &str,
+    path: &str,
+) -> anyhow::Result<()> {
+    let mut script = String::from(
+        "-- Generated by `sqlx migrate run --emit-undo`: reverts the migrations it would apply,\n\
+         -- newest first.\n",
+    );
+    let mut irreversible = Vec::new();
+
+    for migration in planned.iter().rev() {
+        script.push('\n');
+
+        match migrator.down_sql(migration.version) {
+            Some(sql) => {
+                writeln!(
+                    script,
+                    "-- {} {}\n{}",
+                    migration.version,
+                    migration.description,
+                    sql.trim_end()
+                )?;
+                writeln!(
+                    script,
+                    "DELETE FROM {migration_table} WHERE version = {};",
+                    migration.version
+                )?;
+            }
+            None => {
+                writeln!(
+                    script,
+                    "-- !!! IRREVERSIBLE: {} {} has no down migration and must be reverted by \
+                     hand !!!",
+                    migration.version, migration.description
+                )?;
+                irreversible.push(migration);
+            }
+        }
+    }
+
+    fs::write(path, script).with_context(|| format!("failed to write undo script {path}"))?;
+
+    for migration in &irreversible {
+        println!(
+            "{} migration {} ({}) has no down migration; the undo script cannot revert it",
+            style("warning:").bold().yellow(),
+            style(migration.version).cyan(),
+            migration.description
+        );
+    }
+
+    println!(
+        "Wrote an undo script for {} of {} migrations to {}",
+        planned.len() - irreversible.len(),
+        planned.len(),
+        style(path).cyan()
+    );
+
+    Ok(())
+}
+
+pub async fn revert(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    opts: &RevertOpts,
+) -> anyhow::Result<()> {
+    let mut conn = crate::connect(&connect_opts).await?;
+
+    let res = revert_with_conn(&mut conn, migration_source, migration_table, opts).await;
+
+    let _ = conn.close().await;
+
+    res
+}
+
+/// Like [`revert`], but on an existing connection, e.g. one checked out from a pool.
+///
+/// The connection is left open.
+pub async fn revert_with_conn(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RevertOpts,
+) -> anyhow::Result<()> {
+    lock_migrations(conn, opts.migration_lock_timeout).await?;
+
+    let res = revert_locked(conn, migration_source, migration_table, opts).await;
+
+    let unlock = conn.unlock().await;
+
+    res?;
+    unlock?;
+
+    Ok(())
+}
+
+async fn revert_locked(
+    conn: &mut AnyConnection,
+    migration_source: &str,
+    migration_table: Option<String>,
+    opts: &RevertOpts,
+) -> anyhow::Result<()> {
+    let dry_run = opts.dry_run;
+    let target_version = opts.target_version();
+
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    if let Some(target_version) = target_version {
+        if target_version != 0 && !migrator.version_exists(target_version) {
+            return Err(version_not_present(&migrator, target_version));
+        }
+    }
+
+    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    conn.ensure_migrations_table(migration_table.to_owned()).await?;
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        if !opts.no_dirty_check {
+            bail!(MigrateError::DirtyRecord(record));
+        }
+
+        warn_ignoring_dirty(record.version);
+    }
+
+    let applied_migrations = conn.list_applied_migrations(migration_table.to_owned()).await?;
+    validate_applied_migrations(&applied_migrations, &migrator, *opts.ignore_missing)?;
+
+    if let Some(release) = &opts.release {
+        return revert_release(
+            conn,
+            &migrator,
+            &migration_table,
+            &applied_migrations,
+            release,
+            opts,
+        )
+        .await;
+    }
+
+    let latest_version = conn
+        .latest_applied_migration(migration_table.to_owned())
+        .await?
+        .map_or(0, |migration| migration.version);
+    if let Some(target_version) = target_version {
+        if target_version > latest_version {
+            bail!(MigrateError::VersionTooNew(target_version, latest_version));
+        }
+    }
+
+    if opts.all && !applied_migrations.is_empty() {
+        let down_migrations: HashMap<_, _> = migrator
+            .iter()
+            .filter(|m| m.migration_type.is_down_migration())
+            .map(|m| (m.version, m))
+            .collect();
+
+        let missing_down: Vec<_> = applied_migrations
+            .iter()
+            .filter(|m| !down_migrations.contains_key(&m.version))
+            .map(|m| m.version.to_string())
+            .collect();
+
+        if !missing_down.is_empty() {
+            bail!(
+                "cannot revert all migrations: no down migration for {}",
+                missing_down.join(", ")
+            );
+        }
+
+        if !dry_run && !opts.yes {
+            for applied in applied_migrations.iter().rev() {
+                let migration = down_migrations[&applied.version];
+                println!(
+                    "  {}/{}",
+                    style(migration.version).cyan(),
+                    migration.description
+                );
+            }
+
+            if !confirm_revert(&format!(
+                "Revert all {} applied migrations?",
+                applied_migrations.len()
+            ))? {
+                return Ok(());
+            }
+        }
+    }
+
+    let applied_migrations: HashMap<_, _> = applied_migrations
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    if let Some(steps) = opts.steps {
+        let revertible = migrator
+            .iter()
+            .filter(|m| m.migration_type.is_down_migration())
+            .filter(|m| applied_migrations.contains_key(&m.version))
+            .count();
+
+        if revertible < steps as usize {
+            bail!(
+                "cannot revert {steps} migrations: only {revertible} applied migrations have \
+                 a down migration"
+            );
+        }
+    }
+
+    // The migrator only yields the down migrations it found, so a deleted down file would
+    // otherwise make the loop below silently skip that version.
+    if !opts.all {
+        let mut to_revert: Vec<i64> = applied_migrations.keys().copied().collect();
+        to_revert.sort_unstable_by(|a, b| b.cmp(a));
+        let to_revert: Vec<i64> = match target_version {
+            Some(target_version) => to_revert
+                .into_iter()
+                .filter(|version| *version > target_version)
+                .collect(),
+            None => to_revert
+                .into_iter()
+                .take(opts.steps.unwrap_or(1) as usize)
+                .collect(),
+        };
+
+        let down_migrations: Vec<_> = to_revert
+            .iter()
+            .filter_map(|version| {
+                migrator
+                    .iter()
+                    .find(|m| m.version == *version && m.migration_type.is_down_migration())
+            })
+            .collect();
+
+        if let Some(version) = to_revert
+            .iter()
+            .find(|version| !down_migrations.iter().any(|m| m.version == **version))
+        {
+            bail!(MigrateError::DownMissing(*version));
+        }
+
+        // reverting down to a low version can drop a lot of schema
+        if let Some(target_version) = target_version {
+            if !dry_run && !opts.yes && !down_migrations.is_empty() {
+                for migration in &down_migrations {
+                    println!(
+                        "  {}/{}",
+                        style(migration.version).cyan(),
+                        migration.description
+                    );
+                }
+
+                if !confirm_revert(&format!(
+                    "Revert {} migrations down to version {target_version}?",
+                    down_migrations.len()
+                ))? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let mut reverted = 0;
+    let mut is_applied = false;
+    for migration in migrator.iter().rev() {
+        if !migration.migration_type.is_down_migration() {
+            // Skipping non down migration
+            // This will skip any simple or up migration file
+            continue;
+        }
+
+        if applied_migrations.contains_key(&migration.version) {
+            let skip = match target_version {
+                Some(target_version) if migration.version <= target_version => true,
+                _ => false,
+            };
+            let elapsed = if dry_run || skip {
+                Duration::new(0, 0)
+            } else {
+                conn.revert(migration, migration_table.to_owned()).await?
+            };
+            let text = if skip {
+                "Skipped"
+            } else if dry_run {
+                "Can apply"
+            } else {
+                "Applied"
+            };
+
+            println!(
+                "{} {}/{} {} {}",
+                text,
+                style(migration.version).cyan(),
+                style(migration.migration_type.label()).green(),
+                migration.description,
+                style(format!("({elapsed:?})")).dim()
+            );
+
+            is_applied = true;
+            reverted += 1;
+
+            // Only a single migration, or `--steps` migrations, will be reverted at a time
+            // if no target version is supplied, so we break.
+            if target_version.is_none() && reverted >= opts.steps.unwrap_or(1) {
+                break;
+            }
+        }
+    }
+    if !is_applied {
+        println!("No migrations available to revert");
+    }
+
+    if opts.all && !dry_run {
+        let remaining = conn.list_applied_migrations(migration_table).await?.len();
+
+        if remaining == 0 {
+            println!("No migrations are applied");
+        } else {
+            bail!("{remaining} migrations are still applied after reverting all migrations");
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask `question` before reverting the migrations just listed.
+///
+/// Without a terminal the question could not be answered, so this fails instead, asking for
+/// `--yes`.
+fn confirm_revert(question: &str) -> anyhow::Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("stdin is not a terminal, so the revert cannot be confirmed; pass --yes to confirm it");
+    }
+
+    Ok(crate::ask_to_continue(question))
+}
+
+/// Revert the applied migrations recorded under `release`, newest first, for
+/// `revert --release`.
+async fn revert_release(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    migration_table: &str,
+    applied_migrations: &[AppliedMigration],
+    release: &str,
+    opts: &RevertOpts,
+) -> anyhow::Result<()> {
+    validate_release_id(release)?;
+
+    let table = releases_table(migration_table);
+    ensure_releases_table(conn, &table).await?;
+
+    let versions: Vec<i64> = sqlx::query_scalar(&format!(
+        "SELECT version FROM {table} WHERE release_id = '{release}' ORDER BY version DESC"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // reverted since, e.g. by a plain `revert`
+    let versions: Vec<i64> = versions
+        .into_iter()
+        .filter(|version| applied_migrations.iter().any(|m| m.version == *version))
+        .collect();
+
+    if versions.is_empty() {
+        bail!(
+            "no applied migrations were recorded under release {release}; releases are \
+             recorded by `migrate run --audit-runs --release-id`"
+        );
+    }
+
+    let mut migrations = Vec::with_capacity(versions.len());
+    for version in versions {
+        match migrator
+            .iter()
+            .find(|m| m.version == version && m.migration_type.is_down_migration())
+        {
+            Some(migration) => migrations.push(migration),
+            None => bail!(MigrateError::DownMissing(version)),
+        }
+    }
+
+    if !opts.dry_run && !opts.yes {
+        for migration in &migrations {
+            println!(
+                "  {}/{}",
+                style(migration.version).cyan(),
+                migration.description
+            );
+        }
+
+        if !confirm_revert(&format!(
+            "Revert the {} migrations of release {release}?",
+            migrations.len()
+        ))? {
+            return Ok(());
+        }
+    }
+
+    for migration in migrations {
+        let elapsed = if opts.dry_run {
+            Duration::new(0, 0)
+        } else {
+            let elapsed = conn
+                .revert(migration, migration_table.to_owned())
+                .await?;
+            conn.execute(&*format!(
+                "DELETE FROM {table} WHERE version = {}",
+                migration.version
+            ))
+            .await?;
+            elapsed
+        };
+
+        println!(
+            "{} {}/{} {} {}",
+            if opts.dry_run { "Can apply" } else { "Applied" },
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+            style(format!("({elapsed:?})")).dim()
+        );
+    }
+
+    Ok(())
+}
+
+fn seed_table(migration_table: &str) -> String {
+    format!("{migration_table}_seeds")
+}
+
+/// Run the seed with `version`, or with `all` every seed that has not been run yet.
+pub async fn seed(
+    seed_source: &str,
+    version: Option<i64>,
+    all: bool,
+    dry_run: bool,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    match (version, all) {
+        (Some(_), true) => bail!("--version and --all cannot be combined"),
+        (None, false) => bail!("pass --version to run a single seed, or --all to run every seed"),
+        _ => {}
+    }
+
+    // seed files are named like migrations, so reuse the migration source to read them
+    let seeds = Migrator::new(Path::new(seed_source), None)
+        .await
+        .with_context(|| format!("failed to read seeds from {seed_source}"))?;
+
+    if let Some(version) = version {
+        if !seeds.version_exists(version) {
+            bail!("no seed with version {version} in {seed_source}");
+        }
+    }
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    let res = seed_with_conn(&mut conn, &seeds, version, dry_run, &migration_table).await;
+
+    // close the connection on failure too, as `run` does
+    let _ = conn.close().await;
+
+    res
+}
+
+async fn seed_with_conn(
+    conn: &mut AnyConnection,
+    seeds: &Migrator,
+    version: Option<i64>,
+    dry_run: bool,
+    migration_table: &str,
+) -> anyhow::Result<()> {
+    let table = seed_table(migration_table);
+
+    conn.execute(&*format!(
+        "CREATE TABLE IF NOT EXISTS {table} ( version BIGINT PRIMARY KEY )"
+    ))
+    .await?;
+
+    let seeded: HashSet<i64> = sqlx::query_scalar(&format!("SELECT version FROM {table}"))
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    for seed in seeds.iter() {
+        if matches!(version, Some(version) if seed.version != version) {
+            continue;
+        }
+
+        if seeded.contains(&seed.version) {
+            if version.is_some() {
+                println!("Seed {} has already been run", style(seed.version).cyan());
+            }
+            continue;
+        }
+
+        let elapsed = if dry_run {
+            Duration::new(0, 0)
+        } else {
+            let start = Instant::now();
+
+            let mut tx = conn.begin().await?;
+            (&mut *tx).execute(&*seed.sql).await?;
+            (&mut *tx)
+                .execute(&*format!(
+                    "INSERT INTO {table} ( version ) VALUES ( {} )",
+                    seed.version
+                ))
+                .await?;
+            tx.commit().await?;
+
+            start.elapsed()
+        };
+        let text = if dry_run { "Can seed" } else { "Seeded" };
+
+        println!(
+            "{} {} {} {}",
+            text,
+            style(seed.version).cyan(),
+            seed.description,
+            style(format!("({elapsed:?})")).dim()
+        );
+    }
+
+    Ok(())
+}
+
+fn snapshot_table(migration_table: &str) -> String {
+    format!("{migration_table}_snapshots")
+}
+
+fn validate_snapshot_name(name: &str) -> anyhow::Result<()> {
+    // The name is spliced into SQL, so only allow a conservative set of characters.
+    anyhow::ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')),
+        "invalid snapshot name {name:?}: only letters, digits, `_`, `-` and `.` are allowed"
+    );
+
+    Ok(())
+}
+
+async fn ensure_snapshot_table(conn: &mut AnyConnection, table: &str) -> anyhow::Result<()> {
+    conn.execute(&*format!(
+        "CREATE TABLE IF NOT EXISTS {table} ( name VARCHAR(255) PRIMARY KEY, versions TEXT NOT NULL )"
+    ))
+    .await?;
+
+    Ok(())
+}
+
+async fn load_snapshot(
+    conn: &mut AnyConnection,
+    table: &str,
+    name: &str,
+) -> anyhow::Result<Option<HashSet<i64>>> {
+    let versions: Option<String> =
+        sqlx::query_scalar(&format!("SELECT versions FROM {table} WHERE name = '{name}'"))
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    versions
+        .map(|versions| {
+            versions
+                .split(',')
+                .filter(|v| !v.is_empty())
+                .map(str::parse)
+                .collect::<Result<HashSet<i64>, _>>()
+        })
+        .transpose()
+        .with_context(|| format!("snapshot `{name}` is corrupt"))
+}
+
+pub async fn snapshot(
+    name: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    force: bool,
+) -> anyhow::Result<()> {
+    validate_snapshot_name(name)?;
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    let table = snapshot_table(&migration_table);
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    ensure_snapshot_table(&mut conn, &table).await?;
+
+    if load_snapshot(&mut conn, &table, name).await?.is_some() {
+        if !force {
+            bail!("snapshot `{name}` already exists; use --force to replace it");
+        }
+
+        conn.execute(&*format!("DELETE FROM {table} WHERE name = '{name}'"))
+            .await?;
+    }
+
+    let applied_migrations = conn.list_applied_migrations(migration_table).await?;
+    let versions = applied_migrations
+        .iter()
+        .map(|m| m.version.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    conn.execute(&*format!(
+        "INSERT INTO {table} ( name, versions ) VALUES ( '{name}', '{versions}' )"
+    ))
+    .await?;
+
+    println!(
+        "Saved snapshot {} of {} applied migrations",
+        style(name).cyan(),
+        applied_migrations.len()
+    );
+
+    let _ = conn.close().await;
+
+    Ok(())
+}
+
+pub async fn rollback_to_snapshot(
+    name: &str,
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    dry_run: bool,
+    confirm: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    validate_snapshot_name(name)?;
+
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    let res = rollback_to_snapshot_with_conn(
+        &mut conn,
+        &migrator,
+        name,
+        dry_run,
+        confirm,
+        &migration_table,
+    )
+    .await;
+
+    let _ = conn.close().await;
+
+    res
+}
+
+/// Like [`rollback_to_snapshot_locked`], holding the migration lock for the whole restore, so that
+/// no migration is applied or reverted between listing the applied migrations and reverting those
+/// missing from the snapshot. The lock is released afterwards even if a revert fails.
+async fn rollback_to_snapshot_with_conn(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    name: &str,
+    dry_run: bool,
+    confirm: bool,
+    migration_table: &str,
+) -> anyhow::Result<()> {
+    conn.lock().await?;
+
+    let res =
+        rollback_to_snapshot_locked(conn, migrator, name, dry_run, confirm, migration_table).await;
+
+    let unlock = conn.unlock().await;
+
+    res?;
+    unlock?;
+
+    Ok(())
+}
+
+async fn rollback_to_snapshot_locked(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    name: &str,
+    dry_run: bool,
+    confirm: bool,
+    migration_table: &str,
+) -> anyhow::Result<()> {
+    let table = snapshot_table(migration_table);
+
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        bail!(MigrateError::DirtyRecord(record));
+    }
+
+    ensure_snapshot_table(conn, &table).await?;
+    let Some(snapshot) = load_snapshot(conn, &table, name).await? else {
+        bail!("no snapshot named `{name}`");
+    };
+
+    let applied_migrations = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?;
+
+    let down_migrations: HashMap<_, _> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_down_migration())
+        .map(|m| (m.version, m))
+        .collect();
+
+    // applied since the snapshot was taken, newest first
+    let mut to_revert = Vec::new();
+    for applied in applied_migrations.iter().rev() {
+        if snapshot.contains(&applied.version) {
+            continue;
+        }
+
+        match down_migrations.get(&applied.version) {
+            Some(migration) => to_revert.push(*migration),
+            None => bail!(
+                "migration {} was applied after snapshot `{name}` but has no down migration",
+                applied.version
+            ),
+        }
+    }
+
+    let no_longer_applied = snapshot
+        .iter()
+        .filter(|version| !applied_migrations.iter().any(|m| m.version == **version))
+        .count();
+    if no_longer_applied > 0 {
+        println!(
+            "{} {} migrations in snapshot `{}` are no longer applied; use `sqlx migrate run` to re-apply them",
+            style("warning:").bold().yellow(),
+            no_longer_applied,
+            name
+        );
+    }
+
+    if to_revert.is_empty() {
+        println!("Database already matches snapshot `{name}`");
+        return Ok(());
+    }
+
+    if !dry_run && confirm {
+        for migration in &to_revert {
+            println!("  {}/{}", style(migration.version).cyan(), migration.description);
+        }
+
+        // like `confirm_revert`, but this command only has the short flag
+        if !io::stdin().is_terminal() {
+            bail!("stdin is not a terminal, so the rollback cannot be confirmed; pass -y to confirm it");
+        }
+
+        if !crate::ask_to_continue(&format!(
+            "Revert {} migrations to return to snapshot {}?",
+            to_revert.len(),
+            style(name).cyan()
+        )) {
+            return Ok(());
+        }
+    }
+
+    for migration in to_revert {
+        let elapsed = if dry_run {
+            Duration::new(0, 0)
+        } else {
+            conn.revert(migration, migration_table.to_owned()).await?
+        };
+        let text = if dry_run { "Can apply" } else { "Applied" };
+
+        println!(
+            "{} {}/{} {} {}",
+            text,
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+            style(format!("({elapsed:?})")).dim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Revert the applied migration `version` and apply it again, with the current local files.
+pub async fn rerun(
+    migration_source: &str,
+    version: i64,
+    connect_opts: &ConnectOpts,
+    dry_run: bool,
+    confirm: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+
+    let find = |down: bool| {
+        migrator
+            .iter()
+            .find(|m| m.version == version && m.migration_type.is_down_migration() == down)
+    };
+    let Some(up) = find(false) else {
+        bail!(MigrateError::VersionNotPresent(version));
+    };
+    let Some(down) = find(true) else {
+        bail!("migration {version} has no down migration to revert it with");
+    };
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        bail!(MigrateError::DirtyRecord(record));
+    }
+
+    let applied_migrations = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?;
+    if !applied_migrations.iter().any(|m| m.version == version) {
+        bail!("migration {version} is not applied; use `sqlx migrate run` to apply it");
+    }
+
+    if dry_run {
+        for migration in [down, up] {
+            println!(
+                "Can apply {}/{} {}",
+                style(migration.version).cyan(),
+                style(migration.migration_type.label()).green(),
+                migration.description,
+            );
+        }
+
+        let _ = conn.close().await;
+        return Ok(());
+    }
+
+    if confirm {
+        println!(
+            "{} the down migration may destroy data which the up migration does not restore",
+            style("warning:").bold().yellow()
+        );
+
+        if !crate::ask_to_continue(&format!(
+            "Revert and re-apply migration {}/{}?",
+            style(version).cyan(),
+            up.description
+        )) {
+            let _ = conn.close().await;
+            return Ok(());
+        }
+    }
+
+    let res = if conn.backend_name() == "PostgreSQL" {
+        // `revert()` and `apply()` nest their own transactions inside this one as savepoints
+        match conn.begin().await {
+            Ok(mut tx) => match rerun_migration(&mut tx, down, up, &migration_table).await {
+                Ok(()) => tx.commit().await.map_err(Into::into),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        }
+    } else {
+        rerun_migration(&mut conn, down, up, &migration_table).await
+    };
+
+    let _ = conn.close().await;
+
+    res
+}
+
+async fn rerun_migration(
+    conn: &mut AnyConnection,
+    down: &Migration,
+    up: &Migration,
+    migration_table: &str,
+) -> anyhow::Result<()> {
+    let print = |migration: &Migration, elapsed: Duration| {
+        println!(
+            "Applied {}/{} {} {}",
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+            style(format!("({elapsed:?})")).dim()
+        );
+    };
+
+    let elapsed = conn.revert(down, migration_table.to_owned()).await?;
+    print(down, elapsed);
+
+    // records the checksum of the local migration
+    let elapsed = conn.apply(up, migration_table.to_owned()).await?;
+    print(up, elapsed);
+
+    Ok(())
+}
+
+/// Derive a version and description from a file named `<VERSION>_<DESCRIPTION>.sql`.
+fn parse_file_name(path: &Path) -> Option<(i64, String)> {
+    let file_name = path.file_name()?.to_str()?;
+    let (version, description) = file_name.split_once('_')?;
+    let description = description.strip_suffix(".sql")?;
+
+    Some((version.parse().ok()?, description.replace('_', " ")))
+}
+
+pub async fn apply_file(
+    path: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    dry_run: bool,
+    record: bool,
+) -> anyhow::Result<()> {
+    let path = Path::new(path);
+    if !path.is_file() {
+        bail!("no such file: {}", path.display());
+    }
+
+    let sql = String::from_utf8(fs::read(path)?)
+        .with_context(|| format!("{} is not valid UTF-8", path.display()))?;
+
+    let (version, description) = match parse_file_name(path) {
+        Some(parsed) => parsed,
+        None if record => bail!(
+            "cannot derive a version from {}; expected a file named `<VERSION>_<DESCRIPTION>.sql`, \
+             or pass --no-record",
+            path.display()
+        ),
+        None => (0, path.display().to_string()),
+    };
+
+    let migration = Migration::new(
+        version,
+        Cow::Owned(description),
+        MigrationType::Simple,
+        Cow::Owned(sql),
+    );
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        bail!(MigrateError::DirtyRecord(record));
+    }
+
+    if record {
+        let applied_migrations = conn
+            .list_applied_migrations(migration_table.to_owned())
+            .await?;
+        if applied_migrations.iter().any(|m| m.version == version) {
+            bail!("migration {version} is already applied");
+        }
+    }
+
+    let elapsed = if dry_run {
+        Duration::new(0, 0)
+    } else if record {
+        conn.apply(&migration, migration_table.to_owned()).await?
+    } else {
+        let start = Instant::now();
+        let mut tx = conn.begin().await?;
+        (&mut *tx).execute(&*migration.sql).await?;
+        tx.commit().await?;
+        start.elapsed()
+    };
+    let text = if dry_run { "Can apply" } else { "Applied" };
+
+    if record {
+        println!(
+            "{} {}/{} {} {}",
+            text,
+            style(migration.version).cyan(),
+            style(migration.migration_type.label()).green(),
+            migration.description,
+            style(format!("({elapsed:?})")).dim()
+        );
+    } else {
+        println!(
+            "{} {} {}",
+            text,
+            style(path.display()).cyan(),
+            style(format!("({elapsed:?})")).dim()
+        );
+    }
+
+    let _ = conn.close().await;
+
+    Ok(())
+}
+
+/// Rewrite the checksums recorded for applied migrations to match the given checksum mode.
+///
+/// Only migrations whose recorded checksum matches the local file in the other mode are
+/// updated; migrations which were actually modified after being applied are reported and
+/// left alone.
+pub async fn repair_checksums(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    ignore_comments: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let applied_migrations = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?;
+
+    let local: HashMap<_, _> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| (m.version, m))
+        .collect();
+
+    let mut repaired = 0;
+    let mut modified = 0;
+
+    for applied_migration in &applied_migrations {
+        let Some(migration) = local.get(&applied_migration.version) else {
+            continue;
+        };
+
+        let with_comments = Migration::compute_checksum(&migration.sql, migration.dialects());
+        let without_comments = migration.checksum_ignoring_comments();
+
+        let (wanted, other) = if ignore_comments {
+            (without_comments, with_comments)
+        } else {
+            (with_comments, without_comments)
+        };
+
+        if *applied_migration.checksum == *wanted {
+            continue;
+        }
+
+        if *applied_migration.checksum != *other {
+            modified += 1;
+            println!(
+                "{} {}/{} was modified after being applied; not repairing its checksum",
+                style("warning:").bold().yellow(),
+                style(migration.version).cyan(),
+                migration.description,
+            );
+            continue;
+        }
+
+        if !dry_run {
+            conn.update_checksum(migration.version, wanted, migration_table.to_owned())
+                .await?;
+        }
+
+        repaired += 1;
+        println!(
+            "{}/{} {}",
+            style(migration.version).cyan(),
+            style(if dry_run { "Can repair" } else { "Repaired" }).green(),
+            migration.description,
+        );
+    }
+
+    let _ = conn.close().await;
+
+    if repaired == 0 && modified == 0 {
+        println!("All checksums are up to date");
+    }
+
+    Ok(())
+}
+
+pub async fn fix_checksums(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    ignore_comments: bool,
+    dry_run: bool,
+    confirm: bool,
+) -> anyhow::Result<()> {
+    let mut migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    migrator.set_checksum_ignore_comments(ignore_comments);
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let applied_migrations: HashMap<_, _> = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    let mismatched: Vec<_> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .filter(|m| {
+            applied_migrations
+                .get(&m.version)
+                .map_or(false, |applied| applied.checksum != m.checksum)
+        })
+        .collect();
+
+    if mismatched.is_empty() {
+        println!("All checksums match the local migrations");
+        let _ = conn.close().await;
+        return Ok(());
+    }
+
+    if !dry_run && confirm {
+        for migration in &mismatched {
+            println!(
+                "  {}/{}",
+                style(migration.version).cyan(),
+                migration.description
+            );
+        }
+
+        if !crate::ask_to_continue(&format!(
+            "Record the local checksums of these {} migrations? Only do this if their changes \
+             do not affect the schema.",
+            mismatched.len()
+        )) {
+            let _ = conn.close().await;
+            return Ok(());
+        }
+    }
+
+    for migration in &mismatched {
+        if !dry_run {
+            conn.update_checksum(
+                migration.version,
+                migration.checksum.to_vec(),
+                migration_table.to_owned(),
+            )
+            .await?;
+        }
+
+        println!(
+            "{}/{} {}",
+            style(migration.version).cyan(),
+            style(if dry_run { "Can fix" } else { "Fixed" }).green(),
+            migration.description,
+        );
+    }
+
+    let _ = conn.close().await;
+
+    Ok(())
+}
+
+#[derive(Serialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Serialize)]
+struct AuditFinding {
+    severity: Severity,
+    kind: &'static str,
+    version: i64,
+    message: String,
+}
+
+pub async fn audit(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let applied_migrations = conn.list_applied_migrations(migration_table).await?;
+    let _ = conn.close().await;
+
+    let local: HashMap<_, _> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| (m.version, m))
+        .collect();
+    let applied: HashSet<_> = applied_migrations.iter().map(|m| m.version).collect();
+    let latest_applied = applied_migrations.iter().map(|m| m.version).max();
+
+    let mut findings = Vec::new();
+
+    for applied_migration in &applied_migrations {
+        match local.get(&applied_migration.version) {
+            None => findings.push(AuditFinding {
+                severity: Severity::Error,
+                kind: "orphan",
+                version: applied_migration.version,
+                message: "applied but missing from the migrations directory".to_owned(),
+            }),
+            Some(migration)
+                if !applied_migration.checksum.is_empty()
+                    && migration.checksum != applied_migration.checksum =>
+            {
+                findings.push(AuditFinding {
+                    severity: Severity::Error,
+                    kind: "checksum_mismatch",
+                    version: applied_migration.version,
+                    message: format!(
+                        "applied with checksum {} but the local file has checksum {}",
+                        short_checksum(&applied_migration.checksum),
+                        short_checksum(&migration.checksum)
+                    ),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(latest_applied) = latest_applied {
+        for migration in migrator.iter() {
+            if migration.migration_type.is_up_migration()
+                && migration.version < latest_applied
+                && !applied.contains(&migration.version)
+            {
+                findings.push(AuditFinding {
+                    severity: Severity::Warning,
+                    kind: "out_of_order",
+                    version: migration.version,
+                    message: format!(
+                        "pending but older than the latest applied migration {latest_applied}"
+                    ),
+                });
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| f.version);
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+
+    if json {
+        let output = serde_json::json!({
+            "errors": errors,
+            "warnings": warnings,
+            "findings": findings,
+        });
+
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if findings.is_empty() {
+        println!("No problems found");
+    } else {
+        for finding in &findings {
+            let severity = match finding.severity {
+                Severity::Warning => style("warning:").bold().yellow(),
+                Severity::Error => style("error:").bold().red(),
+            };
+
+            println!(
+                "{} {} {}: {}",
+                severity,
+                style(finding.version).cyan(),
+                finding.kind.replace('_', " "),
+                finding.message
+            );
+        }
+
+        println!("{errors} errors, {warnings} warnings");
+    }
+
+    if errors > 0 {
+        bail!("audit found {errors} errors");
+    }
+
+    Ok(())
+}
+
+/// Compare the checksum recorded for every applied migration with its local file, failing
+/// with the versions which differ or, unless `ignore_missing`, have no local file.
+pub async fn verify(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    ignore_missing: bool,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let allowlist = ChecksumAllowlist::load(migration_source)?;
+
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let applied_migrations = conn.list_applied_migrations(migration_table).await?;
+    let _ = conn.close().await;
+
+    let local: HashMap<_, _> = migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| (m.version, m))
+        .collect();
+
+    let mut failed = Vec::new();
+    let mut missing = Vec::new();
+
+    for applied_migration in &applied_migrations {
+        let Some(migration) = local.get(&applied_migration.version) else {
+            missing.push(applied_migration.version);
+            continue;
+        };
+
+        if allowlist.is_mismatch(migration, applied_migration) {
+            println!(
+                "{} {}/{}: applied with checksum {} but the local file has checksum {}",
+                style("mismatch:").bold().red(),
+                style(migration.version).cyan(),
+                migration.description,
+                short_checksum(&applied_migration.checksum),
+                short_checksum(&migration.checksum)
+            );
+            failed.push(migration.version);
+        }
+    }
+
+    if ignore_missing {
+        print_missing_migrations(&missing, migration_source);
+    } else {
+        for version in &missing {
+            println!(
+                "{} {}: applied but missing from {}",
+                style("missing:").bold().red(),
+                style(version).cyan(),
+                migration_source
+            );
+        }
+        failed.extend(&missing);
+    }
+
+    if !failed.is_empty() {
+        failed.sort_unstable();
+        let versions: Vec<_> = failed.iter().map(i64::to_string).collect();
+        bail!(
+            "{} applied migrations failed verification: {}",
+            failed.len(),
+            versions.join(", ")
+        );
+    }
+
+    println!(
+        "Verified {} applied migrations",
+        applied_migrations.len() - missing.len()
+    );
+
+    Ok(())
+}
+
+pub async fn fingerprint(
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+    let dirty_version = conn.dirty_version(migration_table.to_owned()).await?;
+    let applied_migrations = conn.list_applied_migrations(migration_table).await?;
+    let _ = conn.close().await;
+
+    if let Some(version) = dirty_version {
+        // stderr, so that stdout can still be compared as-is
+        eprintln!(
+            "{} the database is dirty on version {}; the fingerprint includes the failed migration",
+            style("warning:").bold().yellow(),
+            style(version).cyan()
+        );
+    }
+
+    // Fixed-width fields, so that no two histories hash the same input.
+    let mut hasher = Sha256::new();
+    for migration in &applied_migrations {
+        hasher.update(migration.version.to_be_bytes());
+        hasher.update((migration.checksum.len() as u64).to_be_bytes());
+        hasher.update(&migration.checksum);
+    }
+    let fingerprint = short_checksum(&hasher.finalize());
+
+    if json {
+        let output = serde_json::json!({
+            "fingerprint": fingerprint,
+            "migrations": applied_migrations
+                .iter()
+                .map(|m| serde_json::json!({
+                    "version": m.version,
+                    "checksum": short_checksum(&m.checksum),
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        println!("{fingerprint}");
+    }
+
+    Ok(())
+}
+
+pub fn print_table_ddl(
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    index_installed_on: bool,
+) -> anyhow::Result<()> {
+    let url = connect_opts.required_db_url()?;
+    let mut migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    // `--target-schema` puts the table in that schema, through the `search_path`
+    if let Some(schema) = &connect_opts.target_schema {
+        if !url.starts_with("postgres") {
+            bail!("--target-schema is only supported on PostgreSQL");
+        }
+
+        if !migration_table.contains('.') {
+            migration_table = format!("{}.{migration_table}", crate::quote_ident(schema));
+        }
+    }
+
+    // nothing connects, which would install them
+    sqlx::any::install_default_drivers();
+
+    let ddl = sqlx::Any::migrations_table_ddl(url, &migration_table, index_installed_on)?;
+
+    for (i, statement) in ddl.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let statement = statement.trim();
+        if statement.ends_with(';') {
+            println!("{statement}");
+        } else {
+            println!("{statement};");
+        }
+    }
+
+    Ok(())
+}
+
+/// A string literal for `dialect`.
+fn sql_string(dialect: &str, value: &str) -> String {
+    let value = value.replace('\'', "''");
+
+    // backslashes are escape characters in MySQL's default SQL mode
+    match dialect {
+        "mysql" => format!("'{}'", value.replace('\\', "\\\\")),
+        _ => format!("'{value}'"),
+    }
+}
+
+/// A binary literal for `dialect`.
+fn sql_bytes(dialect: &str, value: &[u8]) -> String {
+    match dialect {
+        "postgres" => format!("decode('{}', 'hex')", short_checksum(value)),
+        _ => format!("X'{}'", short_checksum(value)),
+    }
+}
+
+pub async fn export_history(
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    let res = async {
+        conn.ensure_migrations_table(migration_table.to_owned())
+            .await?;
+        let dirty_version = conn.dirty_version(migration_table.to_owned()).await?;
+        let applied_migrations = conn
+            .list_applied_migrations(migration_table.to_owned())
+            .await?;
+
+        // `AppliedMigration` only carries the version and checksum
+        let installed_on = match conn.backend_name() {
+            "MySQL" => "CAST(installed_on AS CHAR)",
+            _ => "CAST(installed_on AS TEXT)",
+        };
+        let rows: Vec<(i64, String, String, i64)> = sqlx::query_as(&format!(
+            "SELECT version, description, {installed_on}, execution_time \
+             FROM {migration_table} WHERE success ORDER BY version"
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+
+        anyhow::Ok((dialect(&conn), dirty_version, applied_migrations, rows))
+    }
+    .await;
+
+    let _ = conn.close().await;
+    let (dialect, dirty_version, applied_migrations, rows) = res?;
+
+    if let Some(version) = dirty_version {
+        // stderr, so that stdout stays valid SQL
+        eprintln!(
+            "{} the database is dirty on version {}; the failed migration is not exported",
+            style("warning:").bold().yellow(),
+            style(version).cyan()
+        );
+    }
+
+    let checksums: HashMap<_, _> = applied_migrations
+        .into_iter()
+        .map(|m| (m.version, m.checksum))
+        .collect();
+
+    let ddl =
+        sqlx::Any::migrations_table_ddl(connect_opts.required_db_url()?, &migration_table, false)?;
+    let mut script = format!(
+        "-- Generated by `sqlx migrate export-history`: recreates the applied migration history\n\
+         -- in {migration_table} ({dialect}) without running the migrations.\n\n{}\n",
+        ddl.join("\n").trim()
+    );
+
+    for (version, description, installed_on, execution_time) in rows {
+        let Some(checksum) = checksums.get(&version) else {
+            continue;
+        };
+
+        writeln!(
+            script,
+            "\nINSERT INTO {migration_table} \
+             ( version, description, installed_on, success, checksum, execution_time ) \
+             VALUES ( {version}, {}, {}, TRUE, {}, {execution_time} );",
+            sql_string(dialect, &description),
+            sql_string(dialect, &installed_on),
+            sql_bytes(dialect, checksum),
+        )?;
+    }
+
+    print!("{script}");
+
+    Ok(())
+}
+
+/// Upper bounds of the buckets of the `migrate stats` histogram; the last bucket is unbounded.
+const STATS_BUCKETS: [(Duration, &str); 5] = [
+    (Duration::from_millis(1), "< 1ms"),
+    (Duration::from_millis(10), "< 10ms"),
+    (Duration::from_millis(100), "< 100ms"),
+    (Duration::from_secs(1), "< 1s"),
+    (Duration::from_secs(10), "< 10s"),
+];
+
+pub async fn stats(
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+    top: usize,
+    json: bool,
+) -> anyhow::Result<()> {
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    let res = async {
+        conn.ensure_migrations_table(migration_table.to_owned())
+            .await?;
+
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(&format!(
+            "SELECT version, description, execution_time FROM {migration_table} \
+             WHERE success ORDER BY version"
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+
+        anyhow::Ok(rows)
+    }
+    .await;
+
+    let _ = conn.close().await;
+
+    // nanoseconds, as recorded
+    let migrations: Vec<(i64, String, Duration)> = res?
+        .into_iter()
+        .map(|(version, description, execution_time)| {
+            let nanos = u64::try_from(execution_time).unwrap_or(0);
+            (version, description, Duration::from_nanos(nanos))
+        })
+        .collect();
+
+    let total: Duration = migrations.iter().map(|(_, _, elapsed)| *elapsed).sum();
+
+    if json {
+        let output = serde_json::json!({
+            "total_ns": total.as_nanos() as u64,
+            "migrations": migrations
+                .iter()
+                .map(|(version, description, elapsed)| serde_json::json!({
+                    "version": version,
+                    "description": description,
+                    "execution_time_ns": elapsed.as_nanos() as u64,
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    if migrations.is_empty() {
+        println!("No migrations are applied");
+        return Ok(());
+    }
+
+    let average = total / migrations.len() as u32;
+    println!("Applied migrations: {}", migrations.len());
+    println!("Total time:         {}", format_millis(total));
+    println!("Average time:       {}", format_millis(average));
+
+    let mut slowest: Vec<_> = migrations.iter().collect();
+    slowest.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+
+    println!();
+    println!("Slowest migrations:");
+    for (version, description, elapsed) in slowest.into_iter().take(top) {
+        let share = if total.is_zero() {
+            0.0
+        } else {
+            elapsed.as_secs_f64() / total.as_secs_f64() * 100.0
+        };
+
+        println!(
+            "  {}/{} {} {}",
+            style(version).cyan(),
+            description,
+            format_millis(*elapsed),
+            style(format!("({share:.1}%)")).dim()
+        );
+    }
+
+    let mut counts = [0usize; STATS_BUCKETS.len() + 1];
+    for (_, _, elapsed) in &migrations {
+        let bucket = STATS_BUCKETS
+            .iter()
+            .position(|(bound, _)| elapsed < bound)
+            .unwrap_or(STATS_BUCKETS.len());
+        counts[bucket] += 1;
+    }
+
+    // the largest bucket gets a bar of 40 characters
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let labels = STATS_BUCKETS
+        .iter()
+        .map(|(_, label)| *label)
+        .chain([">= 10s"]);
+
+    println!();
+    println!("Histogram:");
+    for (label, count) in labels.zip(counts) {
+        let width = (count * 40).div_ceil(max_count);
+        println!("  {label:>7} | {:<40} {count}", "#".repeat(width));
+    }
+
+    Ok(())
+}
+
+fn format_millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// The columns every migrations table has, whichever driver created it.
+const MIGRATIONS_TABLE_COLUMNS: [&str; 6] = [
+    "version",
+    "description",
+    "installed_on",
+    "success",
+    "checksum",
+    "execution_time",
+];
+
+pub async fn find_tables(
+    connect_opts: &ConnectOpts,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut conn = crate::connect(connect_opts).await?;
+
+    // only the current schema (PostgreSQL) or database (MySQL, SQLite) is searched
+    let candidates: Vec<String> = schema::introspect(&mut conn, &[])
+        .await?
+        .into_iter()
+        .filter(|(_, columns)| {
+            MIGRATIONS_TABLE_COLUMNS
+                .iter()
+                .all(|name| columns.iter().any(|c| c.name.eq_ignore_ascii_case(name)))
+        })
+        .map(|(table, _)| table)
+        .collect();
+
+    let mut with_history = Vec::new();
+    for table in &candidates {
+        let migrations: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(&mut conn)
+            .await?;
+
+        println!(
+            "{} {} migrations{}",
+            style(table).cyan(),
+            migrations,
+            if *table == migration_table {
+                " (migration table)"
+            } else {
+                ""
+            }
+        );
+
+        if migrations > 0 {
+            with_history.push(table.as_str());
+        }
+    }
+
+    let _ = conn.close().await;
+
+    if candidates.is_empty() {
+        println!("No migrations tables found");
+    } else if !candidates.contains(&migration_table) {
+        println!("The migration table {migration_table} does not exist");
+    }
+
+    if with_history.len() > 1 {
+        println!(
+            "{} migrations are recorded in {} tables: {}. They may have been run with different \
+             --migration-table values",
+            style("warning:").bold().yellow(),
+            with_history.len(),
+            with_history.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn migration_kind(migration_type: MigrationType) -> &'static str {
+    match migration_type {
+        MigrationType::Simple => "simple",
+        MigrationType::ReversibleUp => "up",
+        MigrationType::ReversibleDown => "down",
+    }
+}
+
+pub async fn diff_dirs(dir_a: &str, dir_b: &str, json: bool) -> anyhow::Result<()> {
+    let migrator_a = Migrator::new(Path::new(dir_a), None).await?;
+    let migrator_b = Migrator::new(Path::new(dir_b), None).await?;
+
+    // Up and down files of a reversible migration share a version, so key on both.
+    let by_key = |migrator: &Migrator| -> BTreeMap<(i64, bool), Migration> {
+        migrator
+            .iter()
+            .map(|m| ((m.version, m.migration_type.is_down_migration()), m.clone()))
+            .collect()
+    };
+
+    let a = by_key(&migrator_a);
+    let b = by_key(&migrator_b);
+
+    let only_a: Vec<&Migration> = a
+        .iter()
+        .filter(|(key, _)| !b.contains_key(key))
+        .map(|(_, m)| m)
+        .collect();
+    let only_b: Vec<&Migration> = b
+        .iter()
+        .filter(|(key, _)| !a.contains_key(key))
+        .map(|(_, m)| m)
+        .collect();
+    let changed: Vec<(&Migration, &Migration)> = a
+        .iter()
+        .filter_map(|(key, m_a)| b.get(key).map(|m_b| (m_a, m_b)))
+        .filter(|(m_a, m_b)| m_a.checksum != m_b.checksum)
+        .collect();
+
+    if json {
+        let entry = |m: &Migration| {
+            serde_json::json!({
+                "version": m.version,
+                "description": m.description,
+                "kind": migration_kind(m.migration_type),
+            })
+        };
+
+        let output = serde_json::json!({
+            "only_in_a": only_a.iter().map(|m| entry(m)).collect::<Vec<_>>(),
+            "only_in_b": only_b.iter().map(|m| entry(m)).collect::<Vec<_>>(),
+            "checksum_changed": changed
+                .iter()
+                .map(|(m_a, m_b)| {
+                    let mut value = entry(m_a);
+                    value["checksum_a"] = short_checksum(&m_a.checksum).into();
+                    value["checksum_b"] = short_checksum(&m_b.checksum).into();
+                    value
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    for (marker, m) in only_a
+        .iter()
+        .map(|m| ("-", *m))
+        .chain(only_b.iter().map(|m| ("+", *m)))
+        .chain(changed.iter().map(|(m, _)| ("~", *m)))
+    {
+        println!(
+            "{} {}/{} {}",
+            marker,
+            m.version,
+            migration_kind(m.migration_type),
+            m.description
+        );
+    }
+
+    Ok(())
+}
+
+const GENERATED_HEADER: &str = "\
+-- DRAFT generated by `sqlx migrate generate --from-database`: review before applying.
+--
+-- Only tables and columns were compared. Constraints, indexes, defaults and column type
+-- changes are not reconciled, and statements may need reordering, e.g. for foreign keys.
+
+";
+
+/// Write a draft migration with the DDL turning the tables and columns created by the local
+/// migrations into those of the database at `connect_opts`.
+pub async fn generate(
+    migration_source: &str,
+    description: &str,
+    from_database: bool,
+    connect_opts: &ConnectOpts,
+    sequential: bool,
+    timestamp: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    // the schema of a database is the only one a migration can be generated from so far
+    if !from_database {
+        bail!("pass --from-database to generate a migration from the schema of the database at --database-url");
+    }
+
+    ensure_single_source(migration_source, "generate")?;
+
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let exclude = bookkeeping_tables(&migration_table);
+
+    let mut conn = crate::connect(connect_opts).await?;
+    let backend = conn.backend_name().to_owned();
+    let database_schema = schema::introspect(&mut conn, &exclude).await;
+    let _ = conn.close().await;
+    let database_schema = database_schema?;
+
+    let scratch_url = scratch_url(connect_opts.required_db_url()?)?;
+    sqlx::Any::create_database(&scratch_url)
+        .await
+        .context("failed to create the scratch database")?;
+
+    let migrations_schema = migrations_schema(&migrator, &scratch_url, &exclude).await;
+
+    if let Err(e) = sqlx::Any::drop_database(&scratch_url).await {
+        println!(
+            "{} failed to drop the scratch database {}: {e}",
+            style("warning:").bold().yellow(),
+            mask_password(&scratch_url)
+        );
+    }
+
+    let statements = schema::diff(&backend, &migrations_schema?, &database_schema);
+    if statements.is_empty() {
+        println!("The migrations match the database schema; no migration generated");
+        return Ok(());
+    }
+
+    fs::create_dir_all(migration_source).context("Unable to create migrations directory")?;
+
+    let migration_type = MigrationType::infer(&migrator, false);
+    let (sequential, timestamp) = ordering_flags(migration_source, sequential, timestamp)?;
+    let ordering = MigrationOrdering::infer(sequential, timestamp, &migrator.migrations);
+    let file_prefix = ordering.file_prefix();
+
+    let up_type = if migration_type.is_reversible() {
+        MigrationType::ReversibleUp
+    } else {
+        MigrationType::Simple
+    };
+    let path = Path::new(migration_source).join(format!(
+        "{}_{}{}",
+        file_prefix,
+        description.replace(' ', "_"),
+        up_type.suffix()
+    ));
+
+    println!("Creating {}", style(path.display()).cyan());
+    fs::write(
+        &path,
+        format!("{GENERATED_HEADER}{}\n", statements.join("\n\n")),
+    )
+    .context("Failed to create migration file")?;
+
+    if migration_type.is_reversible() {
+        let file_name = render_file_name(
+            DEFAULT_FILENAME_TEMPLATE,
+            file_prefix,
+            description,
+            MigrationType::ReversibleDown,
+        )?;
+        create_file(
+            migration_source,
+            &file_name,
+            MigrationType::ReversibleDown.file_content(),
+        )?;
+    }
+
+    println!(
+        "{} the generated migration is a draft; review it before applying",
+        style("warning:").bold().yellow()
+    );
+
+    Ok(())
+}
+
+/// The tables sqlx keeps next to `migration_table`, which are not part of the schema.
+fn bookkeeping_tables(migration_table: &str) -> Vec<String> {
+    [
+        seed_table(migration_table),
+        snapshot_table(migration_table),
+        runs_table(migration_table),
+        releases_table(migration_table),
+        followups_table(migration_table),
+        migration_table.to_owned(),
+    ]
+    .iter()
+    .map(|table| table.rsplit('.').next().unwrap_or(table).to_owned())
+    .collect()
+}
+
+/// The tables and columns created by running every local migration on the scratch database.
+async fn migrations_schema(
+    migrator: &Migrator,
+    scratch_url: &str,
+    exclude: &[String],
+) -> anyhow::Result<schema::Schema> {
+    let mut conn = AnyConnection::connect(scratch_url).await?;
+
+    let res = async {
+        migrator
+            .run_direct(&mut conn)
+            .await
+            .context("failed to run the migrations on the scratch database")?;
+
+        schema::introspect(&mut conn, exclude).await
+    }
+    .await;
+
+    // a database with open connections cannot be dropped
+    let _ = conn.close().await;
+
+    res
+}
+
+/// A URL for a scratch database on the same server as the database at `url`, or in a temporary
+/// file for SQLite.
+fn scratch_url(url: &str) -> anyhow::Result<String> {
+    let name = format!("sqlx_scratch_{}", std::process::id());
+    let mut url = url::Url::parse(url).context("invalid database URL")?;
+
+    if url.scheme() == "sqlite" {
+        let path = std::env::temp_dir().join(format!("{name}.db"));
+        return Ok(format!("sqlite://{}", path.display()));
+    }
+
+    let database = url.path().trim_start_matches('/').to_owned();
+    if database.is_empty() {
+        bail!("DATABASE_URL does not specify a database name");
+    }
+
+    url.set_path(&format!("{database}_{name}"));
+
+    Ok(url.into())
+}
+
+/// Drop the schema given by `--target-schema`, for `migrate drop-schema`.
+pub async fn drop_schema(connect_opts: &ConnectOpts, confirm: bool) -> anyhow::Result<()> {
+    let Some(schema) = &connect_opts.target_schema else {
+        bail!("--target-schema is required");
+    };
+
+    // `crate::connect()` would create the schema first
+    let mut conn = crate::retry_connect_errors(connect_opts, AnyConnection::connect).await?;
+
+    let res = async {
+        if conn.backend_name() != "PostgreSQL" {
+            bail!("--target-schema is only supported on PostgreSQL");
+        }
+
+        if confirm
+            && !crate::ask_to_continue(&format!("Drop schema {schema} and everything in it?"))
+        {
+            return Ok(false);
+        }
+
+        conn.execute(&*format!(
+            "DROP SCHEMA IF EXISTS {} CASCADE",
+            crate::quote_ident(schema)
+        ))
+        .await?;
+
+        Ok(true)
+    }
+    .await;
+
+    let _ = conn.close().await;
+
+    if res? {
+        println!("Dropped schema {}", style(schema).cyan());
+    }
+
+    Ok(())
+}
+
+/// Apply the local migrations on a scratch database, reverting and re-applying each one with a
+/// down migration, for `migrate test-reversibility`.
+pub async fn test_reversibility(
+    migration_source: &str,
+    connect_opts: &ConnectOpts,
+    schema_equal: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    let exclude = bookkeeping_tables(&migration_table);
+
+    // nothing connects to the database at `--database-url`, which would install them
+    sqlx::any::install_default_drivers();
+
+    let scratch_url = scratch_url(connect_opts.required_db_url()?)?;
+    sqlx::Any::create_database(&scratch_url)
+        .await
+        .context("failed to create the scratch database")?;
+
+    let res = async {
+        let mut conn = AnyConnection::connect(&scratch_url).await?;
+        let res = check_reversibility(
+            &mut conn,
+            &migrator,
+            &migration_table,
+            schema_equal.then_some(&exclude[..]),
+        )
+        .await;
+
+        // a database with open connections cannot be dropped
+        let _ = conn.close().await;
+
+        res
+    }
+    .await;
+
+    if let Err(e) = sqlx::Any::drop_database(&scratch_url).await {
+        println!(
+            "{} failed to drop the scratch database {}: {e}",
+            style("warning:").bold().yellow(),
+            mask_password(&scratch_url)
+        );
+    }
+
+    res
+}
+
+/// With `schema_equal`, the schema is compared before each migration and after reverting it,
+/// ignoring the tables given.
+async fn check_reversibility(
+    conn: &mut AnyConnection,
+    migrator: &Migrator,
+    migration_table: &str,
+    schema_equal: Option<&[String]>,
+) -> anyhow::Result<()> {
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    let mut mismatched = 0;
+
+    for up in migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+    {
+        let Some(down) = migrator
+            .iter()
+            .find(|m| m.version == up.version && m.migration_type.is_down_migration())
+        else {
+            conn.apply(up, migration_table.to_owned())
+                .await
+                .with_context(|| format!("failed to apply migration {}", up.version))?;
+            println!(
+                "Skipped {}/{} {} {}",
+                style(up.version).cyan(),
+                style(up.migration_type.label()).green(),
+                up.description,
+                style("(no down migration)").dim()
+            );
+            continue;
+        };
+
+        let before = match schema_equal {
+            Some(exclude) => Some(schema::introspect(conn, exclude).await?),
+            None => None,
+        };
+
+        conn.apply(up, migration_table.to_owned())
+            .await
+            .with_context(|| format!("failed to apply migration {}", up.version))?;
+        conn.revert(down, migration_table.to_owned())
+            .await
+            .with_context(|| format!("failed to revert migration {}", up.version))?;
 
-                println!(
-                    "{} {}/{} {} {}",
-                    text,
-                    style(migration.version).cyan(),
-                    style(migration.migration_type.label()).green(),
-                    migration.description,
-                    style(format!("({elapsed:?})")).dim()
-                );
+        let differences = match (before, schema_equal) {
+            (Some(before), Some(exclude)) => {
+                schema::differences(&before, &schema::introspect(conn, exclude).await?)
+            }
+            _ => Vec::new(),
+        };
+
+        if differences.is_empty() {
+            println!(
+                "Reverted {}/{} {}",
+                style(up.version).cyan(),
+                style(down.migration_type.label()).green(),
+                up.description
+            );
+        } else {
+            mismatched += 1;
+            println!(
+                "{} {}/{} {}: the schema differs from before the migration",
+                style("Mismatch").bold().red(),
+                style(up.version).cyan(),
+                style(down.migration_type.label()).green(),
+                up.description
+            );
+            for difference in differences {
+                println!("    {difference}");
             }
         }
+
+        // later migrations build on this one
+        conn.apply(up, migration_table.to_owned())
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to apply migration {} again after reverting it; its down migration \
+                     may not fully undo it",
+                    up.version
+                )
+            })?;
     }
 
-    // Close the connection before exiting:
-    // * For MySQL and Postgres this should ensure timely cleanup on the server side,
-    //   including decrementing the open connection count.
-    // * For SQLite this should checkpoint and delete the WAL file to ensure the migrations
-    //   were actually applied to the database file and aren't just sitting in the WAL file.
-    let _ = conn.close().await;
+    if mismatched > 0 {
+        bail!("{mismatched} down migrations do not restore the schema");
+    }
 
     Ok(())
 }
 
-pub async fn revert(
+/// Convert the local migrations, and the migrations table if `connect_opts` is given and has a
+/// database URL, to the `to` versioning scheme.
+pub async fn convert(
     migration_source: &str,
-    connect_opts: &ConnectOpts,
+    to: Versioning,
+    connect_opts: Option<&ConnectOpts>,
     dry_run: bool,
-    ignore_missing: bool,
-    target_version: Option<i64>,
+    confirm: bool,
     migration_table: Option<String>,
 ) -> anyhow::Result<()> {
-    let migrator = Migrator::new(Path::new(migration_source), migration_table).await?;
-    if let Some(target_version) = target_version {
-        if target_version != 0 && !migrator.version_exists(target_version) {
-            bail!(MigrateError::VersionNotPresent(target_version));
+    ensure_single_source(migration_source, "convert")?;
+
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let mut versions: Vec<i64> = migrator.iter().map(|m| m.version).collect();
+    versions.sort_unstable();
+    versions.dedup();
+
+    let converted = convert_versions(&versions, to);
+    let renames = plan_renames(migration_source, &converted)?;
+
+    let mut conn = match connect_opts.filter(|opts| opts.database_url.is_some()) {
+        Some(connect_opts) => Some(crate::connect(connect_opts).await?),
+        None => None,
+    };
+
+    let mut updates = Vec::new();
+    if let Some(conn) = &mut conn {
+        conn.ensure_migrations_table(migration_table.to_owned())
+            .await?;
+
+        for applied in conn
+            .list_applied_migrations(migration_table.to_owned())
+            .await?
+        {
+            let local_version = if converted.contains_key(&applied.version) {
+                applied.version
+            } else {
+                // already renamed locally, e.g. when converting another environment
+                let mut matching = migrator.iter().filter(|m| {
+                    !m.migration_type.is_down_migration() && m.checksum == applied.checksum
+                });
+
+                match (matching.next(), matching.next()) {
+                    (Some(migration), None) => migration.version,
+                    _ => bail!(
+                        "applied migration {} was not found in {migration_source}, nor a \
+                         single local migration with the same checksum, so its version cannot \
+                         be converted",
+                        applied.version
+                    ),
+                }
+            };
+
+            let new_version: i64 = converted[&local_version].parse()?;
+            if new_version != applied.version {
+                updates.push((applied.version, new_version));
+            }
         }
     }
 
-    let mut conn = crate::connect(&connect_opts).await?;
+    if renames.is_empty() && updates.is_empty() {
+        println!(
+            "Migrations already use {} versioning",
+            style(to.name()).cyan()
+        );
+        return Ok(());
+    }
 
-    let migration_table = migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+    let action = if dry_run { "Can rename" } else { "Renaming" };
+    for (from, to) in &renames {
+        println!(
+            "{action} {} to {}",
+            style(from.display()).cyan(),
+            style(to.display()).cyan()
+        );
+    }
+    let action = if dry_run { "Can update" } else { "Updating" };
+    for (from, to) in &updates {
+        println!(
+            "{action} applied version {} to {} in {migration_table}",
+            style(from).cyan(),
+            style(to).cyan()
+        );
+    }
 
-    conn.ensure_migrations_table(migration_table).await?;
+    if dry_run {
+        return Ok(());
+    }
 
-    let version = conn.dirty_version(migration_table.to_owned()).await?;
-    if let Some(version) = version {
-        bail!(MigrateError::Dirty(version));
+    if confirm
+        && !crate::ask_to_continue(
+            "This rewrites migration history, which must then be converted in every other \
+             environment too. Continue?",
+        )
+    {
+        return Ok(());
     }
 
-    let applied_migrations = conn.list_applied_migrations(migration_table.to_owned()).await?;
-    validate_applied_migrations(&applied_migrations, &migrator, ignore_missing)?;
+    match &mut conn {
+        Some(conn) => {
+            let mut tx = conn.begin().await?;
 
-    let latest_version = applied_migrations
-        .iter()
-        .max_by(|x, y| x.version.cmp(&y.version))
-        .and_then(|migration| Some(migration.version))
-        .unwrap_or(0);
-    if let Some(target_version) = target_version {
-        if target_version > latest_version {
-            bail!(MigrateError::VersionTooNew(target_version, latest_version));
+            // through negative versions, so that no two rows ever share a version
+            for (from, to) in &updates {
+                (&mut *tx)
+                    .execute(&*format!(
+                        "UPDATE {migration_table} SET version = {} WHERE version = {from}",
+                        -to
+                    ))
+                    .await?;
+            }
+            (&mut *tx)
+                .execute(&*format!(
+                    "UPDATE {migration_table} SET version = -version WHERE version < 0"
+                ))
+                .await?;
+
+            // dropping the transaction on error rolls the updates back
+            rename_all(&renames)?;
+
+            if let Err(e) = tx.commit().await {
+                let reverse: Vec<_> = renames
+                    .iter()
+                    .map(|(from, to)| (to.clone(), from.clone()))
+                    .collect();
+                rename_all(&reverse).context("failed to restore the renamed migrations")?;
+
+                return Err(e).context("failed to update the migrations table");
+            }
         }
+        None => rename_all(&renames)?,
     }
 
-    let applied_migrations: HashMap<_, _> = applied_migrations
-        .into_iter()
-        .map(|m| (m.version, m))
-        .collect();
+    if let Some(conn) = conn {
+        let _ = conn.close().await;
+    }
 
-    let mut is_applied = false;
-    for migration in migrator.iter().rev() {
-        if !migration.migration_type.is_down_migration() {
-            // Skipping non down migration
-            // This will skip any simple or up migration file
-            continue;
-        }
+    if read_ordering_marker(migration_source)?.is_some() {
+        write_ordering_marker(migration_source, to)?;
+    }
 
-        if applied_migrations.contains_key(&migration.version) {
-            let skip = match target_version {
-                Some(target_version) if migration.version <= target_version => true,
-                _ => false,
-            };
-            let elapsed = if dry_run || skip {
-                Duration::new(0, 0)
-            } else {
-                conn.revert(migration, migration_table.to_owned()).await?
-            };
-            let text = if skip {
-                "Skipped"
-            } else if dry_run {
-                "Can apply"
-            } else {
-                "Applied"
-            };
+    println!(
+        "Converted {} migrations to {} versioning",
+        versions.len(),
+        style(to.name()).cyan()
+    );
 
-            println!(
-                "{} {}/{} {} {}",
-                text,
-                style(migration.version).cyan(),
-                style(migration.migration_type.label()).green(),
-                migration.description,
-                style(format!("({elapsed:?})")).dim()
-            );
+    Ok(())
+}
 
-            is_applied = true;
+/// The new version prefix of every version in `versions`, which must be sorted.
+///
+/// Timestamps are assigned one second apart, ending a second ago, so that migrations added
+/// later with `add --timestamp` still sort last. Versions which are all timestamps already are kept.
+fn convert_versions(versions: &[i64], to: Versioning) -> HashMap<i64, String> {
+    match to {
+        Versioning::Sequential => versions
+            .iter()
+            .zip(1..)
+            .map(|(&version, new_version)| {
+                (
+                    version,
+                    MigrationOrdering::sequential(new_version)
+                        .file_prefix()
+                        .to_string(),
+                )
+            })
+            .collect(),
+        Versioning::Timestamp
+            if versions.iter().all(|version| {
+                NaiveDateTime::parse_from_str(&version.to_string(), "%Y%m%d%H%M%S").is_ok()
+            }) =>
+        {
+            versions
+                .iter()
+                .map(|&version| (version, version.to_string()))
+                .collect()
+        }
+        Versioning::Timestamp => {
+            let start = now() - chrono::Duration::seconds(versions.len() as i64);
 
-            // Only a single migration will be reverted at a time if no target
-            // version is supplied, so we break.
-            if let None = target_version {
-                break;
-            }
+            versions
+                .iter()
+                .zip(0..)
+                .map(|(&version, offset)| {
+                    let timestamp = start + chrono::Duration::seconds(offset);
+                    (version, timestamp.format("%Y%m%d%H%M%S").to_string())
+                })
+                .collect()
         }
     }
-    if !is_applied {
-        println!("No migrations available to revert");
+}
+
+/// The files in `migration_source` to rename for the new version prefixes in `converted`.
+fn plan_renames(
+    migration_source: &str,
+    converted: &HashMap<i64, String>,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut renames = Vec::new();
+
+    for entry in fs::read_dir(migration_source)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            continue;
+        };
+        let Some((prefix, rest)) = name.split_once('_') else {
+            continue;
+        };
+        let Some(new_prefix) = prefix
+            .parse::<i64>()
+            .ok()
+            .and_then(|version| converted.get(&version))
+        else {
+            continue;
+        };
+
+        if prefix != new_prefix {
+            renames.push((
+                entry.path(),
+                Path::new(migration_source).join(format!("{new_prefix}_{rest}")),
+            ));
+        }
     }
 
-    let _ = conn.close().await;
+    renames.sort();
+
+    Ok(renames)
+}
+
+/// Rename every file of `renames`, or none: on failure, the files already renamed are
+/// renamed back.
+fn rename_all(renames: &[(PathBuf, PathBuf)]) -> anyhow::Result<()> {
+    // through temporary names, in case a new name is the old name of another file
+    let staged: Vec<_> = renames
+        .iter()
+        .map(|(from, to)| {
+            let mut temporary = from.clone().into_os_string();
+            temporary.push(".converting");
+            (from, PathBuf::from(temporary), to)
+        })
+        .collect();
+
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut rename = |from: &Path, to: &Path| -> io::Result<()> {
+        fs::rename(from, to)?;
+        done.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    };
+
+    let res = staged
+        .iter()
+        .try_for_each(|(from, temporary, _)| rename(from, temporary))
+        .and_then(|()| {
+            staged.iter().try_for_each(|(_, temporary, to)| {
+                if to.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", to.display()),
+                    ));
+                }
+                rename(temporary, to)
+            })
+        });
+
+    if let Err(e) = res {
+        for (from, to) in done.iter().rev() {
+            let _ = fs::rename(to, from);
+        }
+
+        return Err(e).context("failed to rename migrations; the renamed files were restored");
+    }
 
     Ok(())
 }
 
 pub fn build_script(migration_source: &str, force: bool) -> anyhow::Result<()> {
+    ensure_single_source(migration_source, "build-script")?;
+
     anyhow::ensure!(
         Path::new("Cargo.toml").exists(),
         "must be run in a Cargo project root"
@@ -495,3 +5650,361 @@ fn main() {{
 
     Ok(())
 }
+
+/// Directives whose effect would change, or be lost, once their migration is concatenated
+/// with others.
+const SQUASH_UNSUPPORTED_DIRECTIVES: [&str; 4] =
+    ["no-transaction", "batch-commit", "lock-timeout", "baseline"];
+
+/// Where `squash` moves the migrations squashed into the migration with `version`.
+fn archive_dir(migration_source: &str, version: i64) -> PathBuf {
+    Path::new(migration_source)
+        .join("archive")
+        .join(version.to_string())
+}
+
+/// The SQL of the migration squashing `migrations`, which are sorted by version, and that of
+/// its down migration if every one of them has a down migration in `migrator`.
+fn squash_sql(migrator: &Migrator, migrations: &[&Migration]) -> (String, Option<String>) {
+    let first = migrations[0].version;
+    let last = migrations[migrations.len() - 1].version;
+
+    let mut up = format!(
+        "-- Squashed migrations {first} to {last}; the originals are in archive/{last}.\n"
+    );
+    for migration in migrations {
+        up.push_str(&format!(
+            "\n-- {} {}\n{}\n",
+            migration.version,
+            migration.description,
+            migration.sql.trim()
+        ));
+    }
+
+    let mut down = format!("-- Reverts squashed migrations {first} to {last}.\n");
+    for migration in migrations.iter().rev() {
+        let Some(down_migration) = migrator
+            .iter()
+            .find(|m| m.version == migration.version && m.migration_type.is_down_migration())
+        else {
+            return (up, None);
+        };
+
+        down.push_str(&format!(
+            "\n-- {} {}\n{}\n",
+            migration.version,
+            migration.description,
+            down_migration.sql.trim()
+        ));
+    }
+
+    (up, Some(down))
+}
+
+/// Squash the local up migrations with a version up to `before` into one migration, archiving
+/// the originals, and record it in place of them in the migrations table if they were applied.
+pub async fn squash(
+    migration_source: &str,
+    before: i64,
+    description: &str,
+    connect_opts: &ConnectOpts,
+    dry_run: bool,
+    confirm: bool,
+    migration_table: Option<String>,
+) -> anyhow::Result<()> {
+    ensure_single_source(migration_source, "squash")?;
+
+    let migrator = Migrator::new(source_dirs(migration_source), migration_table.clone()).await?;
+    let migration_table =
+        migration_table.unwrap_or_else(|| sqlx::migrate::DEFAULT_MIGRATION_TABLE.to_string());
+
+    let local: Vec<&Migration> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && m.version <= before)
+        .collect();
+
+    // squashed already, when squashing the history of another database after the files
+    let archived = match local.as_slice() {
+        [squashed] if archive_dir(migration_source, squashed.version).is_dir() => Some(
+            Migrator::new(archive_dir(migration_source, squashed.version), None).await?,
+        ),
+        _ => None,
+    };
+    let files_squashed = archived.is_some();
+    let source = archived.as_ref().unwrap_or(&migrator);
+
+    let originals: Vec<&Migration> = source
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && m.version <= before)
+        .collect();
+
+    if originals.len() < 2 {
+        bail!(
+            "nothing to squash: {} migrations have a version up to {before}",
+            originals.len()
+        );
+    }
+
+    for migration in &originals {
+        if !migration.dialects().is_empty() {
+            bail!(
+                "cannot squash migration {}: it has SQL for specific databases",
+                migration.version
+            );
+        }
+        if let Some(directive) = SQUASH_UNSUPPORTED_DIRECTIVES.iter().find(|directive| {
+            DIALECTS
+                .iter()
+                .any(|dialect| migration.directive(dialect, directive).is_some())
+        }) {
+            bail!(
+                "cannot squash migration {}: it has a `-- sqlx:{directive}` directive",
+                migration.version
+            );
+        }
+    }
+
+    let version = originals[originals.len() - 1].version;
+    let archive = archive_dir(migration_source, version);
+    let (up_sql, down_sql) = squash_sql(source, &originals);
+    let checksum = Migration::new(
+        version,
+        Cow::Owned(description.to_owned()),
+        MigrationType::Simple,
+        Cow::Owned(up_sql.clone()),
+    )
+    .checksum
+    .into_owned();
+
+    if files_squashed && *local[0].checksum != *checksum {
+        bail!(
+            "migration {version} is not the squash of the migrations in {}",
+            archive.display()
+        );
+    }
+
+    let mut conn = crate::connect(connect_opts).await?;
+    conn.ensure_migrations_table(migration_table.to_owned())
+        .await?;
+
+    if let Some(record) = conn.dirty_record(migration_table.to_owned()).await? {
+        if record.version <= version {
+            bail!(MigrateError::DirtyRecord(record));
+        }
+    }
+
+    let applied: HashMap<_, _> = conn
+        .list_applied_migrations(migration_table.to_owned())
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    let squashed_versions = &originals[..originals.len() - 1];
+    let recorded = applied
+        .get(&version)
+        .is_some_and(|m| *m.checksum == *checksum)
+        && squashed_versions
+            .iter()
+            .all(|m| !applied.contains_key(&m.version));
+    let pending: Vec<_> = originals
+        .iter()
+        .filter(|m| !applied.contains_key(&m.version))
+        .map(|m| m.version.to_string())
+        .collect();
+
+    let rewrite = if recorded || pending.len() == originals.len() {
+        false
+    } else if pending.is_empty() {
+        for migration in &originals {
+            if applied[&migration.version].checksum != migration.checksum {
+                bail!(MigrateError::VersionMismatch(migration.version));
+            }
+        }
+        true
+    } else {
+        bail!(
+            "cannot squash: migrations {} are not applied to this database while the others \
+             are; apply them first",
+            pending.join(", ")
+        );
+    };
+
+    if files_squashed && !rewrite {
+        println!(
+            "Migrations up to {} are already squashed",
+            style(version).cyan()
+        );
+        let _ = conn.close().await;
+        return Ok(());
+    }
+
+    let mut moves = Vec::new();
+    let mut prefix = None;
+    if !files_squashed {
+        for entry in fs::read_dir(migration_source)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let file_name = entry.file_name();
+            let Some((file_prefix, _)) = file_name.to_str().and_then(|name| name.split_once('_'))
+            else {
+                continue;
+            };
+            let Ok(file_version) = file_prefix.parse::<i64>() else {
+                continue;
+            };
+
+            if file_version == version {
+                prefix = Some(file_prefix.to_owned());
+            }
+            if originals.iter().any(|m| m.version == file_version) {
+                moves.push((entry.path(), archive.join(&file_name)));
+            }
+        }
+        moves.sort();
+    }
+
+    let mut files = Vec::new();
+    if !files_squashed {
+        let prefix = prefix
+            .as_deref()
+            .with_context(|| format!("no file found for migration {version}"))?;
+        match &down_sql {
+            Some(down_sql) => {
+                files.push((
+                    render_file_name(
+                        DEFAULT_FILENAME_TEMPLATE,
+                        prefix,
+                        description,
+                        MigrationType::ReversibleUp,
+                    )?,
+                    up_sql.as_str(),
+                ));
+                files.push((
+                    render_file_name(
+                        DEFAULT_FILENAME_TEMPLATE,
+                        prefix,
+                        description,
+                        MigrationType::ReversibleDown,
+                    )?,
+                    down_sql.as_str(),
+                ));
+            }
+            None => files.push((
+                render_file_name(
+                    DEFAULT_FILENAME_TEMPLATE,
+                    prefix,
+                    description,
+                    MigrationType::Simple,
+                )?,
+                up_sql.as_str(),
+            )),
+        }
+    }
+
+    if dry_run {
+        for (from, to) in &moves {
+            println!(
+                "Can archive {} to {}",
+                style(from.display()).cyan(),
+                style(to.display()).cyan()
+            );
+        }
+        for (file_name, _) in &files {
+            println!(
+                "Can create {}",
+                style(Path::new(migration_source).join(file_name).display()).cyan()
+            );
+        }
+    }
+    if rewrite {
+        println!(
+            "{} squashed migration {} in {migration_table} in place of {} migrations",
+            if dry_run { "Can record" } else { "Recording" },
+            style(version).cyan(),
+            originals.len()
+        );
+    }
+
+    if dry_run {
+        let _ = conn.close().await;
+        return Ok(());
+    }
+
+    if confirm
+        && !crate::ask_to_continue(
+            "This rewrites migration history, which must then be squashed in every other \
+             environment that applied these migrations too. Continue?",
+        )
+    {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+
+    if rewrite {
+        let versions: Vec<_> = squashed_versions
+            .iter()
+            .map(|m| m.version.to_string())
+            .collect();
+        (&mut *tx)
+            .execute(&*format!(
+                "DELETE FROM {migration_table} WHERE version IN ({})",
+                versions.join(", ")
+            ))
+            .await?;
+        tx.update_checksum(version, checksum, migration_table.to_owned())
+            .await?;
+    }
+
+    // dropping the transaction on error rolls the updates back
+    let mut created = Vec::new();
+    let restore = |created: &[PathBuf]| -> anyhow::Result<()> {
+        for path in created {
+            let _ = fs::remove_file(path);
+        }
+        let reverse: Vec<_> = moves
+            .iter()
+            .map(|(from, to)| (to.clone(), from.clone()))
+            .collect();
+        rename_all(&reverse).context("failed to restore the archived migrations")
+    };
+
+    if !moves.is_empty() {
+        fs::create_dir_all(&archive)?;
+        rename_all(&moves)?;
+        for (file_name, contents) in &files {
+            if let Err(e) = create_file(migration_source, file_name, contents) {
+                restore(&created)?;
+                return Err(e);
+            }
+            created.push(Path::new(migration_source).join(file_name));
+        }
+        for (from, to) in &moves {
+            println!(
+                "Archived {} to {}",
+                style(from.display()).cyan(),
+                style(to.display()).cyan()
+            );
+        }
+    }
+
+    if let Err(e) = tx.commit().await {
+        restore(&created)?;
+        return Err(e).context("failed to update the migrations table");
+    }
+
+    let _ = conn.close().await;
+
+    println!(
+        "Squashed {} migrations into {}/{}",
+        originals.len(),
+        style(version).cyan(),
+        description
+    );
+
+    Ok(())
+}
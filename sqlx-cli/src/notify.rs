@@ -0,0 +1,67 @@
+use console::style;
+use serde_json::Value;
+
+use crate::opt::{NotifyEvent, NotifyOpts};
+
+/// Reports migration events to the URL given by `--notify-url`.
+///
+/// Notifications are best-effort: failing to deliver one prints a warning but never fails
+/// the migration run.
+pub struct Notifier<'a> {
+    url: Option<&'a str>,
+    events: &'a [NotifyEvent],
+}
+
+impl<'a> Notifier<'a> {
+    pub fn new(opts: &'a NotifyOpts) -> anyhow::Result<Self> {
+        if opts.notify_url.is_some() && !cfg!(feature = "notify") {
+            anyhow::bail!("`--notify-url` requires sqlx-cli to be built with the `notify` feature");
+        }
+
+        Ok(Self {
+            url: opts.notify_url.as_deref(),
+            events: &opts.notify_on,
+        })
+    }
+
+    /// POST `payload`, with an added `event` field, if notifications are enabled for `event`.
+    pub async fn send(&self, event: NotifyEvent, mut payload: Value) {
+        let Some(url) = self.url else {
+            return;
+        };
+
+        if !self.events.contains(&event) {
+            return;
+        }
+
+        payload["event"] = event.name().into();
+
+        if let Err(e) = post(url.to_owned(), payload).await {
+            println!(
+                "{} failed to send {} notification to {}: {}",
+                style("warning:").bold().yellow(),
+                event.name(),
+                url,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(feature = "notify")]
+async fn post(url: String, payload: Value) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        ureq::post(&url)
+            .timeout(std::time::Duration::from_secs(5))
+            .send_json(payload)
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "notify"))]
+async fn post(_url: String, _payload: Value) -> anyhow::Result<()> {
+    // unreachable: `Notifier::new()` rejects `--notify-url` without the feature
+    Ok(())
+}
@@ -1,14 +1,55 @@
-use std::ops::{Deref, Not};
+use std::fmt;
+use std::ops::{Deref, Not, RangeInclusive};
+use std::str::FromStr;
 
-use clap::{Args, Parser};
+use clap::{Args, Parser, ValueEnum};
 #[cfg(feature = "completions")]
 use clap_complete::Shell;
 
+use crate::config::Config;
+
 #[derive(Parser, Debug)]
 #[clap(version, about, author)]
 pub struct Opt {
     #[clap(subcommand)]
     pub command: Command,
+
+    /// When to color the output.
+    #[clap(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+}
+
+/// When to color the output.
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color output to a terminal, unless the `NO_COLOR` environment variable is set.
+    #[default]
+    Auto,
+    /// Always color the output, even when it is not a terminal.
+    Always,
+    /// Never color the output.
+    Never,
+}
+
+impl ColorMode {
+    /// Enable or disable colors for everything styled with `console::style`.
+    pub fn apply(self) {
+        let enabled = match self {
+            // see https://no-color.org: any non-empty value disables colors
+            ColorMode::Auto
+                if std::env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty()) =>
+            {
+                false
+            }
+            // leave it to `console` to detect a terminal
+            ColorMode::Auto => return,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        };
+
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -54,6 +95,24 @@ pub enum Command {
     Completions { shell: Shell },
 }
 
+impl Command {
+    /// The database connection arguments, for commands which connect.
+    pub fn connect_opts_mut(&mut self) -> Option<&mut ConnectOpts> {
+        match self {
+            Command::Database(database) => match &mut database.command {
+                DatabaseCommand::Create { connect_opts }
+                | DatabaseCommand::Drop { connect_opts, .. }
+                | DatabaseCommand::Reset { connect_opts, .. }
+                | DatabaseCommand::Setup { connect_opts, .. } => Some(connect_opts),
+            },
+            Command::Prepare { connect_opts, .. } => Some(connect_opts),
+            Command::Migrate(migrate) => migrate.command.connect_opts_mut(),
+            #[cfg(feature = "completions")]
+            Command::Completions { .. } => None,
+        }
+    }
+}
+
 /// Group of commands for creating and dropping your database.
 #[derive(Parser, Debug)]
 pub struct DatabaseOpt {
@@ -117,12 +176,36 @@ pub enum DatabaseCommand {
 /// Group of commands for creating and running migrations.
 #[derive(Parser, Debug)]
 pub struct MigrateOpt {
+    /// Print the resolved settings and where each came from, then exit without doing anything.
+    #[clap(long, global = true)]
+    pub print_config: bool,
+
     #[clap(subcommand)]
     pub command: MigrateCommand,
 }
 
 #[derive(Parser, Debug)]
 pub enum MigrateCommand {
+    /// Set up a project for migrations.
+    ///
+    /// Creates the migrations directory and, with `--config`, a starter `.sqlx-migrate.toml`
+    /// containing the default settings.
+    ///
+    /// Refuses to run if the migrations directory already exists and is not empty,
+    /// unless `--force` is passed.
+    Init {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Also write a starter `.sqlx-migrate.toml` to the current directory.
+        #[clap(long)]
+        config: bool,
+
+        /// Proceed even if the migrations directory is not empty or the config file exists.
+        #[clap(long)]
+        force: bool,
+    },
+
     /// Create a new migration with the given description.
     ///
     /// A version number will be automatically assigned to the migration.
@@ -145,11 +228,21 @@ pub enum MigrateCommand {
         #[clap(flatten)]
         source: Source,
 
+        /// The directory to create the migration in, required when the source lists several
+        /// migrations directories. Its version still follows the migrations of all of them.
+        #[clap(long)]
+        dir: Option<String>,
+
         /// If true, creates a pair of up and down migration files with same version
         /// else creates a single sql file
         #[clap(short)]
         reversible: bool,
 
+        /// Create a single sql file even if `default_reversible` is set in the config file
+        /// or `SQLX_DEFAULT_REVERSIBLE` is set.
+        #[clap(long, conflicts_with = "reversible")]
+        no_reversible: bool,
+
         /// If set, use timestamp versioning for the new migration. Conflicts with `--sequential`.
         #[clap(short, long)]
         timestamp: bool,
@@ -158,6 +251,101 @@ pub enum MigrateCommand {
         #[clap(short, long, conflicts_with = "timestamp")]
         sequential: bool,
 
+        /// Also create a seed file with the same version in the seeds directory.
+        #[clap(long)]
+        with_seed: bool,
+
+        /// Name new migration files after this template instead of
+        /// `{version}_{description}{type}.sql`.
+        ///
+        /// The placeholders are `{version}`, `{description}` with spaces replaced by
+        /// underscores, `{slug}`, the description in lowercase with other characters than
+        /// letters and digits replaced by underscores, `{date}`, today's date as `YYYYMMDD`,
+        /// `{type}`, `.up` or `.down` for reversible migrations and empty otherwise, and
+        /// `{suffix}`, the same followed by `.sql`. The template must start with `{version}_`:
+        /// migrations are ordered by the version read from the start of their file name.
+        ///
+        /// Defaults to the `filename_template` key of `.sqlx-migrate.toml`.
+        #[clap(long, conflicts_with = "from_template_dir")]
+        filename_template: Option<String>,
+
+        /// Create the migration by copying every file in this directory into the migrations
+        /// directory, replacing `{version}` and `{description}` in file names and contents.
+        ///
+        /// The directory must contain an up migration named like
+        /// `{version}_{description}.up.sql` or `{version}_{description}.sql`.
+        #[clap(
+            long,
+            conflicts_with_all = [
+                "reversible",
+                "no_reversible",
+                "with_seed",
+                "with_build_script",
+                "edit",
+            ]
+        )]
+        from_template_dir: Option<String>,
+
+        /// When adding the first migration to a Cargo project without a `build.rs`, create
+        /// the build script `sqlx migrate build-script` would, without asking.
+        ///
+        /// Otherwise this is only offered when running interactively.
+        #[clap(long)]
+        with_build_script: bool,
+
+        /// Open the created migration files in `$EDITOR`, the up migration first.
+        ///
+        /// If `$EDITOR` is not set, the files are created and a warning is printed.
+        #[clap(long)]
+        edit: bool,
+
+        #[clap(flatten)]
+        seeds: SeedSource,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Print the version prefix `add` would use for a new migration, without creating it.
+    ///
+    /// Uses the same inference as `add`, including `--sequential` and `--timestamp`.
+    NextVersion {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Use timestamp versioning. Conflicts with `--sequential`.
+        #[clap(short, long)]
+        timestamp: bool,
+
+        /// Use sequential versioning. Conflicts with `--timestamp`.
+        #[clap(short, long, conflicts_with = "timestamp")]
+        sequential: bool,
+
+        /// Also print the versioning mode, `sequential` or `timestamp`.
+        #[clap(short, long)]
+        verbose: bool,
+    },
+
+    /// Print `<VERSION>\t<DESCRIPTION>` for each local migration, for shell completion.
+    ///
+    /// The output format is stable. Only connects to the database with `--applied` or
+    /// `--pending`.
+    #[clap(name = "__list-versions", hide = true)]
+    ListVersions {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Only list migrations which have been applied.
+        #[clap(long)]
+        applied: bool,
+
+        /// Only list migrations which have not been applied.
+        #[clap(long, conflicts_with = "applied")]
+        pending: bool,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
         #[clap(long)]
         migration_table: Option<String>,
     },
@@ -167,138 +355,1479 @@ pub enum MigrateCommand {
         #[clap(flatten)]
         source: Source,
 
-        /// List all the migrations to be run without applying
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+
+        #[clap(flatten)]
+        run_opts: RunOpts,
+    },
+
+    /// Revert the latest migration with a down file.
+    Revert {
+        #[clap(flatten)]
+        source: Source,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+
+        #[clap(flatten)]
+        revert_opts: RevertOpts,
+    },
+
+    /// List all available migrations.
+    #[clap(visible_alias = "status")]
+    Info {
+        #[clap(flatten)]
+        source: Source,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[arg(long)]
+        migration_table: Option<String>,
+
+        #[clap(flatten)]
+        info_opts: InfoOpts,
+    },
+
+    /// Run seed files against the database.
+    ///
+    /// Seeds are data, not schema: they live in their own directory, are named like
+    /// migrations (`<VERSION>_<DESCRIPTION>.sql`) and are tracked in a separate table,
+    /// named after the migrations table with a `_seeds` suffix, so each runs only once.
+    /// They are typically environment-specific and excluded from production.
+    Seed {
+        #[clap(flatten)]
+        seeds: SeedSource,
+
+        /// Run the seed with this version.
+        #[clap(long, required_unless_present = "all", conflicts_with = "all")]
+        version: Option<i64>,
+
+        /// Run every seed that has not been run yet.
+        #[clap(long)]
+        all: bool,
+
+        /// List the seeds to be run without running them
         #[clap(long)]
         dry_run: bool,
 
         #[clap(flatten)]
-        ignore_missing: IgnoreMissing,
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Record the currently applied migrations under a name, to roll back to later.
+    ///
+    /// Snapshots are stored in the database, in a table named after the migrations table
+    /// with a `_snapshots` suffix.
+    Snapshot {
+        /// Name of the snapshot. May contain letters, digits, `_`, `-` and `.`.
+        name: String,
 
         #[clap(flatten)]
         connect_opts: ConnectOpts,
 
-        /// Apply migrations up to the specified version. If unspecified, apply all
-        /// pending migrations. If already at the target version, then no-op.
         #[clap(long)]
-        target_version: Option<i64>,
+        migration_table: Option<String>,
+
+        /// Replace an existing snapshot with the same name.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Revert every migration applied since the named snapshot was taken.
+    ///
+    /// Every such migration must have a down file. Migrations recorded in the snapshot
+    /// that have since been reverted are not re-applied; use `sqlx migrate run` for that.
+    RollbackToSnapshot {
+        /// Name of the snapshot to roll back to.
+        name: String,
+
+        #[clap(flatten)]
+        source: Source,
+
+        /// List the migrations to be reverted without applying
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
 
         #[clap(long)]
         migration_table: Option<String>,
     },
 
-    /// Revert the latest migration with a down file.
-    Revert {
+    /// Revert an applied migration and apply it again, with the current local down and up
+    /// migrations, e.g. after editing a migration which was already applied.
+    ///
+    /// The recorded checksum is updated to match the local migration. On PostgreSQL, the
+    /// down and up migrations run in a single transaction. Any data destroyed by the down
+    /// migration, e.g. by dropping a table, is not restored by the up migration.
+    Rerun {
         #[clap(flatten)]
         source: Source,
 
-        /// List the migration to be reverted without applying
+        /// The version of the applied migration to run again.
+        #[clap(long)]
+        version: i64,
+
+        /// List the migrations to be run without applying
         #[clap(long)]
         dry_run: bool,
 
         #[clap(flatten)]
-        ignore_missing: IgnoreMissing,
+        confirmation: Confirmation,
 
         #[clap(flatten)]
         connect_opts: ConnectOpts,
 
-        /// Revert migrations down to the specified version. If unspecified, revert
-        /// only the last migration. Set to 0 to revert all migrations. If already
-        /// at the target version, then no-op.
         #[clap(long)]
-        target_version: Option<i64>,
+        migration_table: Option<String>,
+    },
+
+    /// Apply a single SQL file, which need not be in the migrations directory.
+    ///
+    /// The file is run in a transaction and recorded in the migrations table under the
+    /// version and description derived from its name, `<VERSION>_<DESCRIPTION>.sql`.
+    /// Pass `--no-record` to run it without touching the migrations table.
+    ApplyFile {
+        /// Path to the SQL file to apply.
+        path: String,
+
+        /// Show the file that would be applied without applying it
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Don't record the file in the migrations table.
+        #[clap(long)]
+        no_record: bool,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
 
+        #[clap(long)]
         migration_table: Option<String>,
     },
 
-    /// List all available migrations.
-    Info {
+    /// Cross-reference applied migrations against the migrations directory and report
+    /// every discrepancy found.
+    ///
+    /// Reports applied migrations missing locally (errors), applied migrations whose
+    /// checksum differs from the local file (errors), and pending migrations older than
+    /// the latest applied one (warnings). Exits with an error if any errors are found.
+    /// Read-only.
+    Audit {
         #[clap(flatten)]
         source: Source,
 
         #[clap(flatten)]
         connect_opts: ConnectOpts,
 
-        #[arg(long)]
+        #[clap(long)]
         migration_table: Option<String>,
+
+        /// Print the report as JSON instead.
+        #[clap(long)]
+        json: bool,
     },
 
-    /// Generate a `build.rs` to trigger recompilation when a new migration is added.
+    /// Check every applied migration against its local file, exiting with an error which
+    /// lists each version whose recorded checksum differs or which no longer exists locally.
     ///
-    /// Must be run in a Cargo project root.
-    BuildScript {
+    /// Checksums approved in `checksum-allowlist.toml` pass. Read-only.
+    Verify {
         #[clap(flatten)]
         source: Source,
 
-        /// Overwrite the build script if it already exists.
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
         #[clap(long)]
-        force: bool,
+        migration_table: Option<String>,
+
+        #[clap(flatten)]
+        ignore_missing: IgnoreMissing,
     },
-}
 
-/// Argument for the migration scripts source.
-#[derive(Args, Debug)]
-pub struct Source {
-    /// Path to folder containing migrations.
-    #[clap(long, default_value = "migrations")]
-    source: String,
-}
+    /// Update the checksums recorded for applied migrations after switching
+    /// `--checksum-ignore-comments` on or off.
+    ///
+    /// Checksums are only updated for migrations whose recorded checksum matches the local
+    /// file in the other mode; migrations modified after being applied are reported instead.
+    RepairChecksums {
+        #[clap(flatten)]
+        source: Source,
 
-impl Deref for Source {
-    type Target = String;
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
 
-    fn deref(&self) -> &Self::Target {
-        &self.source
-    }
-}
+        #[clap(long)]
+        migration_table: Option<String>,
 
-/// Argument for the database URL.
-#[derive(Args, Debug)]
-pub struct ConnectOpts {
-    /// Location of the DB, by default will be read from the DATABASE_URL env var or `.env` files.
-    #[clap(long, short = 'D', env)]
-    pub database_url: Option<String>,
+        /// Record checksums computed with SQL comments stripped. Without this flag, the
+        /// recorded checksums are switched back to covering the full file.
+        #[clap(long)]
+        checksum_ignore_comments: bool,
 
-    /// The maximum time, in seconds, to try connecting to the database server before
-    /// returning an error.
-    #[clap(long, default_value = "10")]
-    pub connect_timeout: u64,
+        /// List the checksums that would be updated without updating them
+        #[clap(long)]
+        dry_run: bool,
+    },
 
-    /// Set whether or not to create SQLite databases in Write-Ahead Log (WAL) mode:
-    /// https://www.sqlite.org/wal.html
-    ///
-    /// WAL mode is enabled by default for SQLite databases created by `sqlx-cli`.
+    /// Record the checksums of the local migration files for applied migrations whose
+    /// recorded checksum differs, e.g. after a whitespace-only edit.
     ///
-    /// However, if your application sets a `journal_mode` on `SqliteConnectOptions` to something
-    /// other than `Wal`, then it will have to take the database file out of WAL mode on connecting,
-    /// which requires an exclusive lock and may return a `database is locked` (`SQLITE_BUSY`) error.
-    #[cfg(feature = "sqlite")]
-    #[clap(long, action = clap::ArgAction::Set, default_value = "true")]
-    pub sqlite_create_db_wal: bool,
-}
+    /// Only migrations with a local file are touched. Asks for confirmation first: a migration
+    /// whose changes do affect the schema will never be applied again.
+    FixChecksums {
+        #[clap(flatten)]
+        source: Source,
 
-impl ConnectOpts {
-    /// Require a database URL to be provided, otherwise
-    /// return an error.
-    pub fn required_db_url(&self) -> anyhow::Result<&str> {
-        self.database_url.as_deref().ok_or_else(
-            || anyhow::anyhow!(
-                "the `--database-url` option the or `DATABASE_URL` environment variable must be provided"
-            )
-        )
-    }
-}
+        /// Record checksums computed with SQL comments stripped, as used by
+        /// `run --checksum-ignore-comments`.
+        #[clap(long)]
+        checksum_ignore_comments: bool,
 
-/// Argument for automatic confirmation.
-#[derive(Args, Copy, Clone, Debug)]
-pub struct Confirmation {
-    /// Automatic confirmation. Without this option, you will be prompted before dropping
-    /// your database.
-    #[clap(short)]
-    pub yes: bool,
-}
+        /// List the checksums that would be updated without updating them
+        #[clap(long)]
+        dry_run: bool,
 
-/// Argument for ignoring applied migrations that were not resolved.
-#[derive(Args, Copy, Clone, Debug)]
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Print a hash of the applied migration history: the version and checksum of every
+    /// migration recorded in the migrations table, in version order.
+    ///
+    /// Two databases with the same fingerprint have the same applied migrations, e.g. to
+    /// check that staging and production have not diverged. Read-only.
+    Fingerprint {
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+
+        /// Print the fingerprint as JSON, together with the version and checksum of every
+        /// applied migration, to find where two databases diverge.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Print the statements which create the migrations table, as `run` executes them,
+    /// without connecting to the database.
+    ///
+    /// The driver is picked from the scheme of `--database-url`. A DBA can review the
+    /// statements or create the table beforehand; sqlx then finds it already present.
+    PrintTableDdl {
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        /// The migrations table, optionally qualified with its schema, e.g. `meta._sqlx_migrations`.
+        #[clap(long)]
+        migration_table: Option<String>,
+
+        /// Also print the index `run --index-installed-on` creates.
+        #[clap(long)]
+        index_installed_on: bool,
+    },
+
+    /// Print the applied migration history as SQL: a `CREATE TABLE IF NOT EXISTS` for the
+    /// migrations table and an `INSERT` for every applied migration, in the dialect of the
+    /// database.
+    ///
+    /// Replaying the output recreates the history on another database, e.g. a restored one,
+    /// without running the migrations. Read-only.
+    ExportHistory {
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Print statistics on the execution times recorded for the applied migrations: total,
+    /// average, the slowest migrations and a histogram.
+    ///
+    /// Shows which migrations dominate the setup time of a fresh database, e.g. to optimize
+    /// or squash them. Read-only.
+    Stats {
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+
+        /// How many of the slowest migrations to list.
+        #[clap(long, default_value_t = 5)]
+        top: usize,
+
+        /// Print the execution time of every applied migration as JSON instead, e.g. for
+        /// charting.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// List the tables in the database which look like migrations tables, i.e. have the
+    /// columns of one, and how many migrations each records.
+    ///
+    /// Warns if more than one records migrations, e.g. because some were run with a different
+    /// `--migration-table`. Read-only.
+    FindTables {
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        /// The table the other commands use, marked in the output.
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Compare the migrations in two directories.
+    ///
+    /// Prints one line per difference: `-` for migrations only in the first directory,
+    /// `+` for migrations only in the second, and `~` for migrations present in both
+    /// but with different checksums.
+    DiffDirs {
+        /// The base migrations directory.
+        dir_a: String,
+
+        /// The migrations directory to compare against the base.
+        dir_b: String,
+
+        /// Print the differences as JSON instead.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Generate a draft migration reconciling the local migrations with the schema of a
+    /// database.
+    ///
+    /// Runs every local migration on a scratch database created next to the one at
+    /// `--database-url`, compares the tables and columns of both, and writes a new migration
+    /// with the DDL turning the former into the latter. The scratch database is dropped
+    /// afterwards.
+    ///
+    /// Best-effort: constraints, indexes, defaults and column type changes are not reconciled.
+    /// Always review the generated migration before applying it.
+    Generate {
+        description: String,
+
+        #[clap(flatten)]
+        source: Source,
+
+        /// Compare against the schema of the database at `--database-url`. Currently the
+        /// only supported mode, so `generate` fails without it.
+        #[clap(long)]
+        from_database: bool,
+
+        /// If set, use timestamp versioning for the new migration. Conflicts with `--sequential`.
+        #[clap(short, long)]
+        timestamp: bool,
+
+        /// If set, use sequential versioning for the new migration. Conflicts with `--timestamp`.
+        #[clap(short, long, conflicts_with = "timestamp")]
+        sequential: bool,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Acknowledge the manual follow-up of an applied migration with a
+    /// `-- sqlx:manual-followup <message>` directive, so that `info` stops reminding of it.
+    Ack {
+        /// The version of the migration whose follow-up was done.
+        #[clap(long)]
+        version: i64,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Drop the objects a dirty migration created before it failed, and clear its dirty flag so
+    /// that `run` applies it again from a clean slate.
+    ///
+    /// Best-effort: the `DROP ... IF EXISTS` statements are guessed from the `CREATE` statements
+    /// of the migration, and anything else it changed is not undone. The plan is printed and has
+    /// to be confirmed first.
+    CleanupDirty {
+        #[clap(flatten)]
+        source: Source,
+
+        /// The version of the dirty migration.
+        #[clap(long)]
+        version: i64,
+
+        /// Print the cleanup plan without executing it
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Drop the schema given by `--target-schema` and everything in it, e.g. after testing
+    /// migrations there. Only supported on PostgreSQL.
+    DropSchema {
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+    },
+
+    /// Check that the down migrations revert their up migrations, on a scratch database
+    /// created next to the one at `--database-url`.
+    ///
+    /// Applies the local migrations in order. Each migration with a down migration is
+    /// reverted and applied again before moving on to the next one. The scratch database is
+    /// dropped afterwards.
+    TestReversibility {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Also check that reverting each migration restores the tables and columns from
+        /// before it was applied, and report the differences otherwise.
+        #[clap(long)]
+        schema_equal: bool,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Rename the local migrations to another versioning scheme, keeping their order, and
+    /// update the versions recorded in the migrations table to match.
+    ///
+    /// This rewrites history: every other database the migrations were applied to must be
+    /// converted at the same time, and every binary embedding them with `migrate!()` rebuilt.
+    /// Without a database URL, or with `--files-only`, only the files are renamed.
+    Convert {
+        #[clap(flatten)]
+        source: Source,
+
+        /// The versioning scheme to convert to.
+        #[clap(long, value_enum)]
+        to: Versioning,
+
+        /// Only rename the local files, leaving the migrations table untouched.
+        #[clap(long)]
+        files_only: bool,
+
+        /// List every rename and version update without changing anything.
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Squash the migrations up to a version into a single migration, e.g. to speed up setting
+    /// up a fresh database once many migrations have accumulated.
+    ///
+    /// The up migrations with a version up to and including `--before` are concatenated, in
+    /// order, into a new migration with the latest of their versions; it is reversible if all
+    /// of them are. The originals are moved to `archive/<VERSION>` in the migrations directory,
+    /// which is not read.
+    ///
+    /// Refuses to run unless the database has applied either all or none of the squashed
+    /// migrations. If all, the migrations table then records the squashed migration in their
+    /// place. Run it again against every other database which applied them: once the files are
+    /// squashed, only the migrations table is updated.
+    Squash {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Squash the migrations with a version up to and including this one.
+        #[clap(long, value_name = "VERSION")]
+        before: i64,
+
+        /// The description of the squashed migration.
+        #[clap(long, default_value = "squashed")]
+        description: String,
+
+        /// List the files to be archived and created, and the changes to the migrations
+        /// table, without making them.
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(flatten)]
+        confirmation: Confirmation,
+
+        #[clap(flatten)]
+        connect_opts: ConnectOpts,
+
+        #[clap(long)]
+        migration_table: Option<String>,
+    },
+
+    /// Generate a `build.rs` to trigger recompilation when a new migration is added.
+    ///
+    /// Must be run in a Cargo project root.
+    BuildScript {
+        #[clap(flatten)]
+        source: Source,
+
+        /// Overwrite the build script if it already exists.
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+impl MigrateCommand {
+    /// The `--source` argument, for subcommands which read the migrations folder.
+    pub fn source(&self) -> Option<&Source> {
+        match self {
+            MigrateCommand::Init { source, .. }
+            | MigrateCommand::Add { source, .. }
+            | MigrateCommand::NextVersion { source, .. }
+            | MigrateCommand::ListVersions { source, .. }
+            | MigrateCommand::Run { source, .. }
+            | MigrateCommand::Revert { source, .. }
+            | MigrateCommand::Info { source, .. }
+            | MigrateCommand::RollbackToSnapshot { source, .. }
+            | MigrateCommand::Rerun { source, .. }
+            | MigrateCommand::Audit { source, .. }
+            | MigrateCommand::Verify { source, .. }
+            | MigrateCommand::RepairChecksums { source, .. }
+            | MigrateCommand::FixChecksums { source, .. }
+            | MigrateCommand::CleanupDirty { source, .. }
+            | MigrateCommand::Generate { source, .. }
+            | MigrateCommand::TestReversibility { source, .. }
+            | MigrateCommand::Convert { source, .. }
+            | MigrateCommand::Squash { source, .. }
+            | MigrateCommand::BuildScript { source, .. } => Some(source),
+            MigrateCommand::Snapshot { .. }
+            | MigrateCommand::Seed { .. }
+            | MigrateCommand::ApplyFile { .. }
+            | MigrateCommand::Fingerprint { .. }
+            | MigrateCommand::Stats { .. }
+            | MigrateCommand::ExportHistory { .. }
+            | MigrateCommand::PrintTableDdl { .. }
+            | MigrateCommand::FindTables { .. }
+            | MigrateCommand::DiffDirs { .. }
+            | MigrateCommand::DropSchema { .. }
+            | MigrateCommand::Ack { .. } => None,
+        }
+    }
+
+    /// The database connection arguments, for subcommands which connect.
+    pub fn connect_opts(&self) -> Option<&ConnectOpts> {
+        match self {
+            MigrateCommand::ListVersions { connect_opts, .. }
+            | MigrateCommand::Run { connect_opts, .. }
+            | MigrateCommand::Revert { connect_opts, .. }
+            | MigrateCommand::Info { connect_opts, .. }
+            | MigrateCommand::Snapshot { connect_opts, .. }
+            | MigrateCommand::RollbackToSnapshot { connect_opts, .. }
+            | MigrateCommand::Rerun { connect_opts, .. }
+            | MigrateCommand::Seed { connect_opts, .. }
+            | MigrateCommand::ApplyFile { connect_opts, .. }
+            | MigrateCommand::Audit { connect_opts, .. }
+            | MigrateCommand::Verify { connect_opts, .. }
+            | MigrateCommand::RepairChecksums { connect_opts, .. }
+            | MigrateCommand::FixChecksums { connect_opts, .. }
+            | MigrateCommand::CleanupDirty { connect_opts, .. }
+            | MigrateCommand::Fingerprint { connect_opts, .. }
+            | MigrateCommand::Stats { connect_opts, .. }
+            | MigrateCommand::ExportHistory { connect_opts, .. }
+            | MigrateCommand::PrintTableDdl { connect_opts, .. }
+            | MigrateCommand::FindTables { connect_opts, .. }
+            | MigrateCommand::Generate { connect_opts, .. }
+            | MigrateCommand::TestReversibility { connect_opts, .. }
+            | MigrateCommand::DropSchema { connect_opts, .. }
+            | MigrateCommand::Convert { connect_opts, .. }
+            | MigrateCommand::Squash { connect_opts, .. }
+            | MigrateCommand::Ack { connect_opts, .. } => Some(connect_opts),
+            _ => None,
+        }
+    }
+
+    /// [`MigrateCommand::connect_opts`], mutably.
+    pub fn connect_opts_mut(&mut self) -> Option<&mut ConnectOpts> {
+        match self {
+            MigrateCommand::ListVersions { connect_opts, .. }
+            | MigrateCommand::Run { connect_opts, .. }
+            | MigrateCommand::Revert { connect_opts, .. }
+            | MigrateCommand::Info { connect_opts, .. }
+            | MigrateCommand::Snapshot { connect_opts, .. }
+            | MigrateCommand::RollbackToSnapshot { connect_opts, .. }
+            | MigrateCommand::Rerun { connect_opts, .. }
+            | MigrateCommand::Seed { connect_opts, .. }
+            | MigrateCommand::ApplyFile { connect_opts, .. }
+            | MigrateCommand::Audit { connect_opts, .. }
+            | MigrateCommand::Verify { connect_opts, .. }
+            | MigrateCommand::RepairChecksums { connect_opts, .. }
+            | MigrateCommand::FixChecksums { connect_opts, .. }
+            | MigrateCommand::CleanupDirty { connect_opts, .. }
+            | MigrateCommand::Fingerprint { connect_opts, .. }
+            | MigrateCommand::Stats { connect_opts, .. }
+            | MigrateCommand::ExportHistory { connect_opts, .. }
+            | MigrateCommand::PrintTableDdl { connect_opts, .. }
+            | MigrateCommand::FindTables { connect_opts, .. }
+            | MigrateCommand::Generate { connect_opts, .. }
+            | MigrateCommand::TestReversibility { connect_opts, .. }
+            | MigrateCommand::DropSchema { connect_opts, .. }
+            | MigrateCommand::Convert { connect_opts, .. }
+            | MigrateCommand::Squash { connect_opts, .. }
+            | MigrateCommand::Ack { connect_opts, .. } => Some(connect_opts),
+            _ => None,
+        }
+    }
+
+    /// The `--migration-table` argument, for subcommands which accept it.
+    pub fn migration_table(&self) -> Option<Option<&str>> {
+        match self {
+            MigrateCommand::Add {
+                migration_table, ..
+            }
+            | MigrateCommand::ListVersions {
+                migration_table, ..
+            }
+            | MigrateCommand::Run {
+                migration_table, ..
+            }
+            | MigrateCommand::Revert {
+                migration_table, ..
+            }
+            | MigrateCommand::Info {
+                migration_table, ..
+            }
+            | MigrateCommand::Snapshot {
+                migration_table, ..
+            }
+            | MigrateCommand::RollbackToSnapshot {
+                migration_table, ..
+            }
+            | MigrateCommand::Rerun {
+                migration_table, ..
+            }
+            | MigrateCommand::Seed {
+                migration_table, ..
+            }
+            | MigrateCommand::ApplyFile {
+                migration_table, ..
+            }
+            | MigrateCommand::Audit {
+                migration_table, ..
+            }
+            | MigrateCommand::Verify {
+                migration_table, ..
+            }
+            | MigrateCommand::RepairChecksums {
+                migration_table, ..
+            }
+            | MigrateCommand::FixChecksums {
+                migration_table, ..
+            }
+            | MigrateCommand::CleanupDirty {
+                migration_table, ..
+            }
+            | MigrateCommand::Fingerprint {
+                migration_table, ..
+            }
+            | MigrateCommand::Stats {
+                migration_table, ..
+            }
+            | MigrateCommand::ExportHistory {
+                migration_table, ..
+            }
+            | MigrateCommand::PrintTableDdl {
+                migration_table, ..
+            }
+            | MigrateCommand::FindTables {
+                migration_table, ..
+            }
+            | MigrateCommand::Generate {
+                migration_table, ..
+            }
+            | MigrateCommand::TestReversibility {
+                migration_table, ..
+            }
+            | MigrateCommand::Convert {
+                migration_table, ..
+            }
+            | MigrateCommand::Squash {
+                migration_table, ..
+            }
+            | MigrateCommand::Ack {
+                migration_table, ..
+            } => Some(migration_table.as_deref()),
+            _ => None,
+        }
+    }
+}
+
+/// A scheme for numbering migration versions.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Versioning {
+    /// `YYYYMMDDHHMMSS` timestamps, as created by `add --timestamp`.
+    Timestamp,
+    /// `0001`, `0002` and so on, as created by `add --sequential`.
+    Sequential,
+}
+
+impl Versioning {
+    pub fn name(self) -> &'static str {
+        match self {
+            Versioning::Timestamp => "timestamp",
+            Versioning::Sequential => "sequential",
+        }
+    }
+}
+
+/// Options controlling how `sqlx migrate run` applies pending migrations.
+#[derive(Args, Debug, Default)]
+pub struct RunOpts {
+    /// List all the migrations to be run without applying
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// With `--dry-run`, also print the SQL each pending migration would execute, under a
+    /// header naming the migration.
+    #[clap(long, requires = "dry_run")]
+    pub show_sql: bool,
+
+    #[clap(flatten)]
+    pub ignore_missing: IgnoreMissing,
+
+    /// Apply migrations up to the specified version, or `latest` for all of them. If
+    /// unspecified, apply all pending migrations. If already at the target version, then no-op.
+    #[clap(long)]
+    pub target_version: Option<TargetVersion>,
+
+    /// Only apply pending migrations with these versions, e.g. `5..8` or `3,4,7`.
+    ///
+    /// Ranges are inclusive. Earlier pending migrations may not be skipped unless
+    /// `--allow-gaps` is passed.
+    #[clap(long, conflicts_with = "target_version")]
+    pub versions: Option<VersionSet>,
+
+    /// Apply migrations up to and including the one whose description contains this text.
+    ///
+    /// Fails if no migration or more than one migration matches.
+    #[clap(long, conflicts_with_all = ["target_version", "versions"])]
+    pub target_description: Option<String>,
+
+    /// Apply only the pending migration with this version.
+    ///
+    /// Fails if it is already applied, or if an earlier migration is still pending.
+    #[clap(
+        long,
+        value_name = "VERSION",
+        conflicts_with_all = ["target_version", "versions", "target_description", "fake"]
+    )]
+    pub only: Option<i64>,
+
+    /// Fail if the migrations directory contains no migrations, instead of doing nothing.
+    ///
+    /// Catches a `--source` pointing at the wrong directory, e.g. in CI.
+    #[clap(long)]
+    pub fail_on_empty_source: bool,
+
+    /// Record pending `-- sqlx:baseline` migrations as applied without executing them,
+    /// regardless of their probe query. Use when adopting a pre-existing database.
+    #[clap(long)]
+    pub adopt: bool,
+
+    /// If the database is dirty, re-apply the dirty migration and continue.
+    ///
+    /// This is only safe if the dirty migration is idempotent, i.e. it can be run again on
+    /// top of its own partially applied effects. If re-applying fails, the database stays dirty.
+    /// A migration which failed while being reverted is not re-applied.
+    #[clap(long)]
+    pub auto_recover: bool,
+
+    /// Run even if the database is dirty, without re-applying the dirty migration.
+    ///
+    /// Dangerous: only for when a failed migration has already been fully resolved by hand.
+    /// Prefer `--auto-recover` where the dirty migration can be re-applied.
+    #[clap(long, conflicts_with = "auto_recover")]
+    pub no_dirty_check: bool,
+
+    /// Record the migration given by `--version` as applied, without executing it.
+    ///
+    /// Use this when the migration's effects were already applied by hand.
+    #[clap(long, requires = "version")]
+    pub fake: bool,
+
+    /// The pending migration to record with `--fake`.
+    #[clap(long, requires = "fake")]
+    pub version: Option<i64>,
+
+    /// Allow `--fake` or `--versions` to apply a migration while earlier migrations are still
+    /// pending.
+    #[clap(long)]
+    pub allow_gaps: bool,
+
+    /// Automatic confirmation. Without this option, you will be prompted before a migration
+    /// is recorded with `--fake`.
+    #[clap(short)]
+    pub yes: bool,
+
+    /// The maximum time, in seconds, each migration may wait to acquire a lock.
+    ///
+    /// Only supported on PostgreSQL. Migrations with a `-- sqlx:lock-timeout` directive
+    /// use their own timeout instead.
+    #[clap(long, value_name = "SECS")]
+    pub lock_timeout: Option<u64>,
+
+    /// The maximum time, in seconds, to wait for the migration lock while another migration
+    /// is in progress.
+    #[clap(long, value_name = "SECS")]
+    pub migration_lock_timeout: Option<u64>,
+
+    /// Apply all pending migrations in one transaction, so that a failure rolls back the
+    /// whole batch instead of leaving earlier migrations applied.
+    ///
+    /// Only supported on PostgreSQL: MySQL and SQLite cannot roll back DDL in a transaction.
+    #[clap(long)]
+    pub single_transaction: bool,
+
+    /// With `--single-transaction`, set a savepoint before each migration. If a migration
+    /// fails, only it is rolled back to its savepoint, and the migrations applied before it
+    /// in the batch are committed.
+    #[clap(long, requires = "single_transaction")]
+    pub savepoint_per_migration: bool,
+
+    /// Refuse to apply a pending migration whose version is lower than the latest applied
+    /// migration, e.g. one that arrived out of order after a merge.
+    #[clap(long)]
+    pub strict_ordering: bool,
+
+    /// Warn about applied migrations which were modified after being applied, then apply
+    /// pending migrations anyway. By default, such modifications abort the run.
+    #[clap(long)]
+    pub warn_on_mismatch: bool,
+
+    /// Record each migration's execution time as measured by the database server,
+    /// excluding network latency and bookkeeping. Only supported by Postgres; other
+    /// databases record client wall-clock time as usual.
+    #[clap(long)]
+    pub server_timing: bool,
+
+    /// Print `[statement k/total]` before each statement of a migration is executed, e.g. to
+    /// follow a long data migration. Statements are executed one at a time, in the migration's
+    /// transaction as usual. Migrations with a `-- sqlx:batch-commit` or
+    /// `-- sqlx:no-transaction` directive report no progress.
+    #[clap(long, conflicts_with = "server_timing")]
+    pub progress: bool,
+
+    /// Index the `installed_on` column of the migrations table, creating the index if it
+    /// does not exist yet.
+    ///
+    /// Only worthwhile for migrations tables with many rows, e.g. shared by many tenants;
+    /// the index costs a few dozen bytes of storage per applied migration.
+    #[clap(long)]
+    pub index_installed_on: bool,
+
+    /// Refuse to run migrations which mix sequential versions (e.g. `0003`) with timestamp
+    /// versions (e.g. `20240101120000`), instead of only warning about them.
+    #[clap(long)]
+    pub strict_versions: bool,
+
+    /// Have the database server log every statement while migrations run, e.g. to find out
+    /// what a problematic migration executed.
+    ///
+    /// Only affects the session running the migrations, and is reset afterwards even if a
+    /// migration fails. Only supported on PostgreSQL, where it sets `log_statement = 'all'`
+    /// and requires superuser; MySQL's general log cannot be enabled for a single session.
+    #[clap(long)]
+    pub server_log: bool,
+
+    /// Cancel the run as soon as a file is created at this path, for schedulers which cannot
+    /// easily send signals.
+    ///
+    /// The statement in flight is cancelled on the server before the connection is closed. On
+    /// PostgreSQL the migration being applied is rolled back; on MySQL, whose DDL cannot be
+    /// rolled back, it is left dirty. Not supported on SQLite.
+    #[clap(long, value_name = "PATH")]
+    pub cancel_file: Option<String>,
+
+    /// Set this boolean column, given as `<TABLE>.<COLUMN>`, to true in every row of its table
+    /// before running migrations, and back to false afterwards, even if a migration fails.
+    ///
+    /// For applications which switch to a read-only maintenance mode while the flag is set.
+    /// The table must already exist and have at least one row. Not touched with `--dry-run`.
+    #[clap(long, value_name = "TABLE.COLUMN")]
+    pub maintenance_flag: Option<MaintenanceFlag>,
+
+    /// Compute checksums with SQL comments stripped, so that editing only the comments of an
+    /// applied migration is not reported as a modification.
+    ///
+    /// Migrations applied without this flag must have their recorded checksums updated with
+    /// `sqlx migrate repair-checksums --checksum-ignore-comments` first, and vice versa.
+    #[clap(long)]
+    pub checksum_ignore_comments: bool,
+
+    /// With `--dry-run`, write a script reverting the migrations which would be applied to
+    /// this path: their down migrations in reverse order, each followed by the removal of its
+    /// row from the migrations table.
+    ///
+    /// Pending migrations without a down migration are flagged in the script and the output.
+    #[clap(long, value_name = "PATH", requires = "dry_run")]
+    pub emit_undo: Option<String>,
+
+    /// Record this run as one row of a separate table: when it started and finished, how many
+    /// migrations it applied, whether it succeeded, `--release-id` and the resulting latest
+    /// version.
+    ///
+    /// Purely additive: the table is created on demand and the migrations table is unchanged.
+    /// Dry runs and runs cancelled with `--cancel-file` are not recorded.
+    #[clap(long)]
+    pub audit_runs: bool,
+
+    /// The table `--audit-runs` records runs in. Defaults to the migrations table name
+    /// followed by `_runs`.
+    #[clap(long, requires = "audit_runs")]
+    pub audit_runs_table: Option<String>,
+
+    /// An identifier of the release being deployed, recorded by `--audit-runs`, together with
+    /// the migrations the run applied, which `revert --release` can then revert.
+    /// May contain letters, digits, `_`, `-` and `.`.
+    #[clap(long, requires = "audit_runs")]
+    pub release_id: Option<String>,
+
+    /// Print more details, such as the applied migrations ignored by `--ignore-missing`.
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    #[clap(flatten)]
+    pub notify: NotifyOpts,
+}
+
+/// Options controlling how `sqlx migrate revert` reverts applied migrations.
+#[derive(Args, Debug, Default)]
+pub struct RevertOpts {
+    /// List the migration to be reverted without applying
+    #[clap(long)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    pub ignore_missing: IgnoreMissing,
+
+    /// Revert migrations down to the specified version. If unspecified, revert
+    /// only the last migration. Set to 0 to revert all migrations. If already
+    /// at the target version, then no-op.
+    #[clap(long)]
+    pub target_version: Option<i64>,
+
+    /// Revert every applied migration, newest first. Same as `--target-version 0`, except
+    /// that it fails before reverting anything if an applied migration has no down
+    /// migration, and asks for confirmation.
+    #[clap(long, conflicts_with = "target_version")]
+    pub all: bool,
+
+    /// Revert this many of the latest applied migrations, newest first.
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["target_version", "all"]
+    )]
+    pub steps: Option<u32>,
+
+    /// Revert exactly the migrations applied by `run --audit-runs --release-id <RELEASE>`,
+    /// newest first, e.g. to roll back a deploy. Fails before reverting anything if one of
+    /// them has no down migration, and asks for confirmation.
+    #[clap(long, value_name = "RELEASE", conflicts_with_all = ["target_version", "all", "steps"])]
+    pub release: Option<String>,
+
+    /// Automatic confirmation. Without this option, you will be prompted before migrations
+    /// are reverted with `--target-version`, `--all` or `--release`, and these fail if stdin
+    /// is not a terminal.
+    #[clap(short, long)]
+    pub yes: bool,
+
+    /// Revert even if the database is dirty.
+    ///
+    /// Dangerous: only for when a failed migration has already been fully resolved by hand.
+    #[clap(long)]
+    pub no_dirty_check: bool,
+
+    /// The maximum time, in seconds, to wait for the migration lock while another migration
+    /// is in progress.
+    #[clap(long, value_name = "SECS")]
+    pub migration_lock_timeout: Option<u64>,
+}
+
+impl RevertOpts {
+    /// The version to revert down to, with `--all` meaning version 0.
+    pub fn target_version(&self) -> Option<i64> {
+        if self.all {
+            Some(0)
+        } else {
+            self.target_version
+        }
+    }
+}
+
+/// Arguments for reporting migration events to an HTTP endpoint.
+#[derive(Args, Debug, Default)]
+pub struct NotifyOpts {
+    /// POST a small JSON payload to this URL when migration events happen.
+    ///
+    /// Delivery is best-effort: failures print a warning but never fail the migration.
+    /// Requires sqlx-cli to be built with the `notify` feature.
+    #[clap(long)]
+    pub notify_url: Option<String>,
+
+    /// The events to send to `--notify-url`.
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [NotifyEvent::Start, NotifyEvent::Apply, NotifyEvent::Finish, NotifyEvent::Error]
+    )]
+    pub notify_on: Vec<NotifyEvent>,
+}
+
+/// A migration event which can be reported with `--notify-url`.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NotifyEvent {
+    /// The run started.
+    Start,
+    /// A migration was applied.
+    Apply,
+    /// The run finished successfully.
+    Finish,
+    /// The run failed.
+    Error,
+}
+
+impl NotifyEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyEvent::Start => "start",
+            NotifyEvent::Apply => "apply",
+            NotifyEvent::Finish => "finish",
+            NotifyEvent::Error => "error",
+        }
+    }
+}
+
+/// Options controlling what `sqlx migrate info` lists.
+#[derive(Args, Debug, Default)]
+pub struct InfoOpts {
+    /// Only list migrations with a version greater than this one.
+    #[clap(long, visible_alias = "since-applied")]
+    pub since: Option<i64>,
+
+    /// Only list migrations with these versions, e.g. `20240101..20240201` or `3,4,7`.
+    ///
+    /// Ranges are inclusive.
+    #[clap(long)]
+    pub versions: Option<VersionSet>,
+
+    /// Instead of listing migrations, wait until none are pending and the database is not
+    /// dirty, then exit.
+    ///
+    /// Useful to hold back a service until migrations applied elsewhere have finished.
+    /// Never takes the migration lock.
+    #[clap(long)]
+    pub watch_until_clean: bool,
+
+    /// The maximum time, in seconds, to wait with `--watch-until-clean` before exiting
+    /// with an error. Waits indefinitely if unspecified.
+    #[clap(long, requires = "watch_until_clean")]
+    pub timeout: Option<u64>,
+
+    /// Compare checksums with SQL comments stripped, as `run --checksum-ignore-comments` does.
+    #[clap(long)]
+    pub checksum_ignore_comments: bool,
+
+    /// Check the rows of the migrations table itself for corruption, e.g. duplicate versions
+    /// or missing checksums left behind by a manual edit, and exit with an error if any is
+    /// found.
+    #[clap(long, conflicts_with = "watch_until_clean")]
+    pub validate_table: bool,
+
+    /// Print the migrations as a JSON array instead, each with its `version`, `description`
+    /// and `status`: `installed`, `pending` or `installed_different_checksum`.
+    ///
+    /// Migrations with a different checksum also have `applied_checksum` and `local_checksum`,
+    /// in hex.
+    #[clap(long, conflicts_with_all = ["watch_until_clean", "validate_table"])]
+    pub json: bool,
+
+    /// Print more details, such as the applied migrations which are not found locally and
+    /// so are not listed.
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// Print only the successfully applied migrations, as recorded in the migrations table,
+    /// as a JSON array.
+    ///
+    /// Local migrations are not read nor compared, so this works without the source directory.
+    #[clap(
+        long,
+        conflicts_with_all = ["watch_until_clean", "validate_table", "json", "since", "versions"]
+    )]
+    pub applied_only_json: bool,
+
+    /// The time zone to print timestamps from the migrations table in: `utc`, `local`, or a
+    /// fixed offset such as `+02:00`.
+    ///
+    /// The stored timestamps are not affected. Currently only `--applied-only-json` prints
+    /// them.
+    #[clap(long, value_name = "ZONE", default_value = "utc", allow_hyphen_values = true)]
+    pub time_zone: DisplayTimeZone,
+
+    /// Exit with status 1 if any migration is pending, 2 if an applied migration has a
+    /// different checksum than the local one, or 3 if the database is dirty, e.g. to block a
+    /// deploy in CI. The migrations are listed as usual.
+    #[clap(long, conflicts_with_all = ["watch_until_clean", "applied_only_json"])]
+    pub exit_code: bool,
+
+    /// Acknowledge a dirty database: it still gets a warning, but does not change the
+    /// `--exit-code` status.
+    ///
+    /// `info` always lists the migrations of a dirty database, unlike `run`, which refuses to
+    /// apply any until the failed migration is resolved.
+    #[clap(long, conflicts_with = "watch_until_clean")]
+    pub allow_dirty: bool,
+
+    /// Compare the applied migrations with a manifest saved from another database, e.g. by
+    /// `fingerprint --json` or `info --applied-only-json`, and list the versions whose checksum
+    /// differs, which are applied only here (extra) or only in the manifest (missing).
+    ///
+    /// Exits with status 1 if there is any difference. Local migrations are not read.
+    #[clap(
+        long,
+        value_name = "MANIFEST",
+        conflicts_with_all = [
+            "watch_until_clean",
+            "validate_table",
+            "applied_only_json",
+            "exit_code",
+            "since",
+            "versions",
+        ]
+    )]
+    pub diff_against: Option<String>,
+}
+
+/// A set of migration versions given as a comma-separated list of versions and
+/// inclusive ranges, e.g. `3,5..8`.
+#[derive(Clone, Debug)]
+pub struct VersionSet(Vec<RangeInclusive<i64>>);
+
+impl VersionSet {
+    pub fn contains(&self, version: i64) -> bool {
+        self.0.iter().any(|range| range.contains(&version))
+    }
+}
+
+impl FromStr for VersionSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("invalid version {:?}", v.trim()))
+        };
+
+        s.split(',')
+            .map(|part| match part.split_once("..") {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!(
+                            "invalid range {:?}: start is after end",
+                            part.trim()
+                        ));
+                    }
+                    Ok(start..=end)
+                }
+                None => parse(part).map(|v| v..=v),
+            })
+            .collect::<Result<_, _>>()
+            .map(VersionSet)
+    }
+}
+
+/// The version given to `sqlx migrate run --target-version`: a migration version, or `latest`
+/// to apply every pending migration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetVersion {
+    Latest,
+    Version(i64),
+}
+
+impl FromStr for TargetVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("latest") {
+            return Ok(TargetVersion::Latest);
+        }
+
+        s.trim()
+            .parse::<i64>()
+            .map(TargetVersion::Version)
+            .map_err(|_| format!("invalid version {:?}; expected a number or `latest`", s.trim()))
+    }
+}
+
+/// The time zone timestamps are printed in, given to `sqlx migrate info --time-zone`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayTimeZone {
+    #[default]
+    Utc,
+    /// The time zone of the machine running the command.
+    Local,
+    Fixed(chrono::FixedOffset),
+}
+
+impl DisplayTimeZone {
+    /// Format `time` as RFC 3339 in this time zone.
+    pub fn format(&self, time: chrono::DateTime<chrono::Utc>) -> String {
+        use chrono::SecondsFormat;
+
+        match self {
+            DisplayTimeZone::Utc => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            DisplayTimeZone::Local => time
+                .with_timezone(&chrono::Local)
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            DisplayTimeZone::Fixed(offset) => time
+                .with_timezone(offset)
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        }
+    }
+}
+
+impl FromStr for DisplayTimeZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || {
+            format!("invalid time zone {s:?}; expected `utc`, `local` or an offset such as `+02:00`")
+        };
+
+        if s.eq_ignore_ascii_case("utc") {
+            return Ok(DisplayTimeZone::Utc);
+        }
+
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(DisplayTimeZone::Local);
+        }
+
+        let (sign, offset) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+            (Some(offset), _) => (1, offset),
+            (_, Some(offset)) => (-1, offset),
+            _ => return Err(invalid()),
+        };
+
+        // `+02`, `+0230` or `+02:30`
+        let digits = offset.replacen(':', "", 1);
+        if !matches!(digits.len(), 2 | 4) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+        let minutes: i32 = digits[2..].parse().unwrap_or(0);
+
+        chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .filter(|_| minutes < 60)
+            .map(DisplayTimeZone::Fixed)
+            .ok_or_else(invalid)
+    }
+}
+
+/// An application-defined boolean column flipped by `sqlx migrate run --maintenance-flag`.
+#[derive(Clone, Debug)]
+pub struct MaintenanceFlag {
+    /// The table, possibly qualified with its schema.
+    pub table: String,
+    pub column: String,
+}
+
+impl fmt::Display for MaintenanceFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.table, self.column)
+    }
+}
+
+impl FromStr for MaintenanceFlag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the names end up in SQL unquoted, like the migrations table
+        let valid = |name: &str| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+
+        match s.rsplit_once('.') {
+            Some((table, column)) if table.split('.').all(valid) && valid(column) => {
+                Ok(MaintenanceFlag {
+                    table: table.to_string(),
+                    column: column.to_string(),
+                })
+            }
+            _ => Err(format!("expected `<TABLE>.<COLUMN>`, got {s:?}")),
+        }
+    }
+}
+
+/// Argument for the migration scripts source.
+#[derive(Args, Debug)]
+pub struct Source {
+    /// Path to folder containing migrations.
+    ///
+    /// Several folders may be given separated by `:` (`;` on Windows), as in `PATH`; their
+    /// migrations are applied in one version order.
+    ///
+    /// Defaults to the `source` key of `.sqlx-migrate.toml`, or `migrations`.
+    #[clap(long)]
+    pub source: Option<String>,
+}
+
+impl Source {
+    /// Resolve the migrations folder, falling back to the config file and then the default.
+    pub fn resolve(&self, config: &Config) -> String {
+        config.resolve_source(self.source.as_deref()).value
+    }
+}
+
+/// Argument for the seed scripts source.
+#[derive(Args, Debug)]
+pub struct SeedSource {
+    /// Path to folder containing seed files.
+    #[clap(long = "seeds", default_value = "seeds")]
+    pub seed_source: String,
+}
+
+/// Argument for the database URL.
+#[derive(Args, Debug)]
+pub struct ConnectOpts {
+    /// Location of the DB, by default will be read from the DATABASE_URL env var or `.env` files.
+    #[clap(long, short = 'D', env)]
+    pub database_url: Option<String>,
+
+    /// Read the database URL from the OS keyring instead, from the entry for the account
+    /// `DATABASE_URL` of this service. Takes precedence over `--database-url` and the
+    /// DATABASE_URL env var.
+    #[cfg(feature = "keyring")]
+    #[clap(long, value_name = "SERVICE")]
+    pub database_url_from_keyring: Option<String>,
+
+    /// The maximum time, in seconds, to try connecting to the database server before
+    /// returning an error.
+    #[clap(long, default_value = "10")]
+    pub connect_timeout: u64,
+
+    /// Set whether or not to create SQLite databases in Write-Ahead Log (WAL) mode:
+    /// https://www.sqlite.org/wal.html
+    ///
+    /// WAL mode is enabled by default for SQLite databases created by `sqlx-cli`.
+    ///
+    /// However, if your application sets a `journal_mode` on `SqliteConnectOptions` to something
+    /// other than `Wal`, then it will have to take the database file out of WAL mode on connecting,
+    /// which requires an exclusive lock and may return a `database is locked` (`SQLITE_BUSY`) error.
+    #[cfg(feature = "sqlite")]
+    #[clap(long, action = clap::ArgAction::Set, default_value = "true")]
+    pub sqlite_create_db_wal: bool,
+
+    /// Work in this schema of the database instead, creating it if it does not exist.
+    ///
+    /// The schema becomes the only one on the connection's `search_path`, so migrations and
+    /// the migrations table are created in it. Drop it with `sqlx migrate drop-schema`.
+    /// Only supported on PostgreSQL.
+    #[clap(long)]
+    pub target_schema: Option<String>,
+}
+
+impl ConnectOpts {
+    /// Require a database URL to be provided, otherwise
+    /// return an error.
+    pub fn required_db_url(&self) -> anyhow::Result<&str> {
+        self.database_url.as_deref().ok_or_else(
+            || anyhow::anyhow!(
+                "the `--database-url` option the or `DATABASE_URL` environment variable must be provided"
+            )
+        )
+    }
+
+    /// Replace the database URL with the one stored in the OS keyring, if
+    /// `--database-url-from-keyring` was passed.
+    #[cfg(feature = "keyring")]
+    pub fn resolve_keyring(&mut self) -> anyhow::Result<()> {
+        let Some(service) = &self.database_url_from_keyring else {
+            return Ok(());
+        };
+
+        let entry = keyring::Entry::new(service, KEYRING_ACCOUNT)?;
+        match entry.get_password() {
+            Ok(url) => {
+                self.database_url = Some(url);
+                Ok(())
+            }
+            Err(keyring::Error::NoEntry) => anyhow::bail!(
+                "the OS keyring has no entry for account `{KEYRING_ACCOUNT}` of service \
+                 `{service}`; store the database URL there with your OS's credential manager, \
+                 e.g. on macOS: `security add-generic-password -s {service} -a {KEYRING_ACCOUNT} \
+                 -w <url>`"
+            ),
+            Err(e) => Err(anyhow::Error::new(e).context(format!(
+                "failed to read the database URL of service `{service}` from the OS keyring"
+            ))),
+        }
+    }
+}
+
+/// The account under which `--database-url-from-keyring` looks up the database URL.
+#[cfg(feature = "keyring")]
+pub const KEYRING_ACCOUNT: &str = "DATABASE_URL";
+
+/// Argument for automatic confirmation.
+#[derive(Args, Copy, Clone, Debug)]
+pub struct Confirmation {
+    /// Automatic confirmation. Without this option, you will be prompted before dropping
+    /// your database.
+    #[clap(short)]
+    pub yes: bool,
+}
+
+/// Argument for ignoring applied migrations that were not resolved.
+#[derive(Args, Copy, Clone, Debug, Default)]
 pub struct IgnoreMissing {
     /// Ignore applied migrations that are missing in the resolved migrations
     #[clap(long)]
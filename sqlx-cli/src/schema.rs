@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+
+use sqlx::AnyConnection;
+
+/// The tables of a database schema and their columns, in column order.
+///
+/// Only what `sqlx migrate generate` and `sqlx migrate test-reversibility --schema-equal`
+/// compare is captured: constraints, indexes, defaults, views and everything else are ignored.
+pub type Schema = BTreeMap<String, Vec<Column>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Read the tables and columns of the current schema (PostgreSQL) or database (MySQL, SQLite),
+/// except for the tables in `exclude`.
+pub async fn introspect(conn: &mut AnyConnection, exclude: &[String]) -> anyhow::Result<Schema> {
+    let query = match conn.backend_name() {
+        // language=PostgreSQL
+        "PostgreSQL" => {
+            "SELECT CAST(c.relname AS TEXT), CAST(a.attname AS TEXT), \
+                    format_type(a.atttypid, a.atttypmod), \
+                    CASE WHEN a.attnotnull THEN 'NO' ELSE 'YES' END \
+             FROM pg_catalog.pg_attribute a \
+             JOIN pg_catalog.pg_class c ON c.oid = a.attrelid \
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+             WHERE n.nspname = current_schema() AND c.relkind IN ('r', 'p') \
+               AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY c.relname, a.attnum"
+        }
+        // language=MySQL
+        "MySQL" => {
+            "SELECT CAST(c.table_name AS CHAR), CAST(c.column_name AS CHAR), \
+                    CAST(c.column_type AS CHAR), CAST(c.is_nullable AS CHAR) \
+             FROM information_schema.columns c \
+             JOIN information_schema.tables t \
+               ON t.table_schema = c.table_schema AND t.table_name = c.table_name \
+             WHERE c.table_schema = DATABASE() AND t.table_type = 'BASE TABLE' \
+             ORDER BY c.table_name, c.ordinal_position"
+        }
+        // language=SQLite
+        "SQLite" => {
+            "SELECT m.name, p.name, p.type, CASE WHEN p.\"notnull\" THEN 'NO' ELSE 'YES' END \
+             FROM sqlite_master m JOIN pragma_table_info(m.name) p \
+             WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+             ORDER BY m.name, p.cid"
+        }
+        backend => anyhow::bail!("schema introspection is not supported on {backend}"),
+    };
+
+    let rows: Vec<(String, String, String, String)> =
+        sqlx::query_as(query).fetch_all(&mut *conn).await?;
+
+    let mut schema = Schema::new();
+
+    for (table, name, data_type, nullable) in rows {
+        if exclude.contains(&table) {
+            continue;
+        }
+
+        schema.entry(table).or_default().push(Column {
+            name,
+            data_type,
+            nullable: nullable == "YES",
+        });
+    }
+
+    Ok(schema)
+}
+
+/// The DDL changing the tables and columns of `from` into those of `to`, one statement per
+/// entry. Differences which cannot be reconciled portably, such as a changed column type, are
+/// described in `-- TODO` comments instead.
+pub fn diff(backend: &str, from: &Schema, to: &Schema) -> Vec<String> {
+    let quote = |ident: &str| match backend {
+        "MySQL" => format!("`{}`", ident.replace('`', "``")),
+        _ => format!("\"{}\"", ident.replace('"', "\"\"")),
+    };
+    let definition = |column: &Column| {
+        let mut definition = format!("{} {}", quote(&column.name), column.data_type);
+        if !column.nullable {
+            definition.push_str(" NOT NULL");
+        }
+        definition
+    };
+
+    let mut statements = Vec::new();
+
+    for (table, columns) in to {
+        let Some(from_columns) = from.get(table) else {
+            let columns: Vec<_> = columns
+                .iter()
+                .map(|column| format!("    {}", definition(column)))
+                .collect();
+
+            statements.push(format!(
+                "CREATE TABLE {} (\n{}\n);",
+                quote(table),
+                columns.join(",\n")
+            ));
+            continue;
+        };
+
+        for column in columns {
+            match from_columns.iter().find(|c| c.name == column.name) {
+                None => {
+                    let mut statement = String::new();
+                    if !column.nullable {
+                        statement.push_str(
+                            "-- TODO: adding a NOT NULL column without a DEFAULT fails if the \
+                             table has rows\n",
+                        );
+                    }
+                    statement.push_str(&format!(
+                        "ALTER TABLE {} ADD COLUMN {};",
+                        quote(table),
+                        definition(column)
+                    ));
+                    statements.push(statement);
+                }
+                Some(from_column) if from_column != column => statements.push(format!(
+                    "-- TODO: {}.{} is `{}` in the migrations, but `{}` in the database",
+                    table,
+                    column.name,
+                    definition(from_column),
+                    definition(column)
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for column in from_columns {
+            if !columns.iter().any(|c| c.name == column.name) {
+                statements.push(format!(
+                    "ALTER TABLE {} DROP COLUMN {};",
+                    quote(table),
+                    quote(&column.name)
+                ));
+            }
+        }
+    }
+
+    for table in from.keys() {
+        if !to.contains_key(table) {
+            statements.push(format!("DROP TABLE {};", quote(table)));
+        }
+    }
+
+    statements
+}
+
+/// The differences between the tables and columns of `before` and `after`, one sentence each,
+/// ignoring the order of columns.
+pub fn differences(before: &Schema, after: &Schema) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    for (table, columns) in before {
+        let Some(after_columns) = after.get(table) else {
+            differences.push(format!("table `{table}` is missing"));
+            continue;
+        };
+
+        for column in columns {
+            match after_columns.iter().find(|c| c.name == column.name) {
+                None => differences.push(format!("column `{table}.{}` is missing", column.name)),
+                Some(after_column) if after_column != column => differences.push(format!(
+                    "column `{table}.{}` was `{}{}`, is now `{}{}`",
+                    column.name,
+                    column.data_type,
+                    if column.nullable { "" } else { " NOT NULL" },
+                    after_column.data_type,
+                    if after_column.nullable { "" } else { " NOT NULL" },
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for column in after_columns {
+            if !columns.iter().any(|c| c.name == column.name) {
+                differences.push(format!("column `{table}.{}` was left behind", column.name));
+            }
+        }
+    }
+
+    for table in after.keys() {
+        if !before.contains_key(table) {
+            differences.push(format!("table `{table}` was left behind"));
+        }
+    }
+
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, nullable: bool) -> Column {
+        Column {
+            name: name.into(),
+            data_type: data_type.into(),
+            nullable,
+        }
+    }
+
+    #[test]
+    fn diff_tables_and_columns() {
+        let from = Schema::from([
+            (
+                "users".to_string(),
+                vec![
+                    column("id", "bigint", false),
+                    column("name", "text", true),
+                    column("legacy", "text", true),
+                ],
+            ),
+            ("old".to_string(), vec![column("id", "bigint", false)]),
+        ]);
+        let to = Schema::from([
+            (
+                "users".to_string(),
+                vec![
+                    column("id", "bigint", false),
+                    column("name", "text", false),
+                    column("email", "text", true),
+                ],
+            ),
+            ("posts".to_string(), vec![column("id", "bigint", false)]),
+        ]);
+
+        assert_eq!(
+            diff("PostgreSQL", &from, &to),
+            [
+                "CREATE TABLE \"posts\" (\n    \"id\" bigint NOT NULL\n);",
+                "-- TODO: users.name is `\"name\" text` in the migrations, \
+                 but `\"name\" text NOT NULL` in the database",
+                "ALTER TABLE \"users\" ADD COLUMN \"email\" text;",
+                "ALTER TABLE \"users\" DROP COLUMN \"legacy\";",
+                "DROP TABLE \"old\";",
+            ]
+        );
+    }
+
+    #[test]
+    fn differences_after_revert() {
+        let before = Schema::from([
+            (
+                "users".to_string(),
+                vec![column("id", "bigint", false), column("name", "text", true)],
+            ),
+            ("old".to_string(), vec![column("id", "bigint", false)]),
+        ]);
+        let after = Schema::from([
+            (
+                "users".to_string(),
+                vec![
+                    column("email", "text", true),
+                    column("name", "text", false),
+                    column("id", "bigint", false),
+                ],
+            ),
+            ("posts".to_string(), vec![column("id", "bigint", false)]),
+        ]);
+
+        assert_eq!(
+            differences(&before, &after),
+            [
+                "table `old` is missing",
+                "column `users.name` was `text`, is now `text NOT NULL`",
+                "column `users.email` was left behind",
+                "table `posts` was left behind",
+            ]
+        );
+        assert!(differences(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn diff_identical() {
+        let schema = Schema::from([("t".to_string(), vec![column("id", "INTEGER", true)])]);
+
+        assert!(diff("SQLite", &schema, &schema).is_empty());
+    }
+}
@@ -3,10 +3,35 @@ use assert_cmd::{assert::Assert, Command};
 use sqlx::{migrate::Migrate, Connection, SqliteConnection};
 use std::{
     env::temp_dir,
-    fs::remove_file,
+    fs::{copy, create_dir_all, remove_dir_all, remove_file},
     path::{Path, PathBuf},
 };
 
+/// `cargo sqlx`, i.e. the `cargo-sqlx` binary with `sqlx` as its first argument.
+pub fn cargo_sqlx() -> Command {
+    let mut command = Command::cargo_bin("cargo-sqlx").unwrap();
+    command.arg("sqlx");
+    command
+}
+
+/// Copy the named migrations of `tests/migrations_reversible` to `dir` in the temp directory,
+/// replacing its previous contents, e.g. for a test which adds or edits migrations.
+pub fn copy_fixtures(dir: &str, names: &[&str]) -> PathBuf {
+    let source = temp_dir().join(dir);
+    let _ = remove_dir_all(&source);
+    create_dir_all(&source).unwrap();
+
+    for name in names {
+        copy(
+            Path::new("tests/migrations_reversible").join(name),
+            source.join(name),
+        )
+        .unwrap();
+    }
+
+    source
+}
+
 pub struct TestDatabase {
     file_path: PathBuf,
     migrations: String,
@@ -20,10 +45,8 @@ impl TestDatabase {
             file_path,
             migrations: String::from(migrations_path.to_str().unwrap()),
         };
-        Command::cargo_bin("cargo-sqlx")
-            .unwrap()
+        cargo_sqlx()
             .args([
-                "sqlx",
                 "database",
                 "create",
                 "--database-url",
@@ -43,12 +66,10 @@ impl TestDatabase {
             Some(v) => v.to_string(),
             None => String::from(""),
         };
-        Command::cargo_bin("cargo-sqlx")
-            .unwrap()
+        cargo_sqlx()
             .args(
                 [
                     vec![
-                        "sqlx",
                         "migrate",
                         match revert {
                             true => "revert",
@@ -67,6 +88,49 @@ impl TestDatabase {
                         true => vec!["--dry-run"],
                         false => vec![],
                     },
+                    // reverting to a target version asks for confirmation otherwise
+                    match (revert, version) {
+                        (true, Some(_)) => vec!["--yes"],
+                        _ => vec![],
+                    },
+                ]
+                .concat(),
+            )
+            .assert()
+    }
+
+    pub fn run_migration_with(&self, args: &[&str]) -> Assert {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "run",
+                        "--database-url",
+                        &self.connection_string(),
+                        "--source",
+                        &self.migrations,
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    }
+
+    pub fn revert_migration_with(&self, args: &[&str]) -> Assert {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "revert",
+                        "--database-url",
+                        &self.connection_string(),
+                        "--source",
+                        &self.migrations,
+                    ],
+                    args.to_vec(),
                 ]
                 .concat(),
             )
@@ -77,7 +141,10 @@ impl TestDatabase {
         let mut conn = SqliteConnection::connect(&self.connection_string())
             .await
             .unwrap();
-        conn.list_applied_migrations()
+        conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+            .await
+            .unwrap();
+        conn.list_applied_migrations(String::from("_sqlx_migrations"))
             .await
             .unwrap()
             .iter()
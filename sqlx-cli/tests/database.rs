@@ -0,0 +1,61 @@
+use assert_cmd::{assert::Assert, Command};
+use std::fs;
+use std::path::Path;
+
+fn database(dir: &Path, args: &[&str]) -> Assert {
+    Command::cargo_bin("cargo-sqlx")
+        .unwrap()
+        .current_dir(dir)
+        .args([&["sqlx", "database"], args].concat())
+        .assert()
+}
+
+fn stdout(assert: &Assert) -> String {
+    String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+}
+
+#[test]
+fn sqlite_relative_paths() {
+    let dir = std::env::temp_dir().join("sqlx-cli-sqlite-relative");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+
+    for (url, file) in [
+        ("sqlite://file.db", "file.db"),
+        ("sqlite://./sub/file.db", "sub/file.db"),
+    ] {
+        let path = dir.join(file);
+
+        let assert = database(&dir, &["create", "--database-url", url]).success();
+        assert!(stdout(&assert).contains(path.to_str().unwrap()));
+        assert!(path.is_file());
+
+        database(&dir, &["drop", "-y", "--database-url", url]).success();
+        assert!(!path.exists());
+    }
+
+    // There is no file to create for an in-memory database.
+    let assert = database(&dir, &["create", "--database-url", "sqlite::memory:"]).success();
+    assert!(!stdout(&assert).contains("Using SQLite database file"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sqlite_force_drop_not_supported() {
+    let dir = std::env::temp_dir().join("sqlx-cli-sqlite-force-drop");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+
+    let url = "sqlite://file.db";
+    database(&dir, &["create", "--database-url", url]).success();
+
+    let assert = database(&dir, &["drop", "-y", "--force", "--database-url", url]).failure();
+    assert!(stdout(&assert)
+        .contains("database driver does not support force-dropping a database (Only PostgreSQL)"));
+    assert!(dir.join("file.db").is_file());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,6 +1,7 @@
 mod common;
 
-use common::TestDatabase;
+use common::{cargo_sqlx, copy_fixtures, TestDatabase};
+use std::path::Path;
 
 #[tokio::test]
 async fn run_reversible_migrations() {
@@ -80,6 +81,350 @@ async fn run_reversible_migrations() {
     }
 }
 
+#[tokio::test]
+async fn run_migrations_by_versions() {
+    let db = TestDatabase::new("migrate_run_versions", "migrations_reversible");
+
+    // Inclusive range.
+    db.run_migration_with(&["--versions", "20230101000000..20230201000000"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    // Skipping an earlier pending migration is refused.
+    db.run_migration_with(&["--versions", "20230401000000"])
+        .failure();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    // Unless gaps are allowed.
+    db.run_migration_with(&["--versions", "20230401000000", "--allow-gaps"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000, 20230401000000]
+    );
+
+    // Comma-separated list.
+    db.run_migration_with(&["--versions", "20230301000000,20230501000000"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![
+            20230101000000,
+            20230201000000,
+            20230301000000,
+            20230401000000,
+            20230501000000,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn run_only_version() {
+    let db = TestDatabase::new("migrate_run_only", "migrations_reversible");
+
+    // An earlier migration is still pending.
+    let assert = db
+        .run_migration_with(&["--only", "20230201000000"])
+        .failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("earlier migration 20230101000000 (test1) is still pending"));
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    db.run_migration_with(&["--only", "20230101000000"])
+        .success();
+    assert_eq!(db.applied_migrations().await, vec![20230101000000]);
+
+    // Already applied.
+    db.run_migration_with(&["--only", "20230101000000"])
+        .failure();
+
+    // Applied out of order, before the next one.
+    db.run_migration_with(&["--versions", "20230301000000", "--allow-gaps"])
+        .success();
+    db.run_migration_with(&["--only", "20230201000000"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000, 20230301000000]
+    );
+}
+
+#[tokio::test]
+async fn run_to_target_description() {
+    let db = TestDatabase::new("migrate_run_target_description", "migrations_reversible");
+
+    // Ambiguous.
+    db.run_migration_with(&["--target-description", "test"])
+        .failure();
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    db.run_migration_with(&["--target-description", "test2"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    // Already applied.
+    db.run_migration_with(&["--target-description", "test1"])
+        .success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    // No match.
+    db.run_migration_with(&["--target-description", "nope"])
+        .failure();
+}
+
+#[tokio::test]
+async fn run_warns_on_mismatch() {
+    let source = copy_fixtures(
+        "sqlx-cli-warn-on-mismatch",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+        ],
+    );
+
+    let db = TestDatabase::new("migrate_warn_on_mismatch", source.to_str().unwrap());
+    db.run_migration(false, None, false).success();
+
+    // Modify the applied migration, and add a new one.
+    let applied = source.join("20230101000000_test1.up.sql");
+    let sql = std::fs::read_to_string(&applied).unwrap();
+    std::fs::write(&applied, format!("-- edited\n{sql}")).unwrap();
+    for name in [
+        "20230201000000_test2.up.sql",
+        "20230201000000_test2.down.sql",
+    ] {
+        std::fs::copy(
+            Path::new("tests/migrations_reversible").join(name),
+            source.join(name),
+        )
+        .unwrap();
+    }
+
+    db.run_migration(false, None, false).failure();
+    assert_eq!(db.applied_migrations().await, vec![20230101000000]);
+
+    let assert = db.run_migration_with(&["--warn-on-mismatch"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("modified after being applied"));
+    assert!(stdout.contains("20230101000000"));
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_ignore_missing_verbose() {
+    let source = copy_fixtures(
+        "sqlx-cli-ignore-missing-verbose",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+            "20230201000000_test2.up.sql",
+            "20230201000000_test2.down.sql",
+        ],
+    );
+
+    let db = TestDatabase::new("migrate_ignore_missing_verbose", source.to_str().unwrap());
+    db.run_migration(false, None, false).success();
+
+    std::fs::remove_file(source.join("20230101000000_test1.up.sql")).unwrap();
+    std::fs::remove_file(source.join("20230101000000_test1.down.sql")).unwrap();
+
+    let assert = db
+        .run_migration_with(&["--ignore-missing", "--verbose"])
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Ignoring 1 applied migrations not found in"));
+    assert!(stdout.contains("  20230101000000"));
+
+    // Silent without `--verbose`.
+    let assert = db.run_migration_with(&["--ignore-missing"]).success();
+    assert!(!String::from_utf8_lossy(&assert.get_output().stdout).contains("Ignoring"));
+
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "info",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            source.to_str().unwrap(),
+            "--verbose",
+        ])
+        .assert()
+        .success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout).contains("  20230101000000"));
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_target_version_latest() {
+    let db = TestDatabase::new("migrate_run_target_version_latest", "migrations_reversible");
+
+    // A version between two migrations lists its neighbours.
+    let assert = db
+        .run_migration_with(&["--target-version", "20230250000000"])
+        .failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("migration 20230250000000 is not present in the migration source"));
+    assert!(stdout.contains(
+        "the closest available versions are 20230101000000, 20230201000000, 20230301000000, \
+         20230401000000"
+    ));
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    db.run_migration_with(&["--target-version", "next"])
+        .failure();
+
+    db.run_migration_with(&["--target-version", "latest"])
+        .success();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+}
+
+#[tokio::test]
+async fn squash_migrations() {
+    let source = copy_fixtures(
+        "sqlx-cli-squash",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+            "20230201000000_test2.up.sql",
+            "20230201000000_test2.down.sql",
+            "20230301000000_test3.up.sql",
+            "20230301000000_test3.down.sql",
+            "20230401000000_test4.up.sql",
+            "20230401000000_test4.down.sql",
+            "20230501000000_test5.up.sql",
+            "20230501000000_test5.down.sql",
+        ],
+    );
+
+    let squash = |db: &TestDatabase, args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "squash",
+                        "--before",
+                        "20230301000000",
+                        "--database-url",
+                        &db.connection_string(),
+                        "--source",
+                        source.to_str().unwrap(),
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+
+    let applied = TestDatabase::new("migrate_squash_applied", source.to_str().unwrap());
+    applied.run_migration(false, None, false).success();
+    let other = TestDatabase::new("migrate_squash_other", source.to_str().unwrap());
+    other.run_migration(false, None, false).success();
+
+    // Only some of the squashed migrations are applied.
+    let partial = TestDatabase::new("migrate_squash_partial", source.to_str().unwrap());
+    partial
+        .run_migration(false, Some(20230101000000), false)
+        .success();
+    let assert = squash(&partial, &["-y"]).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("migrations 20230201000000, 20230301000000 are not applied"));
+
+    let assert = squash(&applied, &["--dry-run"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.matches("Can archive").count(), 6);
+    assert!(stdout.contains("Can record squashed migration 20230301000000"));
+    assert!(source.join("20230101000000_test1.up.sql").exists());
+
+    squash(&applied, &["-y"]).success();
+    assert_eq!(
+        applied.applied_migrations().await,
+        vec![20230301000000, 20230401000000, 20230501000000]
+    );
+    assert!(!source.join("20230101000000_test1.up.sql").exists());
+    assert!(source
+        .join("archive/20230301000000/20230101000000_test1.up.sql")
+        .exists());
+    let squashed = std::fs::read_to_string(source.join("20230301000000_squashed.up.sql")).unwrap();
+    assert!(squashed.contains("CREATE TABLE test1"));
+    assert!(squashed.contains("CREATE TABLE test3"));
+    assert!(source.join("20230301000000_squashed.down.sql").exists());
+    applied.run_migration(false, None, false).success();
+
+    // Another database which applied the migrations: only its migrations table is updated.
+    squash(&other, &["-y"]).success();
+    assert_eq!(
+        other.applied_migrations().await,
+        vec![20230301000000, 20230401000000, 20230501000000]
+    );
+    let assert = squash(&other, &["-y"]).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout).contains("already squashed"));
+    other.run_migration(false, None, false).success();
+
+    // A fresh database applies the squashed migration, which can be reverted.
+    let fresh = TestDatabase::new("migrate_squash_fresh", source.to_str().unwrap());
+    fresh.run_migration(false, None, false).success();
+    assert_eq!(fresh.applied_migrations().await.len(), 3);
+    fresh.run_migration(true, Some(0), false).success();
+    assert_eq!(fresh.applied_migrations().await, vec![] as Vec<i64>);
+
+    drop((applied, other, partial, fresh));
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_with_conn_leaves_connection_open() {
+    use sqlx::{AnyConnection, Connection};
+
+    let db = TestDatabase::new("migrate_run_with_conn", "migrations_reversible");
+
+    sqlx::any::install_default_drivers();
+    let mut conn = AnyConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+
+    sqlx_cli::run_with_conn(
+        &mut conn,
+        "tests/migrations_reversible",
+        None,
+        &sqlx_cli::RunOpts::default(),
+    )
+    .await
+    .unwrap();
+
+    // The caller's connection is still usable, e.g. to be returned to a pool.
+    conn.ping().await.unwrap();
+    let (applied,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM _sqlx_migrations")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(applied, 5);
+
+    conn.close().await.unwrap();
+}
+
 #[tokio::test]
 async fn revert_migrations() {
     let all_migrations: Vec<i64> = vec![
@@ -145,3 +490,2265 @@ async fn revert_migrations() {
         assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
     }
 }
+
+#[tokio::test]
+async fn revert_all_migrations() {
+    let db = TestDatabase::new("migrate_revert_all", "migrations_reversible");
+    db.run_migration(false, None, false).success();
+
+    // Dry-run
+    db.revert_migration_with(&["--all", "--dry-run"]).success();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    // `--all` and `--target-version` are mutually exclusive.
+    db.revert_migration_with(&["--all", "--target-version", "20230301000000"])
+        .failure();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    let assert = db.revert_migration_with(&["--all", "-y"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("No migrations are applied"));
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    // Nothing left to revert.
+    db.revert_migration_with(&["--all", "-y"]).success();
+}
+
+#[tokio::test]
+async fn revert_target_version_requires_confirmation() {
+    let db = TestDatabase::new("migrate_revert_confirm", "migrations_reversible");
+    db.run_migration(false, None, false).success();
+
+    // stdin is not a terminal, so the revert cannot be confirmed.
+    let assert = db
+        .revert_migration_with(&["--target-version", "20230301000000"])
+        .failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("pass --yes"));
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    // A dry run does not revert anything, so it needs no confirmation.
+    db.revert_migration_with(&["--target-version", "20230301000000", "--dry-run"])
+        .success();
+
+    db.revert_migration_with(&["--target-version", "20230301000000", "--yes"])
+        .success();
+    assert_eq!(db.applied_migrations().await.len(), 3);
+}
+
+#[tokio::test]
+async fn rollback_to_snapshot_requires_confirmation() {
+    let db = TestDatabase::new("migrate_snapshot_confirm", "migrations_reversible");
+
+    let migrate = |args: &[&str]| {
+        cargo_sqlx()
+            .arg("migrate")
+            .args(args)
+            .args(["--database-url", &db.connection_string()])
+            .assert()
+    };
+
+    db.run_migration_with(&["--target-version", "20230301000000"])
+        .success();
+    migrate(&["snapshot", "before"]).success();
+    db.run_migration(false, None, false).success();
+
+    let rollback = |extra: &[&str]| {
+        let mut args = vec![
+            "rollback-to-snapshot",
+            "before",
+            "--source",
+            "tests/migrations_reversible",
+        ];
+        args.extend_from_slice(extra);
+        migrate(&args)
+    };
+
+    // stdin is not a terminal, so the rollback cannot be confirmed.
+    let assert = rollback(&[]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("pass -y"));
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    rollback(&["-y"]).success();
+    assert_eq!(db.applied_migrations().await.len(), 3);
+}
+
+#[tokio::test]
+async fn revert_steps() {
+    let db = TestDatabase::new("migrate_revert_steps", "migrations_reversible");
+    db.run_migration(false, None, false).success();
+
+    // Dry-run
+    let assert = db
+        .revert_migration_with(&["--steps", "2", "--dry-run"])
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.matches("Can apply").count(), 2);
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    // `--steps` and `--target-version` are mutually exclusive.
+    db.revert_migration_with(&["--steps", "2", "--target-version", "20230301000000"])
+        .failure();
+
+    db.revert_migration_with(&["--steps", "2"]).success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000, 20230301000000]
+    );
+
+    // Not enough applied migrations.
+    db.revert_migration_with(&["--steps", "4"]).failure();
+    assert_eq!(db.applied_migrations().await.len(), 3);
+}
+
+#[tokio::test]
+async fn revert_release() {
+    let db = TestDatabase::new("migrate_revert_release", "migrations_reversible");
+
+    let revert = |release: &str| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "revert",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+                "--release",
+                release,
+                "-y",
+            ])
+            .assert()
+    };
+
+    db.run_migration_with(&[
+        "--audit-runs",
+        "--release-id",
+        "v1",
+        "--target-version",
+        "20230201000000",
+    ])
+    .success();
+    db.run_migration_with(&[
+        "--audit-runs",
+        "--release-id",
+        "v2",
+        "--target-version",
+        "20230401000000",
+    ])
+    .success();
+    // Not recorded under any release.
+    db.run_migration_with(&[]).success();
+
+    let assert = revert("v2").success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(
+        stdout.find("20230401000000").unwrap() < stdout.find("20230301000000").unwrap(),
+        "newest first"
+    );
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000, 20230501000000]
+    );
+
+    let assert = revert("v2").failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("no applied migrations were recorded under release v2"));
+    revert("v3").failure();
+
+    revert("v1").success();
+    assert_eq!(db.applied_migrations().await, vec![20230501000000]);
+}
+
+#[tokio::test]
+async fn revert_down_missing() {
+    let source = copy_fixtures(
+        "sqlx-cli-revert-down-missing",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+            "20230201000000_test2.up.sql",
+            "20230201000000_test2.down.sql",
+        ],
+    );
+
+    let db = TestDatabase::new("migrate_revert_down_missing", source.to_str().unwrap());
+    db.run_migration(false, None, false).success();
+
+    std::fs::remove_file(source.join("20230201000000_test2.down.sql")).unwrap();
+
+    // The older migration is not reverted in place of the one missing its down file.
+    let assert = db.run_migration(true, None, false).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout
+        .contains("migration 20230201000000 was previously applied but has no down migration"));
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    db.run_migration(true, Some(0), false).failure();
+    assert_eq!(db.applied_migrations().await.len(), 2);
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn list_versions() {
+    let db = TestDatabase::new("migrate_list_versions", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    let list = |args: &[&str]| {
+        let assert = cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "__list-versions",
+                        "--database-url",
+                        &db.connection_string(),
+                        "--source",
+                        "tests/migrations_reversible",
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+            .success();
+
+        String::from_utf8(assert.get_output().stdout.clone()).unwrap()
+    };
+
+    assert_eq!(
+        list(&["--applied"]),
+        "20230101000000\ttest1\n20230201000000\ttest2\n"
+    );
+    assert_eq!(
+        list(&["--pending"]),
+        "20230301000000\ttest3\n20230401000000\ttest4\n20230501000000\ttest5\n"
+    );
+    assert_eq!(list(&[]).lines().count(), 5);
+}
+
+#[tokio::test]
+async fn list_versions_does_not_write() {
+    let db = TestDatabase::new("migrate_list_versions_fresh", "migrations_reversible");
+
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "__list-versions",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            "tests/migrations_reversible",
+            "--pending",
+        ])
+        .assert()
+        .success();
+
+    // without a migrations table, nothing is applied
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 5);
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    let tables: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(tables, 0);
+    conn.close().await.unwrap();
+}
+
+#[test]
+fn add_uses_fake_now() {
+    let source = std::env::temp_dir().join("sqlx-cli-add-fake-now");
+    let _ = std::fs::remove_dir_all(&source);
+
+    cargo_sqlx()
+        .args([
+            "migrate",
+            "add",
+            "--source",
+            source.to_str().unwrap(),
+            "--timestamp",
+            "create users",
+        ])
+        .env("SQLX_MIGRATE_FAKE_NOW", "2024-01-01T12:00:00Z")
+        .assert()
+        .success();
+
+    assert!(source.join("20240101120000_create_users.sql").is_file());
+
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[test]
+fn add_edit() {
+    let source = std::env::temp_dir().join("sqlx-cli-add-edit");
+    let _ = std::fs::remove_dir_all(&source);
+
+    let add = |editor: Option<&str>, description: &str| {
+        let mut command = cargo_sqlx();
+        match editor {
+            Some(editor) => command.env("EDITOR", editor),
+            None => command.env_remove("EDITOR"),
+        };
+
+        let assert = command
+            .args(["migrate", "add", "-r", "--sequential", "--edit", "--source"])
+            .arg(&source)
+            .arg(description)
+            .assert()
+            .success();
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    // `echo` prints the files it was given, in order.
+    let stdout = add(Some("echo"), "create users");
+    let up = source.join("0001_create_users.up.sql");
+    let down = source.join("0001_create_users.down.sql");
+    assert!(stdout.contains(&format!("{} {}", up.display(), down.display())));
+
+    let stdout = add(None, "create posts");
+    assert!(stdout.contains("$EDITOR is not set"));
+    assert!(source.join("0002_create_posts.up.sql").is_file());
+
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[test]
+fn add_default_reversible() {
+    let dir = std::env::temp_dir().join("sqlx-cli-add-default-reversible");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join(".sqlx-migrate.toml"),
+        "default_reversible = true\n",
+    )
+    .unwrap();
+
+    let add = |args: &[&str]| {
+        cargo_sqlx()
+            .current_dir(&dir)
+            .env_remove("SQLX_DEFAULT_REVERSIBLE")
+            .args(["migrate", "add", "--sequential"])
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    add(&["create users"]);
+    assert!(dir.join("migrations/0001_create_users.up.sql").is_file());
+    assert!(dir.join("migrations/0001_create_users.down.sql").is_file());
+
+    // Once there are migrations, their type wins over the flag.
+    add(&["--no-reversible", "create posts"]);
+    assert!(dir.join("migrations/0002_create_posts.down.sql").is_file());
+
+    std::fs::remove_dir_all(dir.join("migrations")).unwrap();
+    add(&["--no-reversible", "create users"]);
+    assert!(dir.join("migrations/0001_create_users.sql").is_file());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn add_filename_template() {
+    let source = std::env::temp_dir().join("sqlx-cli-add-filename-template");
+    let _ = std::fs::remove_dir_all(&source);
+
+    let add_named = |template: &str, description: &str| {
+        cargo_sqlx()
+            .env("SQLX_MIGRATE_FAKE_NOW", "2024-01-02T12:00:00Z")
+            .args([
+                "migrate",
+                "add",
+                "--source",
+                source.to_str().unwrap(),
+                "--sequential",
+                "-r",
+                "--filename-template",
+                template,
+                description,
+            ])
+            .assert()
+    };
+    let add = |template: &str| add_named(template, "create users");
+
+    add("{version}_app_{description}{type}.sql").success();
+    assert!(source.join("0001_app_create_users.up.sql").is_file());
+    assert!(source.join("0001_app_create_users.down.sql").is_file());
+
+    // Dropping the version prefix or the type suffix is rejected before creating anything.
+    add("{description}_{version}{type}.sql").failure();
+    add("{version}_{description}.sql").failure();
+    add("{version}_{prefix}_{description}{suffix}").failure();
+    // the two migration files and the ordering marker
+    assert_eq!(std::fs::read_dir(&source).unwrap().count(), 3);
+
+    add_named("{version}_acme_{date}_{slug}{suffix}", "Add Email-Column").success();
+    assert!(source
+        .join("0002_acme_20240102_add_email_column.up.sql")
+        .is_file());
+    assert!(source
+        .join("0002_acme_20240102_add_email_column.down.sql")
+        .is_file());
+
+    add_named("{version}_{slug}_{date}{type}.sql", "Drop legacy TABLES").success();
+    assert!(source
+        .join("0003_drop_legacy_tables_20240102.up.sql")
+        .is_file());
+
+    // The versions are still read from the start of the names.
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "next-version",
+            "--source",
+            source.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8_lossy(&assert.get_output().stdout).trim(),
+        "0004"
+    );
+
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[test]
+fn add_ordering_marker() {
+    let source = std::env::temp_dir().join("sqlx-cli-add-ordering-marker");
+    let _ = std::fs::remove_dir_all(&source);
+
+    let add = |args: &[&str]| {
+        cargo_sqlx()
+            .env("SQLX_MIGRATE_FAKE_NOW", "2024-01-02T12:00:00Z")
+            .args(
+                [
+                    vec!["migrate", "add", "--source", source.to_str().unwrap()],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+
+    add(&["--sequential", "first"]).success();
+    assert_eq!(
+        std::fs::read_to_string(source.join(".sqlx-ordering")).unwrap(),
+        "sequential\n"
+    );
+
+    // Version 0005 would otherwise be inferred as a timestamp.
+    std::fs::rename(source.join("0001_first.sql"), source.join("0005_first.sql")).unwrap();
+    add(&["second"]).success();
+    assert!(source.join("0006_second.sql").is_file());
+
+    let assert = add(&["--timestamp", "third"]).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("--timestamp conflicts with the sequential versioning recorded in"));
+    add(&["--sequential", "third"]).success();
+    assert!(source.join("0007_third.sql").is_file());
+
+    std::fs::write(source.join(".sqlx-ordering"), "timestamp\n").unwrap();
+    add(&["fourth"]).success();
+    assert!(source.join("20240102120000_fourth.sql").is_file());
+
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[test]
+fn add_from_template_dir() {
+    let dir = std::env::temp_dir().join("sqlx-cli-add-from-template-dir");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let template = dir.join("template");
+    let source = dir.join("migrations");
+    std::fs::create_dir_all(template.join("notes")).unwrap();
+    std::fs::write(
+        template.join("{version}_{description}.up.sql"),
+        "-- {description} ({version})\n",
+    )
+    .unwrap();
+    std::fs::write(template.join("{version}_{description}.down.sql"), "").unwrap();
+    std::fs::write(template.join("notes/{version}.md"), "# {description}\n").unwrap();
+
+    let add = |template: &Path| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "add",
+                "--source",
+                source.to_str().unwrap(),
+                "--sequential",
+                "--from-template-dir",
+                template.to_str().unwrap(),
+                "create users",
+            ])
+            .assert()
+    };
+
+    add(&template).success();
+
+    assert_eq!(
+        std::fs::read_to_string(source.join("0001_create_users.up.sql")).unwrap(),
+        "-- create users (0001)\n"
+    );
+    assert!(source.join("0001_create_users.down.sql").is_file());
+    assert_eq!(
+        std::fs::read_to_string(source.join("notes/0001.md")).unwrap(),
+        "# create users\n"
+    );
+
+    // A template without an up migration is rejected.
+    std::fs::remove_file(template.join("{version}_{description}.up.sql")).unwrap();
+    add(&template).failure();
+    assert!(!source.join("0002_create_users.down.sql").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn add_with_build_script() {
+    let dir = std::env::temp_dir().join("sqlx-cli-add-with-build-script");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+
+    let add = |args: &[&str]| {
+        cargo_sqlx()
+            .current_dir(&dir)
+            .args(["migrate", "add", "--sequential", "create users"])
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    // Not interactive, so nothing is created without the flag.
+    add(&[]);
+    assert!(!dir.join("build.rs").exists());
+
+    // Only for the first migration.
+    add(&["--with-build-script"]);
+    assert!(!dir.join("build.rs").exists());
+
+    std::fs::remove_dir_all(dir.join("migrations")).unwrap();
+    add(&["--with-build-script"]);
+    assert!(std::fs::read_to_string(dir.join("build.rs"))
+        .unwrap()
+        .contains("cargo:rerun-if-changed=migrations"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn run_with_cancel_file() {
+    let db = TestDatabase::new("migrate_run_cancel_file", "migrations_reversible");
+    let cancel_file = std::env::temp_dir().join("sqlx-cli-run-cancel-file");
+    let cancel_file = cancel_file.to_str().unwrap();
+    let stdout = |assert: &assert_cmd::assert::Assert| {
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    // A leftover cancel file is refused rather than cancelling straight away.
+    std::fs::write(cancel_file, "").unwrap();
+    let assert = db
+        .run_migration_with(&["--cancel-file", cancel_file])
+        .failure();
+    assert!(stdout(&assert).contains("already exists"));
+
+    // SQLite runs statements in-process, so they cannot be cancelled.
+    std::fs::remove_file(cancel_file).unwrap();
+    let assert = db
+        .run_migration_with(&["--cancel-file", cancel_file])
+        .failure();
+    assert!(stdout(&assert).contains("not supported on SQLite"));
+
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+}
+
+#[tokio::test]
+async fn target_schema_requires_postgres() {
+    let db = TestDatabase::new("migrate_target_schema", "migrations_reversible");
+    let stdout = |assert: &assert_cmd::assert::Assert| {
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    let assert = db
+        .run_migration_with(&["--target-schema", "blue"])
+        .failure();
+    assert!(stdout(&assert).contains("only supported on PostgreSQL"));
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    let drop_schema = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "drop-schema",
+                "-y",
+                "--database-url",
+                &db.connection_string(),
+            ])
+            .args(args)
+            .assert()
+            .failure()
+    };
+
+    assert!(stdout(&drop_schema(&[])).contains("--target-schema is required"));
+    let assert = drop_schema(&["--target-schema", "blue"]);
+    assert!(stdout(&assert).contains("only supported on PostgreSQL"));
+}
+
+#[tokio::test]
+async fn info_validate_table() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_info_validate_table", "migrations_reversible");
+    let info = || {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "info",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+                "--validate-table",
+            ])
+            .assert()
+    };
+
+    // A migrations table without its primary key, as if created by hand.
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute(
+        "CREATE TABLE _sqlx_migrations ( version BIGINT, description TEXT NOT NULL, \
+         installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, success BOOLEAN NOT NULL, \
+         checksum BLOB NOT NULL, execution_time BIGINT NOT NULL )",
+    )
+    .await
+    .unwrap();
+
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+    info().success();
+
+    conn.execute(
+        "INSERT INTO _sqlx_migrations SELECT * FROM _sqlx_migrations \
+         WHERE version = 20230101000000",
+    )
+    .await
+    .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let assert = info().failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("version 20230101000000 is recorded more than once"));
+    assert!(stdout.contains("version 20230201000000 has a malformed checksum"));
+}
+
+#[tokio::test]
+async fn info_json() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_info_json", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "info",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            "tests/migrations_reversible",
+            "--json",
+        ])
+        .assert()
+        .success();
+
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let migrations = output.as_array().unwrap();
+    assert_eq!(migrations.len(), 5);
+
+    assert_eq!(migrations[0]["version"], 20230101000000i64);
+    assert_eq!(migrations[0]["description"], "test1");
+    assert_eq!(migrations[0]["status"], "installed");
+    assert!(migrations[0].get("applied_checksum").is_none());
+
+    assert_eq!(migrations[1]["status"], "installed_different_checksum");
+    assert_eq!(migrations[1]["applied_checksum"], "00");
+    assert_eq!(migrations[1]["local_checksum"].as_str().unwrap().len(), 96);
+
+    assert_eq!(migrations[2]["status"], "pending");
+}
+
+#[test]
+fn info_applied_only_json() {
+    let db = TestDatabase::new("migrate_info_applied_only_json", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    // No source directory needed.
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "info",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            "tests/does_not_exist",
+            "--applied-only-json",
+        ])
+        .assert()
+        .success();
+
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let applied = output.as_array().unwrap();
+    assert_eq!(applied.len(), 2);
+    assert_eq!(applied[0]["version"], 20230101000000i64);
+    assert_eq!(applied[1]["version"], 20230201000000i64);
+    assert_eq!(applied[1]["description"], "test2");
+    assert_eq!(applied[1]["checksum"].as_str().unwrap().len(), 96);
+    assert!(applied[1]["applied_at"].is_string());
+    assert!(applied[1]["execution_time"].is_i64());
+}
+
+#[tokio::test]
+async fn info_applied_at_time_zone() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_info_applied_at_time_zone", "migrations_reversible");
+    db.run_migration(false, Some(20230101000000), false)
+        .success();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET installed_on = '2024-01-01 12:00:00'")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let applied_at = |args: &[&str]| {
+        let assert = cargo_sqlx()
+            .args([
+                "migrate",
+                "info",
+                "--database-url",
+                &db.connection_string(),
+                "--applied-only-json",
+            ])
+            .args(args)
+            .assert()
+            .success();
+        let output: serde_json::Value =
+            serde_json::from_slice(&assert.get_output().stdout).unwrap();
+        output[0]["applied_at"].as_str().unwrap().to_owned()
+    };
+
+    // The same stored timestamp, rendered in two zones.
+    assert_eq!(applied_at(&[]), "2024-01-01T12:00:00Z");
+    assert_eq!(
+        applied_at(&["--time-zone", "+02:00"]),
+        "2024-01-01T14:00:00+02:00"
+    );
+    assert_eq!(
+        applied_at(&["--time-zone", "-0530"]),
+        "2024-01-01T06:30:00-05:30"
+    );
+
+    cargo_sqlx()
+        .args([
+            "migrate",
+            "info",
+            "--applied-only-json",
+            "--time-zone",
+            "mars",
+        ])
+        .assert()
+        .failure();
+}
+
+#[tokio::test]
+async fn print_table_ddl() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let print = |url: &str, args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec!["migrate", "print-table-ddl", "--database-url", url],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+
+    // Never connects, so the database does not need to exist.
+    let assert = print(
+        "sqlite:///does/not/exist.db",
+        &[
+            "--migration-table",
+            "main.migrations",
+            "--index-installed-on",
+        ],
+    )
+    .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("CREATE TABLE IF NOT EXISTS main.migrations ("));
+    assert!(stdout.contains("checksum BLOB NOT NULL"));
+    assert!(stdout.contains(
+        "CREATE INDEX IF NOT EXISTS main.migrations_installed_on_idx ON migrations (installed_on);"
+    ));
+
+    print("sqlite:///does/not/exist.db", &["--target-schema", "app"]).failure();
+
+    // A table created from the output is used as-is.
+    let db = TestDatabase::new("migrate_print_table_ddl", "migrations_reversible");
+    let assert = print(&db.connection_string(), &[]).success();
+    let ddl = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute(&*ddl).await.unwrap();
+    conn.close().await.unwrap();
+
+    db.run_migration(false, None, false).success();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+}
+
+#[tokio::test]
+async fn export_history() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_export_history", "migrations_reversible");
+    db.run_migration(false, Some(20230301000000), false)
+        .success();
+
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "export-history",
+            "--database-url",
+            &db.connection_string(),
+        ])
+        .assert()
+        .success();
+    let script = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(script.contains("CREATE TABLE IF NOT EXISTS _sqlx_migrations ("));
+    assert_eq!(script.matches("INSERT INTO _sqlx_migrations").count(), 3);
+
+    // Replayed on a fresh database, the history is the same, without any table created by the
+    // migrations themselves.
+    let restored = TestDatabase::new("migrate_export_history_restored", "migrations_reversible");
+    let mut conn = SqliteConnection::connect(&restored.connection_string())
+        .await
+        .unwrap();
+    conn.execute(&*script).await.unwrap();
+    let tables: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+    assert_eq!(tables, vec!["_sqlx_migrations"]);
+    conn.close().await.unwrap();
+
+    assert_eq!(
+        restored.applied_migrations().await,
+        db.applied_migrations().await
+    );
+
+    // The checksums match the local migrations.
+    let assert = restored
+        .run_migration(false, Some(20230301000000), false)
+        .success();
+    assert!(!String::from_utf8_lossy(&assert.get_output().stdout).contains("Applied"));
+}
+
+#[test]
+fn stats() {
+    let db = TestDatabase::new("migrate_stats", "migrations_reversible");
+
+    let stats = |args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "stats",
+                        "--database-url",
+                        &db.connection_string(),
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+            .success()
+    };
+
+    let assert = stats(&[]);
+    assert!(
+        String::from_utf8_lossy(&assert.get_output().stdout).contains("No migrations are applied")
+    );
+
+    db.run_migration(false, Some(20230301000000), false)
+        .success();
+
+    let assert = stats(&["--top", "2"]);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Applied migrations: 3"));
+    assert!(stdout.contains("Total time:"));
+    assert!(stdout.contains("Average time:"));
+    let slowest = stdout
+        .split("Slowest migrations:")
+        .nth(1)
+        .unwrap()
+        .split("Histogram:")
+        .next()
+        .unwrap();
+    assert_eq!(slowest.matches("/test").count(), 2);
+    // Every applied migration falls in one bucket.
+    let counted: usize = stdout
+        .split("Histogram:")
+        .nth(1)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.split_whitespace().last()?.parse::<usize>().ok())
+        .sum();
+    assert_eq!(counted, 3);
+
+    let assert = stats(&["--json"]);
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let migrations = output["migrations"].as_array().unwrap();
+    assert_eq!(migrations.len(), 3);
+    assert_eq!(migrations[2]["version"], 20230301000000i64);
+    assert_eq!(migrations[2]["description"], "test3");
+    assert!(migrations[2]["execution_time_ns"].is_u64());
+    let total: u64 = migrations
+        .iter()
+        .map(|m| m["execution_time_ns"].as_u64().unwrap())
+        .sum();
+    assert_eq!(output["total_ns"].as_u64().unwrap(), total);
+}
+
+#[test]
+fn info_diff_against() {
+    let db = TestDatabase::new("migrate_info_diff_against", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    let info = |manifest: &Path, json: bool| {
+        let mut args = vec![
+            "migrate",
+            "info",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            "tests/does_not_exist",
+            "--diff-against",
+            manifest.to_str().unwrap(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        if json {
+            args.push("--json".into());
+        }
+        cargo_sqlx().args(args).assert()
+    };
+
+    let manifest = std::env::temp_dir().join("sqlx-cli-info-diff-against.json");
+    let assert = cargo_sqlx()
+        .args([
+            "migrate",
+            "fingerprint",
+            "--database-url",
+            &db.connection_string(),
+            "--json",
+        ])
+        .assert()
+        .success();
+    std::fs::write(&manifest, &assert.get_output().stdout).unwrap();
+
+    let assert = info(&manifest, false).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("The applied migrations match"));
+
+    // Another environment: one checksum differs, one version is missing and one is extra.
+    let mut expected: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    let migrations = expected["migrations"].as_array_mut().unwrap();
+    migrations[1]["checksum"] = "00".into();
+    migrations.remove(0);
+    migrations.push(serde_json::json!({ "version": 20230301000000i64, "checksum": "00" }));
+    std::fs::write(&manifest, expected.to_string()).unwrap();
+
+    let assert = info(&manifest, false).failure().code(1);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("differs: 20230201000000"));
+    assert!(stdout.contains("extra: 20230101000000"));
+    assert!(stdout.contains("missing: 20230301000000"));
+
+    let assert = info(&manifest, true).failure().code(1);
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(output["differ"][0]["version"], 20230201000000i64);
+    assert_eq!(output["differ"][0]["manifest_checksum"], "00");
+    assert_eq!(output["extra"], serde_json::json!([20230101000000i64]));
+    assert_eq!(output["missing"], serde_json::json!([20230301000000i64]));
+
+    std::fs::remove_file(&manifest).unwrap();
+}
+
+#[tokio::test]
+async fn generate_from_database() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let source = copy_fixtures(
+        "sqlx-cli-generate-from-database",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+            "20230201000000_test2.up.sql",
+            "20230201000000_test2.down.sql",
+        ],
+    );
+
+    let db = TestDatabase::new("migrate_generate_from_database", source.to_str().unwrap());
+    let generate = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "generate",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                source.to_str().unwrap(),
+                "sync schema",
+            ])
+            .args(args)
+            .assert()
+    };
+
+    db.run_migration(false, None, false).success();
+
+    // The schema of a database is the only one to generate a migration from so far.
+    let assert = generate(&[]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("pass --from-database"));
+
+    let assert = generate(&["--from-database"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("no migration generated"));
+
+    // Change the schema behind the migrations' back.
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute(
+        "ALTER TABLE test1 ADD COLUMN name TEXT; \
+         CREATE TABLE extra ( id INTEGER NOT NULL ); \
+         DROP TABLE test2;",
+    )
+    .await
+    .unwrap();
+    conn.close().await.unwrap();
+
+    generate(&["--from-database"]).success();
+
+    let generated: Vec<_> = std::fs::read_dir(&source)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_str().unwrap().contains("_sync_schema."))
+        .collect();
+    assert_eq!(generated.len(), 2, "{generated:?}");
+
+    let up = generated
+        .iter()
+        .find(|path| path.to_str().unwrap().ends_with(".up.sql"))
+        .unwrap();
+    let sql = std::fs::read_to_string(up).unwrap();
+    assert!(sql.starts_with("-- DRAFT"));
+    assert!(sql.contains("CREATE TABLE \"extra\" (\n    \"id\" INTEGER NOT NULL\n);"));
+    assert!(sql.contains("ALTER TABLE \"test1\" ADD COLUMN \"name\" TEXT;"));
+    assert!(sql.contains("DROP TABLE \"test2\";"));
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn test_reversibility_schema_equal() {
+    let source = std::env::temp_dir().join("sqlx-cli-test-reversibility");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+
+    for (name, sql) in [
+        (
+            "0001_users.up.sql",
+            "CREATE TABLE users ( id INTEGER NOT NULL );",
+        ),
+        ("0001_users.down.sql", "DROP TABLE users;"),
+        (
+            "0002_audit.up.sql",
+            "CREATE TABLE IF NOT EXISTS audit ( id INTEGER );",
+        ),
+        // Does not undo the up migration, which can still be applied again.
+        ("0002_audit.down.sql", "SELECT 1;"),
+        (
+            "0003_posts.sql",
+            "CREATE TABLE posts ( id INTEGER NOT NULL );",
+        ),
+    ] {
+        std::fs::write(source.join(name), sql).unwrap();
+    }
+
+    let db = TestDatabase::new("migrate_test_reversibility", source.to_str().unwrap());
+    let test_reversibility = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "test-reversibility",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                source.to_str().unwrap(),
+            ])
+            .args(args)
+            .assert()
+    };
+
+    // Reverting succeeds.
+    let assert = test_reversibility(&[]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Skipped 3/migrate posts"));
+
+    let assert = test_reversibility(&["--schema-equal"]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Reverted 1/revert users"));
+    assert!(stdout.contains("Mismatch 2/revert audit"));
+    assert!(stdout.contains("table `audit` was left behind"));
+
+    // Only the scratch database was migrated.
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn rerun_modified_migration() {
+    let source = copy_fixtures(
+        "sqlx-cli-rerun",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+        ],
+    );
+
+    let db = TestDatabase::new("migrate_rerun", source.to_str().unwrap());
+    let rerun = |args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "rerun",
+                        "--database-url",
+                        &db.connection_string(),
+                        "--source",
+                        source.to_str().unwrap(),
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+
+    // Not applied yet.
+    rerun(&["--version", "20230101000000", "-y"]).failure();
+
+    db.run_migration(false, None, false).success();
+
+    // Modify the applied migration.
+    std::fs::write(
+        source.join("20230101000000_test1.up.sql"),
+        "CREATE TABLE test1(x INTEGER PRIMARY KEY, y TEXT);\n",
+    )
+    .unwrap();
+    db.run_migration(false, None, false).failure();
+
+    let assert = rerun(&["--version", "20230101000000", "--dry-run"]).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout).contains("Can apply"));
+    db.run_migration(false, None, false).failure();
+
+    rerun(&["--version", "20230101000000", "-y"]).success();
+    assert_eq!(db.applied_migrations().await, vec![20230101000000]);
+
+    // The recorded checksum now matches the modified migration.
+    db.run_migration(false, None, false).success();
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_fail_on_empty_source() {
+    let source = std::env::temp_dir().join("sqlx-cli-empty-source");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+
+    let db = TestDatabase::new("migrate_fail_on_empty_source", source.to_str().unwrap());
+
+    // Legitimately empty by default.
+    db.run_migration(false, None, false).success();
+
+    let assert = db.run_migration_with(&["--fail-on-empty-source"]).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout).contains("no migrations found"));
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_maintenance_flag() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let source = std::env::temp_dir().join("sqlx-cli-maintenance-flag");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+
+    // Records the flag as seen by the migrations.
+    std::fs::write(
+        source.join("20230101000000_seen.sql"),
+        "CREATE TABLE seen AS SELECT maintenance FROM app_state;",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_maintenance_flag", source.to_str().unwrap());
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute(
+        "CREATE TABLE app_state (maintenance BOOLEAN NOT NULL); \
+         INSERT INTO app_state VALUES (FALSE)",
+    )
+    .await
+    .unwrap();
+
+    async fn maintenance(conn: &mut SqliteConnection, table: &str) -> bool {
+        sqlx::query_scalar(&format!("SELECT maintenance FROM {table}"))
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    db.run_migration_with(&["--maintenance-flag", "app_state.maintenance"])
+        .success();
+    assert!(maintenance(&mut conn, "seen").await);
+    assert!(!maintenance(&mut conn, "app_state").await);
+
+    // Cleared even if a migration fails.
+    std::fs::write(source.join("20230201000000_broken.sql"), "NOT SQL;").unwrap();
+    db.run_migration_with(&["--maintenance-flag", "app_state.maintenance"])
+        .failure();
+    assert!(!maintenance(&mut conn, "app_state").await);
+
+    conn.close().await.unwrap();
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_emit_undo() {
+    let source = copy_fixtures(
+        "sqlx-cli-emit-undo",
+        &[
+            "20230101000000_test1.up.sql",
+            "20230101000000_test1.down.sql",
+            "20230201000000_test2.up.sql",
+            "20230201000000_test2.down.sql",
+        ],
+    );
+    std::fs::write(
+        source.join("20230301000000_extra.sql"),
+        "CREATE TABLE extra (id INTEGER);",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_emit_undo", source.to_str().unwrap());
+    db.run_migration(false, Some(20230101000000), false)
+        .success();
+
+    let script_path = std::env::temp_dir().join("sqlx-cli-emit-undo.sql");
+    let assert = db
+        .run_migration_with(&["--dry-run", "--emit-undo", script_path.to_str().unwrap()])
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("20230301000000 (extra) has no down migration"));
+
+    // Nothing was applied.
+    assert_eq!(db.applied_migrations().await, vec![20230101000000]);
+
+    let script = std::fs::read_to_string(&script_path).unwrap();
+    let irreversible = script.find("IRREVERSIBLE: 20230301000000 extra").unwrap();
+    let test2 = script
+        .find("DROP TABLE test2;\nDELETE FROM _sqlx_migrations WHERE version = 20230201000000;")
+        .unwrap();
+    assert!(irreversible < test2);
+    assert!(!script.contains("test1"));
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+    std::fs::remove_file(&script_path).unwrap();
+}
+
+#[tokio::test]
+async fn color_mode() {
+    let db = TestDatabase::new("migrate_color_mode", "migrations_reversible");
+    db.run_migration(false, Some(20230101000000), false)
+        .success();
+
+    let info = |args: &[&str], no_color: Option<&str>| {
+        let mut cmd = cargo_sqlx();
+        cmd.args(
+            [
+                vec![
+                    "migrate",
+                    "info",
+                    "--database-url",
+                    &db.connection_string(),
+                    "--source",
+                    "tests/migrations_reversible",
+                ],
+                args.to_vec(),
+            ]
+            .concat(),
+        );
+        match no_color {
+            Some(value) => cmd.env("NO_COLOR", value),
+            None => cmd.env_remove("NO_COLOR"),
+        };
+        let assert = cmd.assert().success();
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    let ansi = "\x1b[";
+    assert!(info(&["--color", "always"], None).contains(ansi));
+    assert!(!info(&["--color=never"], None).contains(ansi));
+    // `--color always` takes precedence over `NO_COLOR`
+    assert!(info(&["--color", "always"], Some("1")).contains(ansi));
+    assert!(!info(&[], Some("1")).contains(ansi));
+}
+
+#[tokio::test]
+async fn info_dirty_migration() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_info_dirty_migration", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET success = FALSE WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let info = |args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec![
+                        "migrate",
+                        "info",
+                        "--database-url",
+                        &db.connection_string(),
+                        "--source",
+                        "tests/migrations_reversible",
+                    ],
+                    args.to_vec(),
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+
+    let assert = info(&[]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("the database is dirty: migration 20230201000000"));
+    assert!(stdout.contains("description: test2"));
+    assert!(stdout.contains("20230201000000_test2.up.sql"));
+
+    // The full listing is printed before exiting with the dirty status.
+    let assert = info(&["--exit-code"]).failure().code(3);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("the database is dirty"));
+    assert!(stdout.contains("20230501000000/pending"));
+
+    // Acknowledged: only the pending migrations count.
+    info(&["--exit-code", "--allow-dirty"]).failure().code(1);
+
+    let assert = info(&["--json"]).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("the database is dirty"));
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(output.as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn run_dirty_record() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_run_dirty_record", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    // A failed revert flags the applied row, which keeps its execution time.
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET success = FALSE WHERE version = 20230201000000")
+        .await
+        .unwrap();
+
+    let assert = db.run_migration(false, None, false).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("migration 20230201000000 (test2) is partially reverted"));
+
+    // Re-applying the up migration would not resolve it.
+    db.run_migration_with(&["--auto-recover"]).failure();
+
+    // A failed application is recorded without an execution time.
+    conn.execute("UPDATE _sqlx_migrations SET execution_time = -1 WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let assert = db.revert_migration_with(&[]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("migration 20230201000000 (test2) is partially applied"));
+}
+
+#[tokio::test]
+async fn convert_versioning() {
+    let source = std::env::temp_dir().join("sqlx-cli-convert");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+
+    for (name, sql) in [
+        ("0001_a.sql", "CREATE TABLE a (id INTEGER);"),
+        ("0002_b.sql", "CREATE TABLE b (id INTEGER);"),
+        ("0003_c.sql", "CREATE TABLE c (id INTEGER);"),
+    ] {
+        std::fs::write(source.join(name), sql).unwrap();
+    }
+
+    let db = TestDatabase::new("migrate_convert", source.to_str().unwrap());
+    db.run_migration(false, Some(2), false).success();
+
+    let convert = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "convert",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                source.to_str().unwrap(),
+            ])
+            .args(args)
+            .env("SQLX_MIGRATE_FAKE_NOW", "2024-01-01T00:00:00Z")
+            .assert()
+            .success()
+    };
+
+    // Dry-run
+    let assert = convert(&["--to", "timestamp", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert_eq!(stdout.matches("Can rename").count(), 3);
+    assert!(stdout.contains("Can update applied version 2 to 20231231235958"));
+    assert!(source.join("0001_a.sql").is_file());
+    assert_eq!(db.applied_migrations().await, vec![1, 2]);
+
+    convert(&["--to", "timestamp", "-y"]);
+    for name in [
+        "20231231235957_a.sql",
+        "20231231235958_b.sql",
+        "20231231235959_c.sql",
+    ] {
+        assert!(source.join(name).is_file());
+    }
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20231231235957, 20231231235958]
+    );
+
+    // Nothing to do.
+    let assert = convert(&["--to", "timestamp", "-y"]);
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("Migrations already use timestamp versioning"));
+
+    // History is consistent: only the last migration is pending.
+    db.run_migration(false, None, false).success();
+    assert_eq!(db.applied_migrations().await.len(), 3);
+
+    convert(&["--to", "sequential", "-y"]);
+    assert!(source.join("0003_c.sql").is_file());
+    assert_eq!(db.applied_migrations().await, vec![1, 2, 3]);
+
+    // Another environment, converted with the already renamed files.
+    let other = TestDatabase::new("migrate_convert_other", source.to_str().unwrap());
+    other.run_migration(false, None, false).success();
+    convert(&["--to", "timestamp", "-y"]);
+    cargo_sqlx()
+        .args([
+            "migrate",
+            "convert",
+            "--database-url",
+            &other.connection_string(),
+            "--source",
+            source.to_str().unwrap(),
+            "--to",
+            "timestamp",
+            "-y",
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        other.applied_migrations().await,
+        db.applied_migrations().await
+    );
+
+    drop(other);
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn fix_checksums() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_fix_checksums", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let fix_checksums = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "fix-checksums",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+            ])
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    let assert = fix_checksums(&["--dry-run"]);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("20230201000000/Can fix test2"));
+    assert!(!stdout.contains("test1"));
+
+    // Still mismatched after the dry run.
+    let assert = db.run_migration(false, None, false).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("was previously applied but has been modified"));
+
+    let assert = fix_checksums(&["-y"]);
+    assert!(
+        String::from_utf8_lossy(&assert.get_output().stdout).contains("20230201000000/Fixed test2")
+    );
+
+    db.run_migration(false, None, false).success();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    let assert = fix_checksums(&["-y"]);
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("All checksums match the local migrations"));
+}
+
+#[tokio::test]
+async fn find_tables() {
+    let db = TestDatabase::new("migrate_find_tables", "migrations_reversible");
+
+    let find_tables = || {
+        let assert = cargo_sqlx()
+            .args([
+                "migrate",
+                "find-tables",
+                "--database-url",
+                &db.connection_string(),
+            ])
+            .assert()
+            .success();
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    assert!(find_tables().contains("No migrations tables found"));
+
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+    let stdout = find_tables();
+    assert!(stdout.contains("_sqlx_migrations 2 migrations (migration table)"));
+    assert!(!stdout.contains("warning"));
+
+    // Some migrations run against another table by mistake.
+    db.run_migration_with(&[
+        "--migration-table",
+        "other_migrations",
+        "--fake",
+        "--version",
+        "20230101000000",
+        "-y",
+    ])
+    .success();
+    let stdout = find_tables();
+    assert!(stdout.contains("_sqlx_migrations 2 migrations (migration table)"));
+    assert!(stdout.contains("other_migrations 1 migrations"));
+    assert!(
+        stdout.contains("migrations are recorded in 2 tables: _sqlx_migrations, other_migrations")
+    );
+}
+
+#[tokio::test]
+async fn run_summary() {
+    let db = TestDatabase::new("migrate_run_summary", "migrations_reversible");
+
+    let assert = db.run_migration(false, None, true).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("5 migrations can be applied"));
+
+    let assert = db
+        .run_migration(false, Some(20230301000000), false)
+        .success();
+    assert!(
+        String::from_utf8_lossy(&assert.get_output().stdout).contains("Applied 3 migrations in ")
+    );
+
+    // Nothing to apply, nothing to summarize.
+    let assert = db
+        .run_migration(false, Some(20230301000000), false)
+        .success();
+    assert!(!String::from_utf8_lossy(&assert.get_output().stdout).contains("Applied"));
+}
+
+#[tokio::test]
+async fn run_audit_runs() {
+    use sqlx::{Connection, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_run_audit_runs", "migrations_reversible");
+
+    db.run_migration_with(&[
+        "--audit-runs",
+        "--release-id",
+        "v1.2",
+        "--target-version",
+        "20230201000000",
+    ])
+    .success();
+    // Not recorded.
+    db.run_migration_with(&["--audit-runs", "--dry-run"])
+        .success();
+    db.run_migration_with(&["--audit-runs", "--target-version", "1"])
+        .failure();
+    db.run_migration_with(&["--audit-runs", "--release-id", "v1.3'"])
+        .failure();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    let runs: Vec<(i64, bool, Option<String>, Option<i64>)> = sqlx::query_as(
+        "SELECT applied, success, release_id, head_version FROM _sqlx_migrations_runs",
+    )
+    .fetch_all(&mut conn)
+    .await
+    .unwrap();
+    assert_eq!(
+        runs,
+        vec![
+            (2, true, Some("v1.2".to_string()), Some(20230201000000)),
+            (0, false, None, Some(20230201000000)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn run_dry_run_show_sql() {
+    let db = TestDatabase::new("migrate_run_show_sql", "migrations_reversible");
+
+    let assert = db
+        .run_migration_with(&[
+            "--dry-run",
+            "--show-sql",
+            "--target-version",
+            "20230201000000",
+        ])
+        .success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("-- 20230101000000_test1 ---"));
+    assert!(stdout.contains("CREATE TABLE test1(x INTEGER PRIMARY KEY);"));
+    assert!(stdout.contains("CREATE TABLE test2(x INTEGER PRIMARY KEY);"));
+    // Skipped migrations are not shown.
+    assert!(!stdout.contains("CREATE TABLE test3"));
+
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    // Only for dry runs.
+    db.run_migration_with(&["--show-sql"]).failure();
+}
+
+#[tokio::test]
+async fn lock_timeout() {
+    let db = TestDatabase::new("migrate_lock_timeout", "migrations_reversible");
+
+    // The per-migration lock timeout is only supported on PostgreSQL.
+    let assert = db.run_migration_with(&["--lock-timeout", "5"]).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("--lock-timeout is only supported on PostgreSQL"));
+    assert_eq!(db.applied_migrations().await.len(), 0);
+
+    // SQLite has no migration lock to wait for; the timeout is accepted and has no effect.
+    db.run_migration_with(&["--migration-lock-timeout", "5"])
+        .success();
+    assert_eq!(db.applied_migrations().await.len(), 5);
+
+    cargo_sqlx()
+        .args([
+            "migrate",
+            "revert",
+            "--database-url",
+            &db.connection_string(),
+            "--source",
+            "tests/migrations_reversible",
+            "--migration-lock-timeout",
+            "5",
+        ])
+        .assert()
+        .success();
+    assert_eq!(db.applied_migrations().await.len(), 4);
+}
+
+#[tokio::test]
+async fn run_progress() {
+    let source = std::env::temp_dir().join("sqlx-cli-progress");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("20230101000000_seed.sql"),
+        "CREATE TABLE seed (id INTEGER);\n\
+         INSERT INTO seed VALUES (1);\n\
+         -- a comment only; not a statement\n\
+         INSERT INTO seed VALUES ('a;b');\n",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_progress", source.to_str().unwrap());
+    let assert = db.run_migration_with(&["--progress"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    for statement in 1..=3 {
+        assert!(stdout.contains(&format!("20230101000000 [statement {statement}/3]")));
+    }
+    assert!(!stdout.contains("[statement 4/"));
+    assert_eq!(db.applied_migrations().await, vec![20230101000000]);
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_hooks() {
+    use sqlx::{Connection, SqliteConnection};
+
+    let source = copy_fixtures(
+        "sqlx-cli-hooks",
+        &["20230101000000_test1.up.sql", "20230201000000_test2.up.sql"],
+    );
+    std::fs::write(
+        source.join("before_each.sql"),
+        "CREATE TABLE IF NOT EXISTS hook_log (event TEXT);\n\
+         INSERT INTO hook_log VALUES ('before');\n",
+    )
+    .unwrap();
+    std::fs::write(
+        source.join("after_each.sql"),
+        "INSERT INTO hook_log VALUES ('after');\n",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_hooks", source.to_str().unwrap());
+
+    // Skipped in a dry run.
+    db.run_migration(false, None, true).success();
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    let (hook_tables,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'hook_log'",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .unwrap();
+    assert_eq!(hook_tables, 0);
+
+    db.run_migration(false, None, false).success();
+    let events: Vec<String> = sqlx::query_scalar("SELECT event FROM hook_log ORDER BY rowid")
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(events, ["before", "after", "before", "after"]);
+
+    // The hooks are not recorded as migrations.
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000]
+    );
+
+    conn.close().await.unwrap();
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_checkpoint() {
+    let source = std::env::temp_dir().join("sqlx-cli-checkpoint");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("20230101000000_backfill.sql"),
+        "CREATE TABLE users (id INTEGER, email TEXT);\n\
+         -- sqlx:checkpoint created\n\
+         INSERT INTO users VALUES (1, NULL);\n\
+         -- sqlx:checkpoint backfilled\n\
+         INSERT INTO missing VALUES (1);\n",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_checkpoint", source.to_str().unwrap());
+    let assert = db.run_migration(false, None, false).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("migration 20230101000000 failed after checkpoint \"backfilled\""));
+    assert!(stdout.contains("no such table: missing"));
+    assert_eq!(db.applied_migrations().await, vec![] as Vec<i64>);
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn run_manual_followup() {
+    let source = std::env::temp_dir().join("sqlx-cli-manual-followup");
+    let _ = std::fs::remove_dir_all(&source);
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("20230101000000_plain.sql"),
+        "CREATE TABLE plain (id INTEGER);\n",
+    )
+    .unwrap();
+    std::fs::write(
+        source.join("20230201000000_rebuild.sql"),
+        "-- sqlx:manual-followup rebuild the reports view by hand\n\
+         CREATE TABLE reports (id INTEGER);\n",
+    )
+    .unwrap();
+
+    let db = TestDatabase::new("migrate_manual_followup", source.to_str().unwrap());
+
+    let sqlx = |args: &[&str]| {
+        cargo_sqlx()
+            .args(
+                [
+                    vec!["migrate"],
+                    args.to_vec(),
+                    vec!["--database-url", &db.connection_string()],
+                ]
+                .concat(),
+            )
+            .assert()
+    };
+    let info = || sqlx(&["info", "--source", source.to_str().unwrap()]).success();
+
+    // Not applied, nothing to follow up.
+    let assert = db.run_migration_with(&["--dry-run"]).success();
+    assert!(
+        !String::from_utf8_lossy(&assert.get_output().stdout).contains("MANUAL FOLLOW-UP REQUIRED")
+    );
+
+    let assert = db.run_migration_with(&[]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("MANUAL FOLLOW-UP REQUIRED"));
+    assert!(stdout.contains("20230201000000/rebuild: rebuild the reports view by hand"));
+    assert!(!stdout.contains("20230101000000/plain:"));
+
+    let assert = info();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("1 applied migrations have an outstanding manual follow-up"));
+    assert!(stdout.contains("20230201000000/rebuild: rebuild the reports view by hand"));
+
+    let assert = sqlx(&["ack", "--version", "20230101000000"]).failure();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("migration 20230101000000 has no outstanding manual follow-up"));
+
+    let assert = sqlx(&["ack", "--version", "20230201000000"]).success();
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("Acknowledged the manual follow-up of migration 20230201000000"));
+
+    let assert = info();
+    assert!(!String::from_utf8_lossy(&assert.get_output().stdout).contains("manual follow-up"));
+
+    // Only once.
+    sqlx(&["ack", "--version", "20230201000000"]).failure();
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[tokio::test]
+async fn info_exit_code() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_info_exit_code", "migrations_reversible");
+
+    let status = || {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "status",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+                "--exit-code",
+            ])
+            .assert()
+    };
+
+    let assert = status().code(1);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("20230101000000/pending test1"));
+    assert!(!stdout.contains("error:"));
+
+    db.run_migration(false, None, false).success();
+    status().success();
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let assert = status().code(2);
+    assert!(String::from_utf8_lossy(&assert.get_output().stdout)
+        .contains("installed (different checksum)"));
+}
+
+#[tokio::test]
+async fn run_checksum_allowlist() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let source = copy_fixtures(
+        "sqlx-cli-checksum-allowlist",
+        &["20230101000000_test1.up.sql", "20230201000000_test2.up.sql"],
+    );
+
+    let db = TestDatabase::new("migrate_checksum_allowlist", source.to_str().unwrap());
+    db.run_migration(false, None, false).success();
+
+    // As if both migrations were edited after being applied.
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00ab'")
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    let info = || {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "info",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                source.to_str().unwrap(),
+                "--exit-code",
+            ])
+            .assert()
+    };
+
+    let stdout = |assert: assert_cmd::assert::Assert| {
+        String::from_utf8_lossy(&assert.get_output().stdout).into_owned()
+    };
+
+    // Only the first edit is approved.
+    std::fs::write(
+        source.join("checksum-allowlist.toml"),
+        "20230101000000 = \"00AB\"\n20230201000000 = [\"ffff\"]\n",
+    )
+    .unwrap();
+    let output = stdout(db.run_migration(false, None, false).failure());
+    assert!(
+        output.contains("migration 20230201000000 was previously applied but has been modified")
+    );
+    let output = stdout(info().code(2));
+    assert!(output.contains("installed (allowlisted checksum)"));
+    assert!(output.contains("installed (different checksum)"));
+
+    std::fs::write(
+        source.join("checksum-allowlist.toml"),
+        "20230101000000 = \"00ab\"\n20230201000000 = [\"ffff\", \"00ab\"]\n",
+    )
+    .unwrap();
+    db.run_migration(false, None, false).success();
+    assert!(!stdout(info().success()).contains("installed (different checksum)"));
+
+    std::fs::write(
+        source.join("checksum-allowlist.toml"),
+        "latest = \"00ab\"\n",
+    )
+    .unwrap();
+    let output = stdout(db.run_migration(false, None, false).failure());
+    assert!(output.contains("\"latest\" is not a migration version"));
+
+    drop(db);
+    std::fs::remove_dir_all(&source).unwrap();
+}
+
+#[test]
+fn run_savepoint_per_migration_requires_single_transaction() {
+    let db = TestDatabase::new("migrate_savepoint_per_migration", "migrations_reversible");
+
+    let assert = db
+        .run_migration_with(&["--savepoint-per-migration"])
+        .failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("--single-transaction"));
+
+    // Like --single-transaction itself, only supported on PostgreSQL.
+    let assert = db
+        .run_migration_with(&["--single-transaction", "--savepoint-per-migration"])
+        .failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("--single-transaction requires transactional DDL"));
+}
+
+#[tokio::test]
+async fn run_multiple_sources() {
+    let root = std::env::temp_dir().join("sqlx-cli-multiple-sources");
+    let _ = std::fs::remove_dir_all(&root);
+    let (users, posts) = (root.join("users"), root.join("posts"));
+    std::fs::create_dir_all(&users).unwrap();
+    std::fs::create_dir_all(&posts).unwrap();
+
+    // interleaved versions, applied in one global order
+    for (dir, name) in [
+        (&users, "20230101000000_test1.up.sql"),
+        (&posts, "20230201000000_test2.up.sql"),
+        (&users, "20230301000000_test3.up.sql"),
+    ] {
+        std::fs::copy(
+            Path::new("tests/migrations_reversible").join(name),
+            dir.join(name),
+        )
+        .unwrap();
+    }
+
+    let source = std::env::join_paths([&users, &posts]).unwrap();
+    let source = source.to_str().unwrap();
+
+    let db = TestDatabase::new("migrate_multiple_sources", source);
+    db.run_migration(false, None, false).success();
+    assert_eq!(
+        db.applied_migrations().await,
+        vec![20230101000000, 20230201000000, 20230301000000]
+    );
+
+    let add = |args: &[&str]| {
+        cargo_sqlx()
+            .args(["migrate", "add", "--source", source])
+            .args(args)
+            .assert()
+    };
+
+    // the directory to create the migration in must be chosen
+    let assert = add(&["create comments"]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("pass --dir"));
+
+    add(&["--dir", posts.to_str().unwrap(), "create comments"]).success();
+    let created: Vec<_> = std::fs::read_dir(&posts)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.contains("_create_comments."))
+        .collect();
+    // reversible, like the existing migrations
+    assert_eq!(created.len(), 2);
+
+    // a version may only be used in one directory
+    std::fs::copy(
+        users.join("20230101000000_test1.up.sql"),
+        posts.join("20230101000000_duplicate.up.sql"),
+    )
+    .unwrap();
+    let assert = db.run_migration(false, None, false).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("migration version 20230101000000 is used in more than one directory"));
+    assert!(stdout.contains("20230101000000_test1.up.sql"));
+    assert!(stdout.contains("20230101000000_duplicate.up.sql"));
+
+    drop(db);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn verify() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_verify", "migrations_reversible");
+    db.run_migration(false, Some(20230301000000), false)
+        .success();
+
+    let verify = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "verify",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+            ])
+            .args(args)
+            .assert()
+    };
+
+    let assert = verify(&[]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Verified 3 applied migrations"));
+
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20230201000000")
+        .await
+        .unwrap();
+    conn.execute(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (20230250000000, 'gone', TRUE, x'00', 0)",
+    )
+    .await
+    .unwrap();
+    conn.close().await.unwrap();
+
+    let assert = verify(&[]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("mismatch: 20230201000000/test2"));
+    assert!(stdout.contains("missing: 20230250000000"));
+    assert!(
+        stdout.contains("2 applied migrations failed verification: 20230201000000, 20230250000000")
+    );
+
+    let assert = verify(&["--ignore-missing"]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("1 applied migrations failed verification: 20230201000000"));
+}
+
+#[tokio::test]
+async fn cleanup_dirty() {
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    let db = TestDatabase::new("migrate_cleanup_dirty", "migrations_reversible");
+    db.run_migration(false, Some(20230201000000), false)
+        .success();
+
+    // As if the second migration failed after creating its table.
+    let mut conn = SqliteConnection::connect(&db.connection_string())
+        .await
+        .unwrap();
+    conn.execute(
+        "UPDATE _sqlx_migrations SET success = FALSE, execution_time = -1 \
+         WHERE version = 20230201000000",
+    )
+    .await
+    .unwrap();
+
+    let cleanup_dirty = |args: &[&str]| {
+        cargo_sqlx()
+            .args([
+                "migrate",
+                "cleanup-dirty",
+                "--database-url",
+                &db.connection_string(),
+                "--source",
+                "tests/migrations_reversible",
+            ])
+            .args(args)
+            .assert()
+    };
+
+    let assert = cleanup_dirty(&["--version", "20230101000000", "--yes"]).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(
+        stdout.contains("the database is dirty on migration 20230201000000, not 20230101000000")
+    );
+
+    let assert = cleanup_dirty(&["--version", "20230201000000", "--dry-run"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("DROP TABLE IF EXISTS test2;"));
+    assert!(!stdout.contains("Cleaned up"));
+
+    let assert = cleanup_dirty(&["--version", "20230201000000", "--yes"]).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Cleaned up 20230201000000/migrate test2"));
+
+    let tables: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name = 'test2'")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(tables, 0);
+    conn.close().await.unwrap();
+
+    // Applied again from a clean slate.
+    db.run_migration(false, None, false).success();
+}
@@ -71,6 +71,7 @@ impl AnyDriver {
                 database_exists: DebugFn(DB::database_exists),
                 drop_database: DebugFn(DB::drop_database),
                 force_drop_database: DebugFn(DB::force_drop_database),
+                migrations_table_ddl: DebugFn(DB::migrations_table_ddl),
             }),
             ..Self::without_migrate::<DB>()
         }
@@ -96,6 +97,7 @@ pub struct AnyMigrateDatabase {
     database_exists: DebugFn<fn(&str) -> BoxFuture<'_, crate::Result<bool>>>,
     drop_database: DebugFn<fn(&str) -> BoxFuture<'_, crate::Result<()>>>,
     force_drop_database: DebugFn<fn(&str) -> BoxFuture<'_, crate::Result<()>>>,
+    migrations_table_ddl: DebugFn<fn(&str, &str, bool) -> crate::Result<Vec<String>>>,
 }
 
 impl AnyMigrateDatabase {
@@ -114,6 +116,15 @@ impl AnyMigrateDatabase {
     pub fn force_drop_database<'a>(&self, url: &'a str) -> BoxFuture<'a, crate::Result<()>> {
         (self.force_drop_database)(url)
     }
+
+    pub fn migrations_table_ddl(
+        &self,
+        url: &str,
+        migration_table: &str,
+        installed_on_index: bool,
+    ) -> crate::Result<Vec<String>> {
+        (self.migrations_table_ddl)(url, migration_table, installed_on_index)
+    }
 }
 
 /// Install the list of drivers for [`AnyConnection`] to use.
@@ -1,7 +1,9 @@
 use crate::any::driver;
 use crate::any::{Any, AnyConnection};
 use crate::error::Error;
-use crate::migrate::{AppliedMigration, Migrate, MigrateDatabase, MigrateError, Migration};
+use crate::migrate::{
+    AppliedMigration, DirtyRecord, Migrate, MigrateDatabase, MigrateError, Migration,
+};
 use futures_core::future::BoxFuture;
 use std::time::Duration;
 
@@ -41,6 +43,16 @@ impl MigrateDatabase for Any {
                 .await
         })
     }
+
+    fn migrations_table_ddl(
+        url: &str,
+        migration_table: &str,
+        installed_on_index: bool,
+    ) -> Result<Vec<String>, Error> {
+        driver::from_url_str(url)?
+            .get_migrate_database()?
+            .migrations_table_ddl(url, migration_table, installed_on_index)
+    }
 }
 
 impl Migrate for AnyConnection {
@@ -48,10 +60,72 @@ impl Migrate for AnyConnection {
         Box::pin(async { self.get_migrate()?.ensure_migrations_table(migration_table).await })
     }
 
+    fn ensure_installed_on_index(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async {
+            self.get_migrate()?
+                .ensure_installed_on_index(migration_table)
+                .await
+        })
+    }
+
+    fn migrations_table_exists(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<bool, MigrateError>> {
+        Box::pin(async {
+            self.get_migrate()?
+                .migrations_table_exists(migration_table)
+                .await
+        })
+    }
+
     fn dirty_version(&mut self, migration_table: String) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
         Box::pin(async { self.get_migrate()?.dirty_version(migration_table).await })
     }
 
+    fn dirty_record(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<DirtyRecord>, MigrateError>> {
+        Box::pin(async { self.get_migrate()?.dirty_record(migration_table).await })
+    }
+
+    fn clear_dirty_version(
+        &mut self,
+        version: i64,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.get_migrate()?
+                .clear_dirty_version(version, migration_table)
+                .await
+        })
+    }
+
+    fn update_checksum(
+        &mut self,
+        version: i64,
+        checksum: Vec<u8>,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.get_migrate()?
+                .update_checksum(version, checksum, migration_table)
+                .await
+        })
+    }
+
+    fn mark_dirty<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async { self.get_migrate()?.mark_dirty(migration, migration_table).await })
+    }
+
     fn list_applied_migrations(
         &mut self,
         migration_table: String,
@@ -59,10 +133,21 @@ impl Migrate for AnyConnection {
         Box::pin(async { self.get_migrate()?.list_applied_migrations(migration_table).await })
     }
 
+    fn latest_applied_migration(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<AppliedMigration>, MigrateError>> {
+        Box::pin(async { self.get_migrate()?.latest_applied_migration(migration_table).await })
+    }
+
     fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async { self.get_migrate()?.lock().await })
     }
 
+    fn lock_with_timeout(&mut self, timeout: Duration) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move { self.get_migrate()?.lock_with_timeout(timeout).await })
+    }
+
     fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async { self.get_migrate()?.unlock().await })
     }
@@ -75,6 +160,39 @@ impl Migrate for AnyConnection {
         Box::pin(async { self.get_migrate()?.apply(migration, migration_table).await })
     }
 
+    fn apply_server_timed<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async {
+            self.get_migrate()?
+                .apply_server_timed(migration, migration_table)
+                .await
+        })
+    }
+
+    fn apply_with_progress<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+        progress: &'m mut (dyn FnMut(usize, usize) + Send),
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(async move {
+            self.get_migrate()?
+                .apply_with_progress(migration, migration_table, progress)
+                .await
+        })
+    }
+
+    fn record_applied<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async { self.get_migrate()?.record_applied(migration, migration_table).await })
+    }
+
     fn revert<'e: 'm, 'm>(
         &'e mut self,
         migration: &'m Migration,
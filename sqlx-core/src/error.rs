@@ -122,6 +122,8 @@ impl Error {
     pub fn as_database_error(&self) -> Option<&(dyn DatabaseError + 'static)> {
         match self {
             Error::Database(err) => Some(&**err),
+            #[cfg(feature = "migrate")]
+            Error::Migrate(err) => err.as_database_error(),
             _ => None,
         }
     }
@@ -0,0 +1,374 @@
+/// Remove `--` and `/* */` comments from SQL.
+///
+/// String literals, quoted identifiers and dollar-quoted strings are left intact, so comment
+/// markers inside them are preserved. Blank lines and trailing whitespace left behind by removed
+/// comments are dropped too, so that only changes to the executable SQL affect the result.
+///
+/// Backslash escapes in string literals are not recognized; only doubled quotes are, as in
+/// standard SQL.
+pub(crate) fn strip_comments(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut rest = sql;
+
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("--") {
+            // keep the newline so the following line stays separate
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            out.push(' ');
+            block_comment_len(rest)
+        } else {
+            let len = match c {
+                '\'' | '"' | '`' => quoted_len(rest, c),
+                '$' => dollar_quoted_len(rest).unwrap_or(1),
+                _ => c.len_utf8(),
+            };
+            out.push_str(&rest[..len]);
+            len
+        };
+
+        rest = &rest[len..];
+    }
+
+    out.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split SQL into statements at semicolons outside of comments, string literals, quoted
+/// identifiers, dollar-quoted strings and `BEGIN ... END` blocks, such as the body of a SQLite
+/// trigger or a MySQL routine.
+///
+/// Each statement keeps its terminating semicolon. Statements consisting only of comments are
+/// dropped.
+///
+/// Returns `None` if the SQL cannot be split safely: a `BEGIN` or `CASE` is not closed by an
+/// `END`, or the SQL uses the `DELIMITER` command of the MySQL client.
+pub(crate) fn split_statements(sql: &str) -> Option<Vec<&str>> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    // nesting of `BEGIN ... END` blocks and `CASE ... END` expressions
+    let mut depth = 0;
+    // whether the current statement has a word yet, to tell `BEGIN;` from a block
+    let mut in_statement = false;
+
+    while let Some(c) = sql[i..].chars().next() {
+        let rest = &sql[i..];
+
+        i += if rest.starts_with("--") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            block_comment_len(rest)
+        } else {
+            match c {
+                '\'' | '"' | '`' => quoted_len(rest, c),
+                '$' => dollar_quoted_len(rest).unwrap_or(1),
+                ';' if depth == 0 => {
+                    statements.push(&sql[start..=i]);
+                    start = i + 1;
+                    in_statement = false;
+                    1
+                }
+                c if is_word_char(c) => {
+                    let word = leading_word(rest);
+                    let first = !in_statement;
+                    in_statement = true;
+
+                    if first && word.eq_ignore_ascii_case("DELIMITER") {
+                        return None;
+                    }
+
+                    if word.eq_ignore_ascii_case("BEGIN") {
+                        // a statement starting with `BEGIN` starts a transaction instead
+                        if depth > 0 || !first {
+                            depth += 1;
+                        }
+
+                        word.len()
+                    } else if word.eq_ignore_ascii_case("CASE") {
+                        depth += 1;
+                        word.len()
+                    } else if word.eq_ignore_ascii_case("END") && depth > 0 {
+                        let after = rest[word.len()..].trim_start();
+                        let next = leading_word(after);
+                        let through_next = rest.len() - after.len() + next.len();
+
+                        // MySQL's `END IF`, `END LOOP`, ... close blocks which are not counted,
+                        // and the `CASE` of `END CASE` must not open another block
+                        if ["IF", "LOOP", "WHILE", "REPEAT"]
+                            .iter()
+                            .any(|keyword| next.eq_ignore_ascii_case(keyword))
+                        {
+                            through_next
+                        } else if next.eq_ignore_ascii_case("CASE") {
+                            depth -= 1;
+                            through_next
+                        } else {
+                            depth -= 1;
+                            word.len()
+                        }
+                    } else {
+                        word.len()
+                    }
+                }
+                _ => c.len_utf8(),
+            }
+        };
+    }
+
+    if depth > 0 {
+        return None;
+    }
+
+    statements.push(&sql[start..]);
+
+    Some(
+        statements
+            .into_iter()
+            .map(str::trim)
+            .filter(|statement| !matches!(&*strip_comments(statement), "" | ";"))
+            .collect(),
+    )
+}
+
+/// The name of the last `-- sqlx:checkpoint <name>` comment among the comment lines leading a
+/// statement, as split by [`split_statements`].
+///
+/// Returns `Some("")` for a checkpoint without a name.
+pub(crate) fn checkpoint(statement: &str) -> Option<&str> {
+    statement
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .filter_map(|line| {
+            let checkpoint = line
+                .strip_prefix("--")?
+                .trim_start()
+                .strip_prefix("sqlx:checkpoint")?;
+
+            // e.g. `sqlx:checkpointed` is not a checkpoint
+            if !checkpoint.is_empty() && !checkpoint.starts_with(char::is_whitespace) {
+                return None;
+            }
+
+            Some(checkpoint.trim())
+        })
+        .last()
+}
+
+// Block comments nest in Postgres and standard SQL.
+fn block_comment_len(s: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < s.len() {
+        if s[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if s[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += s[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    // unterminated
+    s.len()
+}
+
+fn quoted_len(s: &str, quote: char) -> usize {
+    let mut i = 1;
+
+    while let Some(pos) = s[i..].find(quote) {
+        i += pos + 1;
+
+        // a doubled quote is an escaped quote, not the end of the literal
+        if !s[i..].starts_with(quote) {
+            return i;
+        }
+
+        i += 1;
+    }
+
+    // unterminated
+    s.len()
+}
+
+// `$tag$ ... $tag$` or `$$ ... $$`; returns `None` for e.g. a `$1` parameter.
+fn dollar_quoted_len(s: &str) -> Option<usize> {
+    let tag_end = s[1..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))? + 1;
+
+    if !s[tag_end..].starts_with('$') || s[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let tag = &s[..=tag_end];
+    let body = &s[tag.len()..];
+
+    Some(match body.find(tag) {
+        Some(pos) => tag.len() + pos + tag.len(),
+        // unterminated
+        None => s.len(),
+    })
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn leading_word(s: &str) -> &str {
+    &s[..s.find(|c: char| !is_word_char(c)).unwrap_or(s.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checkpoint, split_statements, strip_comments};
+
+    #[test]
+    fn strips_comments_outside_literals() {
+        let sql = "-- create the table\n\
+                   CREATE TABLE users ( -- trailing\n\
+                   \x20   id BIGINT /* block /* nested */ comment */ PRIMARY KEY\n\
+                   );\n";
+
+        assert_eq!(
+            strip_comments(sql),
+            "CREATE TABLE users (\n    id BIGINT   PRIMARY KEY\n);"
+        );
+    }
+
+    #[test]
+    fn keeps_comment_markers_inside_literals() {
+        let sql = "INSERT INTO t VALUES ('-- not a comment', 'it''s /* kept */');\n\
+                   SELECT \"col--name\" FROM t;";
+
+        assert_eq!(strip_comments(sql), sql);
+    }
+
+    #[test]
+    fn keeps_dollar_quoted_bodies() {
+        let sql = "CREATE FUNCTION f() RETURNS int AS $body$\n\
+                   -- kept\n\
+                   SELECT $1 /* kept */;\n\
+                   $body$ LANGUAGE sql; -- removed";
+
+        assert_eq!(
+            strip_comments(sql),
+            "CREATE FUNCTION f() RETURNS int AS $body$\n\
+             -- kept\n\
+             SELECT $1 /* kept */;\n\
+             $body$ LANGUAGE sql;"
+        );
+    }
+
+    #[test]
+    fn ignores_comment_only_edits() {
+        let before = "-- v1 of the docs\nCREATE TABLE t ( id INT );\n";
+        let after =
+            "-- v2 of the docs\n-- with more detail\n\nCREATE TABLE t ( id INT ); -- done\n";
+
+        assert_eq!(strip_comments(before), strip_comments(after));
+    }
+
+    #[test]
+    fn splits_statements_outside_literals() {
+        let sql = "-- sqlx:batch-commit 2\n\
+                   INSERT INTO t VALUES ('a;b');\n\
+                   /* ; */ INSERT INTO t VALUES (1); -- trailing;\n\
+                   CREATE FUNCTION f() RETURNS int AS $$ SELECT 1; $$ LANGUAGE sql;\n\
+                   -- only a comment;\n";
+
+        assert_eq!(
+            split_statements(sql).unwrap(),
+            [
+                "-- sqlx:batch-commit 2\nINSERT INTO t VALUES ('a;b');",
+                "/* ; */ INSERT INTO t VALUES (1);",
+                "-- trailing;\nCREATE FUNCTION f() RETURNS int AS $$ SELECT 1; $$ LANGUAGE sql;",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_sqlite_triggers_together() {
+        let sql = "BEGIN;\n\
+                   CREATE TRIGGER t_audit AFTER INSERT ON t\n\
+                   BEGIN\n\
+                   \x20   INSERT INTO audit VALUES (new.id, CASE WHEN new.id > 0 THEN 'a' END);\n\
+                   \x20   UPDATE t SET seen = 1 WHERE id = new.id;\n\
+                   END;\n\
+                   COMMIT;";
+
+        assert_eq!(
+            split_statements(sql).unwrap(),
+            [
+                "BEGIN;",
+                "CREATE TRIGGER t_audit AFTER INSERT ON t\n\
+                 BEGIN\n\
+                 \x20   INSERT INTO audit VALUES (new.id, CASE WHEN new.id > 0 THEN 'a' END);\n\
+                 \x20   UPDATE t SET seen = 1 WHERE id = new.id;\n\
+                 END;",
+                "COMMIT;",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_mysql_procedures_together() {
+        let sql = "CREATE PROCEDURE p(IN x INT)\n\
+                   BEGIN\n\
+                   \x20   DECLARE y INT;\n\
+                   \x20   IF x > 0 THEN\n\
+                   \x20       SET y = x;\n\
+                   \x20   END IF;\n\
+                   \x20   CASE y WHEN 1 THEN SET y = 2; ELSE BEGIN END; END CASE;\n\
+                   END;\n\
+                   CALL p(1);";
+
+        let statements = split_statements(sql).unwrap();
+
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("END CASE;\nEND;"));
+        assert_eq!(statements[1], "CALL p(1);");
+    }
+
+    #[test]
+    fn refuses_to_split_unsafely() {
+        assert_eq!(
+            split_statements("CREATE TRIGGER t AFTER INSERT ON x BEGIN SELECT 1;"),
+            None
+        );
+        assert_eq!(
+            split_statements("DELIMITER //\nCREATE PROCEDURE p() BEGIN SELECT 1; END//"),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_leading_checkpoints() {
+        let sql = "CREATE TABLE t ( id INT ); -- sqlx:checkpoint created\n\
+                   -- sqlx:checkpoint validated\n\
+                   -- sqlx:checkpoint backfill\n\
+                   INSERT INTO t VALUES (1);\n\
+                   -- sqlx:checkpointed\n\
+                   INSERT INTO t VALUES (2)\n\
+                   -- sqlx:checkpoint inside\n\
+                   ;";
+
+        assert_eq!(
+            split_statements(sql)
+                .unwrap()
+                .into_iter()
+                .map(checkpoint)
+                .collect::<Vec<_>>(),
+            [None, Some("backfill"), None]
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use crate::error::{BoxDynError, Error};
+use crate::error::{BoxDynError, DatabaseError, Error};
+use crate::migrate::DirtyRecord;
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -18,15 +20,41 @@ pub enum MigrateError {
     #[error("migration {0} is not present in the migration source")]
     VersionNotPresent(i64),
 
+    #[error("migration {0} was previously applied but has no down migration in the migration source")]
+    DownMissing(i64),
+
     #[error("migration {0} is older than the latest applied migration {1}")]
     VersionTooOld(i64, i64),
 
     #[error("migration {0} is newer than the latest applied migration {1}")]
     VersionTooNew(i64, i64),
 
+    #[error(
+        "migration source mixes sequential version {0} with timestamp version {1}; \
+         migrations are applied in numeric order, so rename them to use a single versioning scheme"
+    )]
+    MixedVersionSchemes(i64, i64),
+
+    #[error("migration {0} has no SQL for {1} and no dialect-independent fallback")]
+    DialectMissing(i64, String),
+
+    #[error("migration {0} failed {}: {2}", checkpoint_context(.1))]
+    Checkpoint(i64, Option<String>, #[source] Box<MigrateError>),
+
+    #[error("migration {0} has an invalid directive: {1}")]
+    InvalidDirective(i64, String),
+
+    #[error(
+        "timed out after {0:?} waiting for the migration lock; is another migration in progress?"
+    )]
+    LockTimeout(Duration),
+
     #[error("database driver does not support force-dropping a database (Only PostgreSQL)")]
     ForceNotSupported,
 
+    #[error("database driver does not support `{0}`")]
+    Unsupported(&'static str),
+
     #[deprecated = "migration types are now inferred"]
     #[error("cannot mix reversible migrations with simple migrations. All migrations should be reversible or simple migrations")]
     InvalidMixReversibleAndSimple,
@@ -36,4 +64,46 @@ pub enum MigrateError {
         "migration {0} is partially applied; fix and remove row from `migrations` table"
     )]
     Dirty(i64),
+
+    // Like `Dirty`, with the details recorded in the migrations table.
+    #[error("{}; fix and remove row from `migrations` table", dirty_context(.0))]
+    DirtyRecord(DirtyRecord),
+}
+
+impl MigrateError {
+    /// The error returned by the database, if a migration failed because of one.
+    ///
+    /// The same error is also reachable through [`std::error::Error::source()`].
+    pub fn as_database_error(&self) -> Option<&(dyn DatabaseError + 'static)> {
+        match self {
+            MigrateError::Execute(err) => err.as_database_error(),
+            MigrateError::Checkpoint(_, _, err) => err.as_database_error(),
+            _ => None,
+        }
+    }
+}
+
+fn checkpoint_context(checkpoint: &Option<String>) -> String {
+    match checkpoint {
+        Some(checkpoint) => format!("after checkpoint {checkpoint:?}"),
+        None => "before its first checkpoint".to_owned(),
+    }
+}
+
+fn dirty_context(record: &DirtyRecord) -> String {
+    let state = if record.reverting {
+        "reverted"
+    } else {
+        "applied"
+    };
+
+    // drivers which only know the dirty version leave the description empty
+    if record.description.is_empty() {
+        format!("migration {} is partially {state}", record.version)
+    } else {
+        format!(
+            "migration {} ({}) is partially {state}",
+            record.version, record.description
+        )
+    }
 }
@@ -1,8 +1,10 @@
 use crate::error::Error;
-use crate::migrate::{AppliedMigration, MigrateError, Migration};
+use crate::migrate::{AppliedMigration, DirtyRecord, MigrateError, Migration};
 use futures_core::future::BoxFuture;
 use std::time::Duration;
 
+// all methods fail with `Error::Configuration`, without connecting, if the url does not
+// name a database
 pub trait MigrateDatabase {
     // create database in url
     // uses a maintenance database depending on driver
@@ -21,34 +23,153 @@ pub trait MigrateDatabase {
     fn force_drop_database(_url: &str) -> BoxFuture<'_, Result<(), Error>> {
         Box::pin(async { Err(MigrateError::ForceNotSupported)? })
     }
+
+    // the statements `Migrate::ensure_migrations_table` executes for `migration_table`, and
+    // with `installed_on_index` those of `Migrate::ensure_installed_on_index`, without
+    // connecting; e.g. for a DBA to review or create the table beforehand
+    // the url only selects the driver
+    fn migrations_table_ddl(
+        _url: &str,
+        _migration_table: &str,
+        _installed_on_index: bool,
+    ) -> Result<Vec<String>, Error> {
+        Err(Error::Configuration(
+            "database driver does not support printing the migrations table DDL".into(),
+        ))
+    }
 }
 
 // 'e = Executor
 pub trait Migrate {
     // ensure migrations table exists
-    // will create or migrate it if needed
+    // will create or migrate it if needed, adding the columns a table created by an older
+    // version lacks
     fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>>;
 
+    // Create an index on the `installed_on` column of the migrations table if it does not
+    // exist yet. Opt-in, see `Migrator::set_installed_on_index`.
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn ensure_installed_on_index(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        let _ = migration_table;
+        Box::pin(async {
+            Err(MigrateError::Unsupported(
+                "Migrate::ensure_installed_on_index",
+            ))
+        })
+    }
+
+    // Whether the migrations table exists, for callers which only read it and must not create
+    // it as `ensure_migrations_table` would.
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn migrations_table_exists(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<bool, MigrateError>> {
+        let _ = migration_table;
+        Box::pin(async {
+            Err(MigrateError::Unsupported(
+                "Migrate::migrations_table_exists",
+            ))
+        })
+    }
+
     // Return the version on which the database is dirty or None otherwise.
     // "dirty" means there is a partially applied migration that failed.
     fn dirty_version(&mut self, migration_table: String) -> BoxFuture<'_, Result<Option<i64>, MigrateError>>;
 
+    // Like `dirty_version`, but return the description of the dirty migration and whether it
+    // failed while being applied or reverted, e.g. for recovery tooling to decide what to do.
+    // the default implementation only knows the version, from `dirty_version`
+    fn dirty_record(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<DirtyRecord>, MigrateError>> {
+        let version = self.dirty_version(migration_table);
+        Box::pin(async move {
+            Ok(version.await?.map(|version| DirtyRecord {
+                version,
+                description: String::new(),
+                reverting: false,
+            }))
+        })
+    }
+
+    // Remove the failed row left behind by a partially applied migration,
+    // so that the migration can be applied again.
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn clear_dirty_version(
+        &mut self,
+        version: i64,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        let _ = (version, migration_table);
+        Box::pin(async { Err(MigrateError::Unsupported("Migrate::clear_dirty_version")) })
+    }
+
+    // Replace the checksum recorded for an applied migration.
+    // used to switch between checksum modes without re-applying migrations
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn update_checksum(
+        &mut self,
+        version: i64,
+        checksum: Vec<u8>,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        let _ = (version, checksum, migration_table);
+        Box::pin(async { Err(MigrateError::Unsupported("Migrate::update_checksum")) })
+    }
+
+    // Insert a failed row for the migration, flagging the database as dirty on its version.
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn mark_dirty<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        let _ = (migration, migration_table);
+        Box::pin(async { Err(MigrateError::Unsupported("Migrate::mark_dirty")) })
+    }
+
     // Return the ordered list of applied migrations
     fn list_applied_migrations(
         &mut self,
         migration_table: String
     ) -> BoxFuture<'_, Result<Vec<AppliedMigration>, MigrateError>>;
 
+    // Return the applied migration with the highest version, if any
+    // the default implementation lists every applied migration; drivers should override it
+    // with a query reading a single row
+    fn latest_applied_migration(
+        &mut self,
+        migration_table: String
+    ) -> BoxFuture<'_, Result<Option<AppliedMigration>, MigrateError>> {
+        let applied = self.list_applied_migrations(migration_table);
+        Box::pin(async move { Ok(applied.await?.pop()) })
+    }
+
     // Should acquire a database lock so that only one migration process
     // can run at a time. [`Migrate`] will call this function before applying
     // any migrations.
     fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>>;
 
+    // Like `lock`, but give up with `MigrateError::LockTimeout` if the lock could not be
+    // acquired within `timeout`, e.g. because another migration is in progress.
+    // the default implementation delegates to `lock`, ignoring the timeout
+    fn lock_with_timeout(&mut self, timeout: Duration) -> BoxFuture<'_, Result<(), MigrateError>> {
+        let _ = timeout;
+        self.lock()
+    }
+
     // Should release the lock. [`Migrate`] will call this function after all
     // migrations have been run.
     fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>>;
 
     // run SQL from migration in a DDL transaction
+    // or statement by statement without one, for a migration with a `-- sqlx:no-transaction`
+    // directive
     // insert new row to [_migrations] table on completion (success or failure)
     // returns the time taking to run the migration SQL
     fn apply<'e: 'm, 'm>(
@@ -57,6 +178,44 @@ pub trait Migrate {
         migration_table: String
     ) -> BoxFuture<'m, Result<Duration, MigrateError>>;
 
+    // like `apply`, but records the execution time as measured by the database server,
+    // excluding connection overhead and the bookkeeping queries
+    // drivers which cannot measure it server-side fall back to `apply`
+    fn apply_server_timed<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        self.apply(migration, migration_table)
+    }
+
+    // like `apply`, but executes the migration SQL one statement at a time, calling
+    // `progress(k, total)` before running the k-th of its `total` statements
+    // migrations with a `-- sqlx:batch-commit` or `-- sqlx:no-transaction` directive are
+    // applied as by `apply`, without reporting progress, as are migrations on drivers which
+    // cannot split them
+    fn apply_with_progress<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+        progress: &'m mut (dyn FnMut(usize, usize) + Send),
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        let _ = progress;
+        self.apply(migration, migration_table)
+    }
+
+    // insert a successful row to [_migrations] table without running the migration SQL
+    // used to adopt migrations whose effects are already present in the database
+    // the default implementation fails with `MigrateError::Unsupported`
+    fn record_applied<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        let _ = (migration, migration_table);
+        Box::pin(async { Err(MigrateError::Unsupported("Migrate::record_applied")) })
+    }
+
     // run a revert SQL from migration in a DDL transaction
     // deletes the row in [_migrations] table with specified migration version on completion (success or failure)
     // returns the time taking to run the migration SQL
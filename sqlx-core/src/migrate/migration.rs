@@ -1,8 +1,14 @@
 use std::borrow::Cow;
+use std::time::Duration;
 
 use sha2::{Digest, Sha384};
 
-use super::MigrationType;
+use super::comments::{checkpoint, split_statements, strip_comments};
+use super::{MigrateError, MigrationType};
+
+/// Database dialects which may be named in a migration file name to provide SQL
+/// specific to that database, e.g. `0003_add_users.postgres.up.sql`.
+pub const DIALECTS: &[&str] = &["postgres", "mysql", "sqlite"];
 
 #[derive(Debug, Clone)]
 pub struct Migration {
@@ -10,6 +16,8 @@ pub struct Migration {
     pub description: Cow<'static, str>,
     pub migration_type: MigrationType,
     pub sql: Cow<'static, str>,
+    // see `dialects()`
+    pub(crate) dialects: Cow<'static, [(Cow<'static, str>, Cow<'static, str>)]>,
     pub checksum: Cow<'static, [u8]>,
 }
 
@@ -20,16 +28,400 @@ impl Migration {
         migration_type: MigrationType,
         sql: Cow<'static, str>,
     ) -> Self {
-        let checksum = Cow::Owned(Vec::from(Sha384::digest(sql.as_bytes()).as_slice()));
+        Self::with_dialects(version, description, migration_type, sql, Vec::new())
+    }
+
+    /// Create a migration with SQL variants for specific database dialects.
+    ///
+    /// `sql` is the fallback used for databases without a variant; it is empty if there
+    /// is none. The checksum covers the fallback and every variant.
+    pub fn with_dialects(
+        version: i64,
+        description: Cow<'static, str>,
+        migration_type: MigrationType,
+        sql: Cow<'static, str>,
+        mut dialects: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    ) -> Self {
+        dialects.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let checksum = Cow::Owned(Self::compute_checksum(&sql, &dialects));
 
         Migration {
             version,
             description,
             migration_type,
             sql,
+            dialects: Cow::Owned(dialects),
             checksum,
         }
     }
+
+    // Used by `migrate!()`, which embeds migrations in a `static`: the dialects are already
+    // sorted and the checksum computed at compile time.
+    #[doc(hidden)]
+    pub const fn from_parts(
+        version: i64,
+        description: &'static str,
+        migration_type: MigrationType,
+        sql: &'static str,
+        dialects: &'static [(Cow<'static, str>, Cow<'static, str>)],
+        checksum: &'static [u8],
+    ) -> Self {
+        Migration {
+            version,
+            description: Cow::Borrowed(description),
+            migration_type,
+            sql: Cow::Borrowed(sql),
+            dialects: Cow::Borrowed(dialects),
+            checksum: Cow::Borrowed(checksum),
+        }
+    }
+
+    /// The version of this migration, taken from its file name.
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    /// The description of this migration, taken from its file name.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Whether this is a simple migration or one half of a reversible migration.
+    pub fn migration_type(&self) -> MigrationType {
+        self.migration_type
+    }
+
+    /// SQL specific to a database dialect, as `(dialect, sql)` pairs sorted by dialect.
+    pub fn dialects(&self) -> &[(Cow<'static, str>, Cow<'static, str>)] {
+        &self.dialects
+    }
+
+    /// The SQL of this migration.
+    ///
+    /// For a migration with dialect variants this is the dialect-independent fallback, which
+    /// may be empty; use [`sql_for`][Self::sql_for] to get the SQL run against a database.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    // Without dialect variants, this is the SHA-384 of the SQL, as it always has been.
+    #[doc(hidden)]
+    pub fn compute_checksum<D: AsRef<str>, S: AsRef<str>>(
+        sql: &str,
+        dialects: &[(D, S)],
+    ) -> Vec<u8> {
+        let mut hasher = Sha384::new();
+        hasher.update(sql.as_bytes());
+
+        for (dialect, sql) in dialects {
+            hasher.update(b"\0");
+            hasher.update(dialect.as_ref().as_bytes());
+            hasher.update(b"\0");
+            hasher.update(sql.as_ref().as_bytes());
+        }
+
+        Vec::from(hasher.finalize().as_slice())
+    }
+
+    /// The checksum of this migration with SQL comments removed.
+    ///
+    /// Editing only the comments of a migration, or blank lines around them, does not change
+    /// this checksum.
+    pub fn checksum_ignoring_comments(&self) -> Vec<u8> {
+        let dialects: Vec<_> = self
+            .dialects
+            .iter()
+            .map(|(dialect, sql)| (dialect, strip_comments(sql)))
+            .collect();
+
+        Self::compute_checksum(&strip_comments(&self.sql), &dialects)
+    }
+
+    /// The SQL to execute against a database of the given dialect.
+    ///
+    /// Returns the variant for `dialect` if there is one, otherwise the dialect-independent
+    /// SQL. Errors if the migration only has variants for other dialects.
+    pub fn sql_for(&self, dialect: &str) -> Result<&str, MigrateError> {
+        if let Some((_, sql)) = self.dialects.iter().find(|(d, _)| d == dialect) {
+            return Ok(sql);
+        }
+
+        if self.dialects.is_empty() || !self.sql.is_empty() {
+            return Ok(&self.sql);
+        }
+
+        Err(MigrateError::DialectMissing(
+            self.version,
+            dialect.to_owned(),
+        ))
+    }
+
+    /// Look up a `-- sqlx:<name> [argument]` directive in the SQL executed against a database
+    /// of the given dialect, as returned by [`sql_for`][Self::sql_for].
+    ///
+    /// Each dialect variant of a migration carries its own directives; the fallback's only
+    /// apply to databases without a variant.
+    ///
+    /// Directives are only recognized in the comment block at the top of the file,
+    /// before the first line of SQL. Returns the (trimmed, possibly empty) argument
+    /// if the directive is present.
+    ///
+    /// ```sql
+    /// -- sqlx:baseline SELECT 1 FROM information_schema.tables WHERE table_name = 'users'
+    /// CREATE TABLE users ( id BIGINT PRIMARY KEY );
+    /// ```
+    pub fn directive(&self, dialect: &str, name: &str) -> Option<&str> {
+        // a missing variant is reported when the migration is applied
+        let sql = self.sql_for(dialect).ok()?;
+
+        for line in sql.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some(comment) = line.strip_prefix("--") else {
+                // directives must come before any SQL
+                break;
+            };
+
+            let Some(directive) = comment.trim_start().strip_prefix("sqlx:") else {
+                continue;
+            };
+
+            let (directive_name, argument) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+
+            if directive_name == name {
+                return Some(argument.trim());
+            }
+        }
+
+        None
+    }
+
+    /// The argument of a `-- sqlx:lock-timeout <duration>` directive, if present.
+    ///
+    /// The duration is an integer followed by `ms`, `s`, `min` or `h`, e.g. `10min`.
+    /// Drivers which support it (currently only PostgreSQL) wait at most this long for a lock
+    /// while executing the migration, overriding the session-wide `lock_timeout`, such as one
+    /// set by `sqlx migrate run --lock-timeout`.
+    pub fn lock_timeout(&self, dialect: &str) -> Result<Option<Duration>, MigrateError> {
+        self.directive(dialect, "lock-timeout")
+            .map(|argument| {
+                parse_duration(argument).ok_or_else(|| {
+                    MigrateError::InvalidDirective(
+                        self.version,
+                        format!("invalid lock-timeout {argument:?}"),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// The argument of a `-- sqlx:manual-followup <message>` directive, if present.
+    ///
+    /// The message describes something a human has to do after the migration is applied, e.g.
+    /// rebuilding a materialized view by hand. sqlx does not act on it itself.
+    pub fn manual_followup(&self, dialect: &str) -> Result<Option<&str>, MigrateError> {
+        match self.directive(dialect, "manual-followup") {
+            Some("") => Err(MigrateError::InvalidDirective(
+                self.version,
+                "manual-followup requires a message".to_owned(),
+            )),
+            followup => Ok(followup),
+        }
+    }
+
+    /// The argument of a `-- sqlx:batch-commit <statements>` directive, if present.
+    ///
+    /// A migration with this directive is applied in batches of the given number of
+    /// statements, each in its own transaction, instead of in a single transaction. This keeps
+    /// transactions small for data-heavy migrations, at the cost of atomicity: if a batch
+    /// fails, the batches before it stay committed and the database is left dirty. The
+    /// migration is only recorded as applied once every batch is committed.
+    ///
+    /// Statements are split at semicolons outside of comments, string literals and
+    /// dollar-quoted strings, so e.g. SQLite triggers cannot be batched.
+    pub fn batch_commit(&self, dialect: &str) -> Result<Option<usize>, MigrateError> {
+        self.directive(dialect, "batch-commit")
+            .map(|argument| match argument.parse::<usize>() {
+                Ok(statements) if statements > 0 => Ok(statements),
+                _ => Err(MigrateError::InvalidDirective(
+                    self.version,
+                    format!("invalid batch-commit {argument:?}, expected a number of statements"),
+                )),
+            })
+            .transpose()
+    }
+
+    /// The SQL to execute against a database of the given dialect, split into batches
+    /// according to the `-- sqlx:batch-commit` directive, or `None` without the directive.
+    ///
+    /// A migration with a `-- sqlx:no-transaction` directive is split into single statements,
+    /// to be executed outside of any transaction.
+    #[doc(hidden)]
+    pub fn sql_batches(&self, dialect: &str) -> Result<Option<Vec<String>>, MigrateError> {
+        if self.no_tx(dialect) {
+            for directive in ["batch-commit", "lock-timeout"] {
+                if self.directive(dialect, directive).is_some() {
+                    return Err(MigrateError::InvalidDirective(
+                        self.version,
+                        format!("{directive} cannot be combined with no-transaction"),
+                    ));
+                }
+            }
+
+            return Ok(Some(
+                self.split_for(dialect, "no-transaction")?
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            ));
+        }
+
+        let Some(size) = self.batch_commit(dialect)? else {
+            return Ok(None);
+        };
+
+        let statements = self.split_for(dialect, "batch-commit")?;
+
+        Ok(Some(
+            statements
+                .chunks(size)
+                .map(|batch| batch.join("\n"))
+                .collect(),
+        ))
+    }
+
+    /// The SQL to execute against a database of the given dialect, split into statements, or
+    /// `None` if it cannot be split safely.
+    #[doc(hidden)]
+    pub fn sql_statements(&self, dialect: &str) -> Result<Option<Vec<&str>>, MigrateError> {
+        Ok(split_statements(self.sql_for(dialect)?))
+    }
+
+    // the statements of the SQL, for a directive which executes them one by one
+    fn split_for(&self, dialect: &str, directive: &str) -> Result<Vec<&str>, MigrateError> {
+        self.sql_statements(dialect)?
+            .ok_or_else(|| self.unsplittable(directive))
+    }
+
+    fn unsplittable(&self, directive: &str) -> MigrateError {
+        MigrateError::InvalidDirective(
+            self.version,
+            format!(
+                "{directive} requires SQL which can be split into statements; close every \
+                 BEGIN with an END and do not use DELIMITER"
+            ),
+        )
+    }
+
+    /// The SQL to execute against a database of the given dialect, split into statements, each
+    /// paired with the last `-- sqlx:checkpoint <name>` comment before it, if any.
+    ///
+    /// Checkpoints mark how far a migration got: if a statement fails, the error names the
+    /// checkpoint paired with it as the last one passed. A checkpoint is a comment on a line of
+    /// its own between statements, anywhere in the file:
+    ///
+    /// ```sql
+    /// ALTER TABLE users ADD COLUMN email TEXT;
+    /// -- sqlx:checkpoint email-added
+    /// UPDATE users SET email = login || '@example.com';
+    /// ```
+    ///
+    /// Returns `None` if the SQL cannot be split safely, to be executed whole instead. Such SQL
+    /// cannot have checkpoints, as there are no statements to pair them with.
+    #[doc(hidden)]
+    pub fn sql_checkpoints(
+        &self,
+        dialect: &str,
+    ) -> Result<Option<Vec<(Option<&str>, &str)>>, MigrateError> {
+        let sql = self.sql_for(dialect)?;
+
+        let Some(statements) = split_statements(sql) else {
+            if sql.lines().any(|line| checkpoint(line).is_some()) {
+                return Err(self.unsplittable("checkpoint"));
+            }
+
+            return Ok(None);
+        };
+
+        let mut passed = None;
+
+        statements
+            .into_iter()
+            .map(|statement| {
+                match checkpoint(statement) {
+                    Some("") => {
+                        return Err(MigrateError::InvalidDirective(
+                            self.version,
+                            "checkpoint requires a name".to_owned(),
+                        ))
+                    }
+                    Some(name) => passed = Some(name),
+                    None => {}
+                }
+
+                Ok((passed, statement))
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
+    /// Wrap an error executing a statement of the migration with the checkpoint paired with
+    /// that statement by [`sql_checkpoints`][Self::sql_checkpoints].
+    #[doc(hidden)]
+    pub fn checkpoint_error(
+        &self,
+        checkpoint: Option<&str>,
+        error: impl Into<MigrateError>,
+    ) -> MigrateError {
+        MigrateError::Checkpoint(
+            self.version,
+            checkpoint.map(str::to_owned),
+            Box::new(error.into()),
+        )
+    }
+
+    /// Returns `true` if the migration carries a `-- sqlx:baseline` directive.
+    ///
+    /// A baseline migration describes schema that may already exist when adopting a
+    /// pre-existing database. Instead of being executed, it can be recorded as applied
+    /// if its probe query (the directive's argument) returns a row, or unconditionally
+    /// when adopting with `sqlx migrate run --adopt`.
+    pub fn is_baseline(&self, dialect: &str) -> bool {
+        self.directive(dialect, "baseline").is_some()
+    }
+
+    /// Returns `true` if the migration carries a `-- sqlx:no-transaction` directive.
+    ///
+    /// Such a migration is not wrapped in a transaction, for statements which cannot run in
+    /// one, e.g. `CREATE INDEX CONCURRENTLY` on PostgreSQL. Its statements are executed one at
+    /// a time, and the migration is recorded as applied once all of them succeeded.
+    ///
+    /// Such a migration cannot be rolled back if it fails part-way: the statements before the
+    /// failed one stay applied, and the failed statement itself may leave effects behind, such
+    /// as an invalid index. The database is left dirty on the migration, to be resolved by
+    /// hand. Keep such migrations to a single statement where possible.
+    pub fn no_tx(&self, dialect: &str) -> bool {
+        self.directive(dialect, "no-transaction").is_some()
+    }
+}
+
+fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value.parse().ok()?;
+
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        "min" => Some(Duration::from_secs(value.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(value.checked_mul(60 * 60)?)),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -37,3 +429,29 @@ pub struct AppliedMigration {
     pub version: i64,
     pub checksum: Cow<'static, [u8]>,
 }
+
+/// The row of the migrations table left behind by a migration which failed part-way, as
+/// returned by [`Migrate::dirty_record`][super::Migrate::dirty_record].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirtyRecord {
+    pub version: i64,
+    pub description: String,
+    /// `true` if the migration failed while its down migration was being reverted, `false` if
+    /// it failed while being applied.
+    ///
+    /// Only MySQL, whose DDL commits implicitly, leaves a failed revert behind.
+    pub reverting: bool,
+}
+
+impl DirtyRecord {
+    // A failed application is recorded without an execution time (-1), while the row a failed
+    // revert flags keeps the execution time of the migration's application.
+    #[doc(hidden)]
+    pub fn from_row(version: i64, description: String, execution_time: i64) -> Self {
+        DirtyRecord {
+            version,
+            description,
+            reverting: execution_time >= 0,
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use crate::acquire::Acquire;
-use crate::migrate::{AppliedMigration, Migrate, MigrateError, Migration, MigrationSource};
+use crate::connection::Connection;
+use crate::error::Error;
+use crate::migrate::{
+    AppliedMigration, Migrate, MigrateError, Migration, MigrationSource, MigrationType,
+};
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
 use std::ops::Deref;
 use std::slice;
+use std::time::Duration;
 
 use super::DEFAULT_MIGRATION_TABLE;
 
@@ -13,7 +19,66 @@ pub struct Migrator {
     pub migrations: Cow<'static, [Migration]>,
     pub ignore_missing: bool,
     pub locking: bool,
-    pub migration_table: Option<String>,
+    pub migration_table: Option<Cow<'static, str>>,
+    pub execution_time: ExecutionTime,
+    pub installed_on_index: bool,
+    pub strict_versions: bool,
+}
+
+/// How the `execution_time` of an applied migration is measured.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ExecutionTime {
+    /// Wall-clock time measured by the client, including network latency and the
+    /// transaction bookkeeping.
+    #[default]
+    Wall,
+
+    /// Time measured by the database server's clock around the migration script.
+    ///
+    /// Only supported by Postgres; other drivers fall back to [`ExecutionTime::Wall`].
+    Server,
+}
+
+/// A difference between two [`Migrator`]s, as reported by [`Migrator::diff`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MigrationDiff {
+    /// The migration is only present in the migrator `diff` was called on.
+    OnlyInSelf {
+        version: i64,
+        migration_type: MigrationType,
+    },
+
+    /// The migration is only present in the migrator passed to `diff`.
+    OnlyInOther {
+        version: i64,
+        migration_type: MigrationType,
+    },
+
+    /// The migration is present in both, but with different checksums.
+    ChecksumMismatch {
+        version: i64,
+        migration_type: MigrationType,
+    },
+}
+
+/// A migration which failed part-way through being applied, leaving the database dirty, as
+/// returned by [`Migrator::dirty_migration`].
+#[derive(Debug, Copy, Clone)]
+pub struct DirtyMigration<'m> {
+    /// The version recorded as failed in the migrations table.
+    pub version: i64,
+
+    /// The local migration with this version, or `None` if it no longer exists. Unless it
+    /// was modified since, its SQL is what was partially applied.
+    pub migration: Option<&'m Migration>,
+}
+
+// Up and down migrations of a reversible migration share a version, so key on both.
+fn by_version_and_direction(migrator: &Migrator) -> BTreeMap<(i64, bool), &Migration> {
+    migrator
+        .iter()
+        .map(|m| ((m.version, m.migration_type.is_down_migration()), m))
+        .collect()
 }
 
 fn validate_applied_migrations(
@@ -35,6 +100,28 @@ fn validate_applied_migrations(
     Ok(())
 }
 
+/// Smallest 14-digit version, i.e. one generated from a `YYYYMMDDHHMMSS` timestamp.
+const MIN_TIMESTAMP_VERSION: i64 = 10_000_000_000_000;
+
+/// How many times `run_with_connector` and `undo_with_connector` reconnect after losing the
+/// connection, before giving up.
+const MAX_RECONNECTS: usize = 3;
+
+/// How long `run_with_connector` and `undo_with_connector` wait before reconnecting the first
+/// time; the wait doubles with every further reconnect.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+// Whether the connection was lost or could not be opened, so that reconnecting may succeed.
+fn is_connection_error(error: &MigrateError) -> bool {
+    match error {
+        MigrateError::Execute(e) => {
+            matches!(e, Error::Io(_) | Error::Tls(_) | Error::WorkerCrashed)
+        }
+        MigrateError::Checkpoint(_, _, e) => is_connection_error(e),
+        _ => false,
+    }
+}
+
 impl Migrator {
     /// Creates a new instance with the given source.
     ///
@@ -58,11 +145,18 @@ impl Migrator {
     where
         S: MigrationSource<'s>,
     {
+        let migrations = source.resolve().await.map_err(MigrateError::Source)?;
+
         Ok(Self {
-            migrations: Cow::Owned(source.resolve().await.map_err(MigrateError::Source)?),
+            migrations: Cow::Owned(migrations),
             ignore_missing: false,
             locking: true,
-            migration_table: Some(migration_table.unwrap_or_else(|| DEFAULT_MIGRATION_TABLE.to_string())),
+            migration_table: Some(Cow::Owned(
+                migration_table.unwrap_or_else(|| DEFAULT_MIGRATION_TABLE.to_string()),
+            )),
+            execution_time: ExecutionTime::Wall,
+            installed_on_index: false,
+            strict_versions: false,
         })
     }
 
@@ -86,28 +180,193 @@ impl Migrator {
     }
 
     /// Specify the migration table to use to support multi-tenancy.
+    ///
+    /// A migrator embedded with `migrate!()` into a `static` can't be modified; pass
+    /// `migration_table = "..."` to the macro instead.
     pub fn set_migration_table<S: AsRef<str>>(&mut self, migration_table: S) -> &Self {
-        self.migration_table = Some(migration_table.as_ref().to_string());
+        self.migration_table = Some(Cow::Owned(migration_table.as_ref().to_string()));
         self
     }
 
+    /// Specify how the execution time recorded for applied migrations is measured.
+    /// Defaults to [`ExecutionTime::Wall`].
+    pub fn set_execution_time(&mut self, execution_time: ExecutionTime) -> &Self {
+        self.execution_time = execution_time;
+        self
+    }
+
+    /// Specify whether checksums should ignore SQL comments, so that editing only the comments
+    /// of an applied migration is not reported as a modification. Defaults to `false`.
+    ///
+    /// This recomputes the checksum of every migration. Checksums recorded by a database are
+    /// not changed, so migrations applied in the other mode will fail validation until their
+    /// stored checksums are updated.
+    pub fn set_checksum_ignore_comments(&mut self, ignore_comments: bool) -> &Self {
+        for migration in self.migrations.to_mut() {
+            let checksum = if ignore_comments {
+                migration.checksum_ignoring_comments()
+            } else {
+                Migration::compute_checksum(&migration.sql, &migration.dialects)
+            };
+
+            migration.checksum = Cow::Owned(checksum);
+        }
+
+        self
+    }
+
+    /// Specify whether to index the `installed_on` column of the migrations table, for queries
+    /// filtering applied migrations by time. Defaults to `false`.
+    ///
+    /// The index is created on the next run if it does not exist yet. Every migration table has
+    /// a primary key on `version` regardless. The index only pays off for migrations tables with
+    /// many rows, e.g. when many tenants share one table; it adds a few dozen bytes of storage
+    /// per applied migration.
+    pub fn set_installed_on_index(&mut self, installed_on_index: bool) -> &Self {
+        self.installed_on_index = installed_on_index;
+        self
+    }
+
+    /// Specify whether to refuse running or reverting migrations from a source which mixes
+    /// sequential versions (e.g. `0003`) with timestamp versions (e.g. `20240101120000`).
+    /// Defaults to `false`.
+    ///
+    /// Migrations are applied in numeric order, so the two schemes interleave unpredictably.
+    /// A migrator embedded with `migrate!()` can't be modified; pass `strict_versions = true`
+    /// to the macro instead.
+    pub fn set_strict_versions(&mut self, strict_versions: bool) -> &Self {
+        self.strict_versions = strict_versions;
+        self
+    }
+
+    /// Check that the migrations use a single versioning scheme, as enforced by
+    /// [`Migrator::set_strict_versions`].
+    ///
+    /// Returns [`MigrateError::MixedVersionSchemes`] with the first sequential and the first
+    /// timestamp version otherwise.
+    pub fn validate_version_schemes(&self) -> Result<(), MigrateError> {
+        let sequential = self.iter().find(|m| m.version < MIN_TIMESTAMP_VERSION);
+        let timestamp = self.iter().find(|m| m.version >= MIN_TIMESTAMP_VERSION);
+
+        if let (Some(sequential), Some(timestamp)) = (sequential, timestamp) {
+            return Err(MigrateError::MixedVersionSchemes(
+                sequential.version,
+                timestamp.version,
+            ));
+        }
+
+        Ok(())
+    }
+
     fn migration_table(&self) -> String {
-        match self.migration_table.as_ref() {
+        match self.migration_table.as_deref() {
             Some(s) => s.to_owned(),
             None => DEFAULT_MIGRATION_TABLE.to_string(),
         }
     }
 
     /// Get an iterator over all known migrations.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use sqlx_core::migrate::MigrateError;
+    /// # fn main() -> Result<(), MigrateError> {
+    /// # sqlx::__rt::test_block_on(async move {
+    /// # use sqlx_core::migrate::Migrator;
+    /// use std::path::Path;
+    ///
+    /// let m = Migrator::new(Path::new("./migrations"), None).await?;
+    ///
+    /// for migration in m.iter().filter(|m| m.migration_type().is_up_migration()) {
+    ///     println!("-- {}: {}", migration.version(), migration.description());
+    ///     println!("{}", migration.sql());
+    ///
+    ///     if let Some(down_sql) = m.down_sql(migration.version()) {
+    ///         println!("-- reverted by:\n{down_sql}");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
     pub fn iter(&self) -> slice::Iter<'_, Migration> {
         self.migrations.iter()
     }
 
+    /// Get the SQL which reverts the migration with the given version.
+    ///
+    /// Returns `None` if there is no such migration or it is not reversible.
+    pub fn down_sql(&self, version: i64) -> Option<&str> {
+        self.iter()
+            .find(|m| m.version == version && m.migration_type.is_down_migration())
+            .map(Migration::sql)
+    }
+
     /// Check if a migration version exists.
     pub fn version_exists(&self, version: i64) -> bool {
         self.iter().any(|m| m.version == version)
     }
 
+    /// Check whether any migration in the source has not been applied yet.
+    ///
+    /// This is intended for long-lived processes that periodically poll for schema updates.
+    /// As an optimization it first compares the latest local version against the latest
+    /// applied version and only falls back to a full comparison if they differ. This assumes
+    /// migrations are applied contiguously: a missing migration older than the latest applied
+    /// one is not reported when the latest versions match.
+    pub fn has_pending(&self, applied: &[AppliedMigration]) -> bool {
+        let local_max = self
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| m.version)
+            .max();
+        let applied_max = applied.iter().map(|m| m.version).max();
+
+        if local_max == applied_max {
+            return false;
+        }
+
+        let applied: HashSet<_> = applied.iter().map(|m| m.version).collect();
+
+        self.iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .any(|m| !applied.contains(&m.version))
+    }
+
+    /// Compare these migrations against another set by version and checksum.
+    ///
+    /// Intended to check at startup that migrations embedded with `migrate!()` agree with the
+    /// migrations directory shipped alongside the application, e.g. to catch a stale build
+    /// which embedded an older version of the directory. Returns the differences ordered by
+    /// version; an empty result means both sets are the same.
+    pub fn diff(&self, other: &Migrator) -> Vec<MigrationDiff> {
+        let ours = by_version_and_direction(self);
+        let theirs = by_version_and_direction(other);
+
+        let keys: BTreeSet<_> = ours.keys().chain(theirs.keys()).copied().collect();
+
+        keys.into_iter()
+            .filter_map(|key| match (ours.get(&key), theirs.get(&key)) {
+                (Some(m), None) => Some(MigrationDiff::OnlyInSelf {
+                    version: m.version,
+                    migration_type: m.migration_type,
+                }),
+                (None, Some(m)) => Some(MigrationDiff::OnlyInOther {
+                    version: m.version,
+                    migration_type: m.migration_type,
+                }),
+                (Some(a), Some(b)) if a.checksum != b.checksum => {
+                    Some(MigrationDiff::ChecksumMismatch {
+                        version: a.version,
+                        migration_type: a.migration_type,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Run any pending migrations against the database; and, validate previously applied migrations
     /// against the current migration source to detect accidental changes in previously-applied migrations.
     ///
@@ -141,6 +400,10 @@ impl Migrator {
     where
         C: Migrate,
     {
+        if self.strict_versions {
+            self.validate_version_schemes()?;
+        }
+
         // lock the database for exclusive access by the migrator
         if self.locking {
             conn.lock().await?;
@@ -150,9 +413,13 @@ impl Migrator {
         // eventually this will likely migrate previous versions of the table
         conn.ensure_migrations_table(self.migration_table()).await?;
 
-        let version = conn.dirty_version(self.migration_table()).await?;
-        if let Some(version) = version {
-            return Err(MigrateError::Dirty(version));
+        if self.installed_on_index {
+            conn.ensure_installed_on_index(self.migration_table())
+                .await?;
+        }
+
+        if let Some(record) = conn.dirty_record(self.migration_table()).await? {
+            return Err(MigrateError::DirtyRecord(record));
         }
 
         let applied_migrations = conn.list_applied_migrations(self.migration_table()).await?;
@@ -170,13 +437,23 @@ impl Migrator {
 
             match applied_migrations.get(&migration.version) {
                 Some(applied_migration) => {
-                    if migration.checksum != applied_migration.checksum {
+                    // a migration applied before the migrations table recorded checksums has an
+                    // empty one, which is unknown rather than different
+                    if !applied_migration.checksum.is_empty()
+                        && migration.checksum != applied_migration.checksum
+                    {
                         return Err(MigrateError::VersionMismatch(migration.version));
                     }
                 }
-                None => {
-                    conn.apply(migration, self.migration_table()).await?;
-                }
+                None => match self.execution_time {
+                    ExecutionTime::Wall => {
+                        conn.apply(migration, self.migration_table()).await?;
+                    }
+                    ExecutionTime::Server => {
+                        conn.apply_server_timed(migration, self.migration_table())
+                            .await?;
+                    }
+                },
             }
         }
 
@@ -212,6 +489,117 @@ impl Migrator {
         <A::Connection as Deref>::Target: Migrate,
     {
         let mut conn = migrator.acquire().await?;
+        self.undo_direct(&mut *conn, target).await
+    }
+
+    /// Run any pending migrations on a connection opened by `connect`.
+    ///
+    /// `connect` is called once per run, which lets it fetch fresh credentials (e.g. from a
+    /// secrets manager with rotating passwords) instead of relying on a fixed URL or pool.
+    /// If the connection cannot be opened or is lost during the run, `connect` is called again
+    /// to reconnect and the run is retried, up to 3 times, waiting 100ms before the first
+    /// reconnect and twice as long before each further one. Migrations applied before the
+    /// connection was lost are not applied again, and one which left the database dirty fails
+    /// the retry with [`MigrateError::DirtyRecord`].
+    ///
+    /// On MySQL, a migration is recorded as failed before it runs, and its first DDL statement
+    /// commits that record implicitly. Losing the connection in the middle of a migration with
+    /// DDL therefore leaves the database dirty, and the retry fails with
+    /// [`MigrateError::DirtyRecord`] instead of applying the migration again.
+    ///
+    /// Each connection is closed when its run finishes, whether or not it succeeded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use sqlx::migrate::MigrateError;
+    /// # fn main() -> Result<(), MigrateError> {
+    /// #     sqlx::__rt::test_block_on(async move {
+    /// use sqlx::migrate::Migrator;
+    /// use sqlx::{Connection, SqliteConnection};
+    ///
+    /// let m = Migrator::new(std::path::Path::new("./migrations"), None).await?;
+    /// m.run_with_connector(|| SqliteConnection::connect("sqlite::memory:")).await
+    /// #     })
+    /// # }
+    /// ```
+    pub async fn run_with_connector<F, Fut, C>(&self, connect: F) -> Result<(), MigrateError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<C, Error>>,
+        C: Connection + Migrate,
+    {
+        self.with_connector(connect, None).await
+    }
+
+    /// Run down migrations until a specific version, on a connection opened by `connect`.
+    ///
+    /// See [`Migrator::run_with_connector`] for how `connect` is used.
+    pub async fn undo_with_connector<F, Fut, C>(
+        &self,
+        connect: F,
+        target: i64,
+    ) -> Result<(), MigrateError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<C, Error>>,
+        C: Connection + Migrate,
+    {
+        self.with_connector(connect, Some(target)).await
+    }
+
+    // Run pending migrations, or with `undo_target` undo them, reconnecting with `connect` after
+    // losing the connection.
+    async fn with_connector<F, Fut, C>(
+        &self,
+        mut connect: F,
+        undo_target: Option<i64>,
+    ) -> Result<(), MigrateError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<C, Error>>,
+        C: Connection + Migrate,
+    {
+        let mut reconnects = 0;
+        let mut backoff = RECONNECT_BACKOFF;
+
+        loop {
+            let res = match connect().await {
+                Ok(mut conn) => {
+                    let res = match undo_target {
+                        Some(target) => self.undo_direct(&mut conn, target).await,
+                        None => self.run_direct(&mut conn).await,
+                    };
+
+                    // Closing the connection also releases the migration lock if the run failed.
+                    let closed = conn.close().await;
+                    res.map(|()| closed)
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            match res {
+                Err(e) if reconnects < MAX_RECONNECTS && is_connection_error(&e) => {
+                    reconnects += 1;
+
+                    // give e.g. a restarting server or a failover some time
+                    crate::rt::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(e) => return Err(e),
+                Ok(closed) => return Ok(closed?),
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub async fn undo_direct<C>(&self, conn: &mut C, target: i64) -> Result<(), MigrateError>
+    where
+        C: Migrate,
+    {
+        if self.strict_versions {
+            self.validate_version_schemes()?;
+        }
 
         // lock the database for exclusive access by the migrator
         if self.locking {
@@ -222,9 +610,8 @@ impl Migrator {
         // eventually this will likely migrate previous versions of the table
         conn.ensure_migrations_table(self.migration_table()).await?;
 
-        let version = conn.dirty_version(self.migration_table()).await?;
-        if let Some(version) = version {
-            return Err(MigrateError::Dirty(version));
+        if let Some(record) = conn.dirty_record(self.migration_table()).await? {
+            return Err(MigrateError::DirtyRecord(record));
         }
 
         let applied_migrations = conn.list_applied_migrations(self.migration_table()).await?;
@@ -253,4 +640,70 @@ impl Migrator {
 
         Ok(())
     }
+
+    /// Get the migration which failed part-way through being applied, leaving the database
+    /// dirty, or `None` if the database is not dirty.
+    ///
+    /// This only reads the migrations table; a database without one is not dirty.
+    ///
+    /// Unlike [`Migrate::dirty_version`], this includes the local migration, e.g. for recovery
+    /// tooling to show what was partially applied.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use sqlx::migrate::MigrateError;
+    /// # fn main() -> Result<(), MigrateError> {
+    /// #     sqlx::__rt::test_block_on(async move {
+    /// use sqlx::migrate::Migrator;
+    /// use sqlx::sqlite::SqlitePoolOptions;
+    ///
+    /// let m = Migrator::new(std::path::Path::new("./migrations"), None).await?;
+    /// let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await?;
+    ///
+    /// if let Some(dirty) = m.dirty_migration(&pool).await? {
+    ///     println!("migration {} failed part-way", dirty.version);
+    ///
+    ///     if let Some(migration) = dirty.migration {
+    ///         println!("{}\n{}", migration.description, migration.sql);
+    ///     }
+    /// }
+    /// #         Ok(())
+    /// #     })
+    /// # }
+    /// ```
+    pub async fn dirty_migration<'a, A>(
+        &self,
+        migrator: A,
+    ) -> Result<Option<DirtyMigration<'_>>, MigrateError>
+    where
+        A: Acquire<'a>,
+        <A::Connection as Deref>::Target: Migrate,
+    {
+        let mut conn = migrator.acquire().await?;
+        self.dirty_migration_direct(&mut *conn).await
+    }
+
+    #[doc(hidden)]
+    pub async fn dirty_migration_direct<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Option<DirtyMigration<'_>>, MigrateError>
+    where
+        C: Migrate,
+    {
+        if !conn.migrations_table_exists(self.migration_table()).await? {
+            return Ok(None);
+        }
+
+        let Some(version) = conn.dirty_version(self.migration_table()).await? else {
+            return Ok(None);
+        };
+
+        let migration = self
+            .iter()
+            .find(|m| m.version == version && !m.migration_type.is_down_migration());
+
+        Ok(Some(DirtyMigration { version, migration }))
+    }
 }
@@ -1,3 +1,4 @@
+mod comments;
 mod error;
 #[allow(clippy::module_inception)]
 mod migrate;
@@ -8,9 +9,9 @@ mod source;
 
 pub use error::MigrateError;
 pub use migrate::{Migrate, MigrateDatabase};
-pub use migration::{AppliedMigration, Migration};
+pub use migration::{AppliedMigration, DirtyRecord, Migration, DIALECTS};
 pub use migration_type::MigrationType;
-pub use migrator::Migrator;
-pub use source::MigrationSource;
+pub use migrator::{DirtyMigration, ExecutionTime, MigrationDiff, Migrator};
+pub use source::{MigrationSource, HOOK_FILES};
 
 pub const DEFAULT_MIGRATION_TABLE: &str = "_sqlx_migrations";
\ No newline at end of file
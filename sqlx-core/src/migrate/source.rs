@@ -1,11 +1,19 @@
 use crate::error::BoxDynError;
 use crate::fs;
-use crate::migrate::{Migration, MigrationType};
+use crate::migrate::{Migration, MigrationType, DIALECTS};
+use crate::rt;
 use futures_core::future::BoxFuture;
 
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Files in a migrations directory which are not migrations: `sqlx migrate run` executes
+/// `before_each.sql` before and `after_each.sql` after each migration it applies.
+pub const HOOK_FILES: &[&str] = &["before_each.sql", "after_each.sql"];
 
 /// In the default implementation, a MigrationSource is a directory which
 /// contains the migration SQL scripts. All these scripts must be stored in
@@ -13,11 +21,21 @@ use std::path::{Path, PathBuf};
 /// `<VERSION>` is a string that can be parsed into `i64` and its value is
 /// greater than zero, and `<DESCRIPTION>` is a string.
 ///
-/// Files that don't match this format are silently ignored.
+/// Files that don't match this format are silently ignored, as are the [`HOOK_FILES`].
+///
+/// A migration may provide SQL specific to one database by naming the dialect before
+/// the extension, e.g. `0003_add_users.postgres.up.sql` (see [`DIALECTS`]). The variant
+/// matching the database is executed, falling back to the file without a dialect.
+/// Variants share the version and checksum of the logical migration, while
+/// `-- sqlx:` directives are read from the file that is executed.
 ///
 /// You can create a new empty migration script using sqlx-cli:
 /// `sqlx migrate add <DESCRIPTION>`.
 ///
+/// Migrations may also be spread over several directories, e.g. one per module of a
+/// monorepo, by resolving a slice or `Vec` of directories: their migrations are merged into a
+/// single sequence ordered by version, and a version may only be used in one directory.
+///
 /// Note that migrations for each database are tracked using the
 /// `_sqlx_migrations` table (stored in the database). If a migration's hash
 /// changes and it has already been run, this will cause an error.
@@ -28,50 +46,205 @@ pub trait MigrationSource<'s>: Debug {
 impl<'s> MigrationSource<'s> for &'s Path {
     fn resolve(self) -> BoxFuture<'s, Result<Vec<Migration>, BoxDynError>> {
         Box::pin(async move {
-            let mut s = fs::read_dir(self.canonicalize()?).await?;
+            let migrations = resolve_dir(self).await?;
+            Ok(migrations
+                .into_iter()
+                .map(|(migration, _)| migration)
+                .collect())
+        })
+    }
+}
+
+impl<'s> MigrationSource<'s> for &'s [PathBuf] {
+    fn resolve(self) -> BoxFuture<'s, Result<Vec<Migration>, BoxDynError>> {
+        Box::pin(async move {
             let mut migrations = Vec::new();
+            // the file each version was first found in, and the directory of that file
+            let mut found: HashMap<i64, (&Path, PathBuf)> = HashMap::new();
 
-            while let Some(entry) = s.next().await? {
-                // std::fs::metadata traverses symlinks
-                if !std::fs::metadata(&entry.path)?.is_file() {
-                    // not a file; ignore
-                    continue;
+            for dir in self {
+                for (migration, path) in resolve_dir(dir).await? {
+                    match found.get(&migration.version) {
+                        Some((other_dir, other_path)) if *other_dir != dir.as_path() => {
+                            return Err(format!(
+                                "migration version {} is used in more than one directory: \
+                                 {} and {}",
+                                migration.version,
+                                other_path.display(),
+                                path.display()
+                            )
+                            .into());
+                        }
+                        Some(_) => {}
+                        None => {
+                            found.insert(migration.version, (dir.as_path(), path));
+                        }
+                    }
+
+                    migrations.push(migration);
                 }
+            }
+
+            // stable, so that the up and down migrations of a version stay in order
+            migrations.sort_by_key(|m| m.version);
+
+            Ok(migrations)
+        })
+    }
+}
+
+impl MigrationSource<'static> for Vec<PathBuf> {
+    fn resolve(self) -> BoxFuture<'static, Result<Vec<Migration>, BoxDynError>> {
+        Box::pin(async move { self.as_slice().resolve().await })
+    }
+}
 
-                let file_name = entry.file_name.to_string_lossy();
+/// Resolve the migrations of a single directory, sorted by version, each with the path of one
+/// of its files.
+async fn resolve_dir(dir: &Path) -> Result<Vec<(Migration, PathBuf)>, BoxDynError> {
+    let mut s = fs::read_dir(dir.canonicalize()?).await?;
 
-                let parts = file_name.splitn(2, '_').collect::<Vec<_>>();
+    // dialect variants of a migration are separate files, grouped here by
+    // `(version, suffix, description)`
+    let mut grouped = BTreeMap::new();
 
-                if parts.len() != 2 || !parts[1].ends_with(".sql") {
-                    // not of the format: <VERSION>_<DESCRIPTION>.sql; ignore
-                    continue;
-                }
+    while let Some(entry) = s.next().await? {
+        // std::fs::metadata traverses symlinks
+        if !std::fs::metadata(&entry.path)?.is_file() {
+            // not a file; ignore
+            continue;
+        }
+
+        let file_name = entry.file_name.to_string_lossy();
+
+        if HOOK_FILES.contains(&&*file_name) {
+            // not a migration; ignore
+            continue;
+        }
+
+        let parts = file_name.splitn(2, '_').collect::<Vec<_>>();
+
+        if parts.len() != 2 || !parts[1].ends_with(".sql") {
+            // not of the format: <VERSION>_<DESCRIPTION>.sql; ignore
+            continue;
+        }
+
+        let version: i64 = parts[0].parse()?;
 
-                let version: i64 = parts[0].parse()?;
+        let migration_type = MigrationType::from_filename(parts[1]);
+        // remove the `.sql` and an optional `.<DIALECT>`
+        let description = parts[1].trim_end_matches(migration_type.suffix());
+        let (description, dialect) = match description.rsplit_once('.') {
+            Some((prefix, suffix)) => match DIALECTS.iter().find(|d| **d == suffix) {
+                Some(dialect) => (prefix, Some(*dialect)),
+                None => (description, None),
+            },
+            None => (description, None),
+        };
+        // replace `_` with ` `
+        let description = description.replace('_', " ");
 
-                let migration_type = MigrationType::from_filename(parts[1]);
-                // remove the `.sql` and replace `_` with ` `
-                let description = parts[1]
-                    .trim_end_matches(migration_type.suffix())
-                    .replace('_', " ")
-                    .to_owned();
+        let sql = fs::read_to_string(&entry.path).await?;
 
-                let sql = fs::read_to_string(&entry.path).await?;
+        let (_, sql_default, dialects, _) = grouped
+            .entry((version, migration_type.suffix(), description))
+            .or_insert((
+                migration_type,
+                String::new(),
+                Vec::new(),
+                entry.path.clone(),
+            ));
 
-                migrations.push(Migration::new(
+        match dialect {
+            Some(dialect) => dialects.push((Cow::Borrowed(dialect), Cow::Owned(sql))),
+            None => *sql_default = sql,
+        }
+    }
+
+    let (files, paths): (Vec<_>, Vec<_>) = grouped
+        .into_iter()
+        .map(
+            |((version, _, description), (migration_type, sql, dialects, path))| {
+                let file = MigrationFile {
                     version,
-                    Cow::Owned(description),
+                    description,
                     migration_type,
-                    Cow::Owned(sql),
-                ));
-            }
+                    sql,
+                    dialects,
+                };
+                (file, path)
+            },
+        )
+        .unzip();
 
-            // ensure that we are sorted by `VERSION ASC`
-            migrations.sort_by_key(|m| m.version);
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    // in the order of `files`, and so of `paths`
+    let migrations = rt::spawn_blocking(move || build_migrations(files, threads)).await;
+    let mut migrations: Vec<_> = migrations.into_iter().zip(paths).collect();
 
-            Ok(migrations)
-        })
+    // ensure that we are sorted by `VERSION ASC`
+    migrations.sort_by_key(|(m, _)| m.version);
+
+    Ok(migrations)
+}
+
+/// The contents of the files of a migration, before its checksum is computed.
+struct MigrationFile {
+    version: i64,
+    description: String,
+    migration_type: MigrationType,
+    sql: String,
+    dialects: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+}
+
+/// Below this many migrations per thread, computing the checksums on more threads is not worth
+/// spawning them.
+const MIN_MIGRATIONS_PER_THREAD: usize = 32;
+
+/// Build the migrations, computing their checksums on up to `threads` threads.
+///
+/// The migrations are returned in the order of `files`, however many threads are used.
+fn build_migrations(files: Vec<MigrationFile>, threads: usize) -> Vec<Migration> {
+    let build = |file: MigrationFile| {
+        Migration::with_dialects(
+            file.version,
+            Cow::Owned(file.description),
+            file.migration_type,
+            Cow::Owned(file.sql),
+            file.dialects,
+        )
+    };
+
+    let per_thread = std::cmp::max(
+        files.len().div_ceil(std::cmp::max(threads, 1)),
+        MIN_MIGRATIONS_PER_THREAD,
+    );
+    if files.len() <= per_thread {
+        return files.into_iter().map(build).collect();
     }
+
+    let mut chunks = Vec::new();
+    let mut files = files.into_iter();
+    loop {
+        let chunk: Vec<_> = files.by_ref().take(per_thread).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        chunks.push(chunk);
+    }
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || chunk.into_iter().map(build).collect::<Vec<_>>()))
+            .collect();
+
+        // joined in order, so that the result does not depend on scheduling
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("checksum thread panicked"))
+            .collect()
+    })
 }
 
 impl MigrationSource<'static> for PathBuf {
@@ -79,3 +252,51 @@ impl MigrationSource<'static> for PathBuf {
         Box::pin(async move { self.as_path().resolve().await })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{build_migrations, MigrationFile};
+    use crate::migrate::MigrationType;
+    use std::borrow::Cow;
+
+    fn files() -> Vec<MigrationFile> {
+        (1..=500)
+            .map(|version| MigrationFile {
+                version,
+                description: format!("migration {version}"),
+                migration_type: MigrationType::Simple,
+                sql: format!("INSERT INTO t VALUES ({version});\n").repeat(200),
+                dialects: if version % 7 == 0 {
+                    vec![(
+                        Cow::Borrowed("sqlite"),
+                        Cow::Owned(format!("SELECT {version};")),
+                    )]
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_checksums_match_serial() {
+        let serial = build_migrations(files(), 1);
+        let parallel = build_migrations(files(), 8);
+
+        assert_eq!(serial.len(), parallel.len());
+        for (serial, parallel) in serial.iter().zip(&parallel) {
+            assert_eq!(serial.version, parallel.version);
+            assert_eq!(serial.description, parallel.description);
+            assert_eq!(serial.sql, parallel.sql);
+            assert_eq!(serial.dialects, parallel.dialects);
+            assert_eq!(serial.checksum, parallel.checksum);
+        }
+    }
+
+    #[test]
+    fn few_migrations_keep_their_order() {
+        let migrations = build_migrations(files().into_iter().take(3).collect(), 8);
+        let versions: Vec<_> = migrations.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![1, 2, 3]);
+    }
+}
@@ -3,11 +3,12 @@ extern crate proc_macro;
 
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens, TokenStreamExt};
-use sha2::{Digest, Sha384};
-use sqlx_core::migrate::MigrationType;
+use sqlx_core::migrate::{Migration, MigrationType, DIALECTS, HOOK_FILES};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
-use syn::LitStr;
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, LitBool, LitStr, Token};
 
 pub struct QuotedMigrationType(MigrationType);
 
@@ -28,7 +29,10 @@ struct QuotedMigration {
     version: i64,
     description: String,
     migration_type: QuotedMigrationType,
-    path: String,
+    // `None` if the migration only has dialect variants
+    path: Option<String>,
+    // `(dialect, path)`, sorted by dialect
+    dialects: Vec<(String, String)>,
     checksum: Vec<u8>,
 }
 
@@ -39,26 +43,99 @@ impl ToTokens for QuotedMigration {
             description,
             migration_type,
             path,
+            dialects,
             checksum,
         } = &self;
 
+        // this tells the compiler to watch these paths for changes
+        let sql = match path {
+            Some(path) => quote! { include_str!(#path) },
+            None => quote! { "" },
+        };
+        let dialects = dialects.iter().map(|(dialect, path)| {
+            quote! {
+                (
+                    ::std::borrow::Cow::Borrowed(#dialect),
+                    ::std::borrow::Cow::Borrowed(include_str!(#path)),
+                )
+            }
+        });
+
         let ts = quote! {
-            ::sqlx::migrate::Migration {
-                version: #version,
-                description: ::std::borrow::Cow::Borrowed(#description),
-                migration_type:  #migration_type,
-                // this tells the compiler to watch this path for changes
-                sql: ::std::borrow::Cow::Borrowed(include_str!(#path)),
-                checksum: ::std::borrow::Cow::Borrowed(&[
+            ::sqlx::migrate::Migration::from_parts(
+                #version,
+                #description,
+                #migration_type,
+                #sql,
+                &[
+                    #(#dialects),*
+                ],
+                &[
                     #(#checksum),*
-                ]),
-            }
+                ],
+            )
         };
 
         tokens.append_all(ts.into_iter());
     }
 }
 
+/// The arguments of `migrate!()`: the migrations directory, optionally followed by
+/// `migration_table = "..."` and `strict_versions = true`.
+pub struct MigratorInput {
+    dir: LitStr,
+    migration_table: Option<LitStr>,
+    strict_versions: bool,
+}
+
+impl Parse for MigratorInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let dir = input.parse()?;
+        let mut migration_table = None;
+        let mut strict_versions = false;
+
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if key == "migration_table" {
+                let table: LitStr = input.parse()?;
+                if table.value().is_empty() {
+                    return Err(syn::Error::new_spanned(
+                        table,
+                        "migration table cannot be empty",
+                    ));
+                }
+
+                migration_table = Some(table);
+            } else if key == "strict_versions" {
+                strict_versions = input.parse::<LitBool>()?.value;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    "expected `migration_table` or `strict_versions`",
+                ));
+            }
+        }
+
+        Ok(MigratorInput {
+            dir,
+            migration_table,
+            strict_versions,
+        })
+    }
+}
+
+pub fn expand_migrator_from_input(input: MigratorInput) -> crate::Result<TokenStream> {
+    let path = crate::common::resolve_path(&input.dir.value(), input.dir.span())?;
+
+    expand_migrator_with_options(
+        &path,
+        input.migration_table.map(|table| table.value()),
+        input.strict_versions,
+    )
+}
+
 // mostly copied from sqlx-core/src/migrate/source.rs
 pub fn expand_migrator_from_lit_dir(dir: LitStr) -> crate::Result<TokenStream> {
     expand_migrator_from_dir(&dir.value(), dir.span())
@@ -74,7 +151,17 @@ pub(crate) fn expand_migrator_from_dir(
 }
 
 pub(crate) fn expand_migrator(path: &Path) -> crate::Result<TokenStream> {
-    let mut migrations = Vec::new();
+    expand_migrator_with_options(path, None, false)
+}
+
+fn expand_migrator_with_options(
+    path: &Path,
+    migration_table: Option<String>,
+    strict_versions: bool,
+) -> crate::Result<TokenStream> {
+    // dialect variants of a migration are separate files, grouped here by
+    // `(version, suffix, description)`
+    let mut grouped = BTreeMap::new();
 
     for entry in fs::read_dir(&path)? {
         let entry = entry?;
@@ -86,6 +173,11 @@ pub(crate) fn expand_migrator(path: &Path) -> crate::Result<TokenStream> {
         let file_name = entry.file_name();
         let file_name = file_name.to_string_lossy();
 
+        if HOOK_FILES.contains(&&*file_name) {
+            // not a migration; ignore
+            continue;
+        }
+
         let parts = file_name.splitn(2, '_').collect::<Vec<_>>();
 
         if parts.len() != 2 || !parts[1].ends_with(".sql") {
@@ -96,16 +188,17 @@ pub(crate) fn expand_migrator(path: &Path) -> crate::Result<TokenStream> {
         let version: i64 = parts[0].parse()?;
 
         let migration_type = MigrationType::from_filename(parts[1]);
-        // remove the `.sql` and replace `_` with ` `
-        let description = parts[1]
-            .trim_end_matches(migration_type.suffix())
-            .replace('_', " ")
-            .to_owned();
+        // remove the `.sql` and an optional `.<DIALECT>`
+        let description = parts[1].trim_end_matches(migration_type.suffix());
+        let (description, dialect) = match description.rsplit_once('.') {
+            Some((prefix, suffix)) if DIALECTS.contains(&suffix) => (prefix, Some(suffix)),
+            _ => (description, None),
+        };
+        // replace `_` with ` `
+        let description = description.replace('_', " ");
 
         let sql = fs::read_to_string(&entry.path())?;
 
-        let checksum = Vec::from(Sha384::digest(sql.as_bytes()).as_slice());
-
         // canonicalize the path so we can pass it to `include_str!()`
         let path = entry.path().canonicalize()?;
         let path = path
@@ -118,11 +211,38 @@ pub(crate) fn expand_migrator(path: &Path) -> crate::Result<TokenStream> {
             })?
             .to_owned();
 
+        let (_, default, dialects) = grouped
+            .entry((version, migration_type.suffix(), description))
+            .or_insert((migration_type, None, Vec::new()));
+
+        match dialect {
+            Some(dialect) => dialects.push((dialect.to_owned(), path, sql)),
+            None => *default = Some((path, sql)),
+        }
+    }
+
+    let mut migrations = Vec::new();
+
+    for ((version, _, description), (migration_type, default, mut dialects)) in grouped {
+        dialects.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let checksum = Migration::compute_checksum(
+            default.as_ref().map_or("", |(_, sql)| sql),
+            &dialects
+                .iter()
+                .map(|(dialect, _, sql)| (dialect, sql))
+                .collect::<Vec<_>>(),
+        );
+
         migrations.push(QuotedMigration {
             version,
             description,
             migration_type: QuotedMigrationType(migration_type),
-            path,
+            path: default.map(|(path, _)| path),
+            dialects: dialects
+                .into_iter()
+                .map(|(dialect, path, _)| (dialect, path))
+                .collect(),
             checksum,
         })
     }
@@ -143,13 +263,27 @@ pub(crate) fn expand_migrator(path: &Path) -> crate::Result<TokenStream> {
         proc_macro::tracked_path::path(path);
     }
 
-    Ok(quote! {
+    let migration_table = match migration_table {
+        Some(table) => quote! {
+            ::std::option::Option::Some(::std::borrow::Cow::Borrowed(#table))
+        },
+        None => quote! { ::std::option::Option::None },
+    };
+
+    // a `const` item, so that the migrations are `'static` wherever the macro is expanded
+    Ok(quote! {{
+        const MIGRATIONS: &[::sqlx::migrate::Migration] = &[
+            #(#migrations),*
+        ];
+
         ::sqlx::migrate::Migrator {
-            migrations: ::std::borrow::Cow::Borrowed(&[
-                #(#migrations),*
-            ]),
+            migrations: ::std::borrow::Cow::Borrowed(MIGRATIONS),
             ignore_missing: false,
             locking: true,
+            migration_table: #migration_table,
+            execution_time: ::sqlx::migrate::ExecutionTime::Wall,
+            installed_on_index: false,
+            strict_versions: #strict_versions,
         }
-    })
+    }})
 }
@@ -20,7 +20,9 @@ fn parse_for_maintenance(url: &str) -> Result<(MySqlConnectOptions, String), Err
         database.to_owned()
     } else {
         return Err(Error::Configuration(
-            "DATABASE_URL does not specify a database".into(),
+            "DATABASE_URL does not specify a database name, \
+             e.g. `mysql://user@localhost/my_database`"
+                .into(),
         ));
     };
 
@@ -72,14 +74,26 @@ impl MigrateDatabase for MySql {
             Ok(())
         })
     }
+
+    fn migrations_table_ddl(
+        _url: &str,
+        migration_table: &str,
+        installed_on_index: bool,
+    ) -> Result<Vec<String>, Error> {
+        let mut ddl = vec![create_migrations_table(migration_table)];
+        if installed_on_index {
+            ddl.push(create_installed_on_index(migration_table));
+        }
+
+        Ok(ddl)
+    }
 }
 
-impl Migrate for MySqlConnection {
-    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
-        Box::pin(async move {
-            // language=MySQL
-            self.execute(
-                format!(r#"
+// executed by `ensure_migrations_table`
+fn create_migrations_table(migration_table: &str) -> String {
+    // language=MySQL
+    format!(
+        r#"
 CREATE TABLE IF NOT EXISTS {migration_table} (
     version BIGINT PRIMARY KEY,
     description TEXT NOT NULL,
@@ -88,14 +102,317 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
     checksum BLOB NOT NULL,
     execution_time BIGINT NOT NULL
 );
-                "#).as_ref(),
+"#
+    )
+}
+
+// Columns added to the migrations table since its first layout, in order, with the definitions
+// `ensure_migrations_table` adds them to an older table with. Rows applied before the upgrade
+// get an empty checksum, the implicit default of a `BLOB` column, and an unknown execution
+// time of -1.
+const UPGRADE_COLUMNS: &[(&str, &str)] = &[
+    ("checksum", "BLOB NOT NULL"),
+    ("execution_time", "BIGINT NOT NULL DEFAULT -1"),
+];
+
+// executed by `ensure_installed_on_index` if the index does not exist yet
+fn create_installed_on_index(migration_table: &str) -> String {
+    // the index is created in the schema of its table, so its name is unqualified
+    let table_name = migration_table.rsplit('.').next().unwrap_or(migration_table);
+
+    // language=MySQL
+    format!("CREATE INDEX {table_name}_installed_on_idx ON {migration_table} (installed_on)")
+}
+
+/// Apply a migration with a `-- sqlx:batch-commit` directive, committing after every batch.
+///
+/// A migration with a `-- sqlx:no-transaction` directive is applied the same way, with every
+/// statement as a batch of its own executed outside of a transaction. The migration is only
+/// recorded as applied once every batch is committed.
+async fn apply_batched(
+    conn: &mut MySqlConnection,
+    migration: &Migration,
+    batches: Vec<String>,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    let start = Instant::now();
+
+    for batch in &batches {
+        let res = async {
+            if migration.no_tx("mysql") {
+                let _ = conn.execute(&**batch).await?;
+                return Ok(());
+            }
+
+            let mut tx = conn.begin().await?;
+            let _ = tx.execute(&**batch).await?;
+            tx.commit().await?;
+
+            Ok::<_, MigrateError>(())
+        }
+        .await;
+
+        if let Err(e) = res {
+            // DDL commits implicitly, so even a failed first batch may be partially applied
+            let _ = conn.mark_dirty(migration, migration_table).await;
+
+            return Err(e);
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=MySQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?, ?, TRUE, ?, ? )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .bind(elapsed.as_nanos() as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Revert a migration with a `-- sqlx:no-transaction` directive, executing its statements one at
+/// a time outside of a transaction.
+///
+/// If a statement fails, the statements before it stay applied: the migration's row is flagged
+/// as failed, leaving the database dirty on the partially reverted migration.
+async fn revert_no_tx(
+    conn: &mut MySqlConnection,
+    migration: &Migration,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    // single statements, rejecting directives which need a transaction
+    let statements = migration.sql_batches("mysql")?.unwrap_or_default();
+    let start = Instant::now();
+
+    for statement in &statements {
+        if let Err(e) = conn.execute(&**statement).await {
+            // language=MySQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET success = FALSE WHERE version = ?"
+            ))
+            .bind(migration.version)
+            .execute(&mut *conn)
+            .await;
+
+            return Err(e.into());
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=MySQL
+    let _ = query(&format!(
+        r#"DELETE FROM {migration_table} WHERE version = ?"#
+    ))
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Apply a migration in a single transaction, reporting progress before each statement if
+/// `progress` is given.
+async fn apply_migration(
+    conn: &mut MySqlConnection,
+    migration: &Migration,
+    migration_table: String,
+    mut progress: Option<&mut (dyn FnMut(usize, usize) + Send)>,
+) -> Result<Duration, MigrateError> {
+    if let Some(batches) = migration.sql_batches("mysql")? {
+        return apply_batched(conn, migration, batches, migration_table).await;
+    }
+
+    let sql = migration.sql_for("mysql")?;
+
+    // Use a single transaction for the actual migration script and the essential bookeeping so we never
+    // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
+    // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
+    // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
+    // and update it once the actual transaction completed.
+    let mut tx = conn.begin().await?;
+    let start = Instant::now();
+
+    // For MySQL we cannot really isolate migrations due to implicit commits caused by table modification, see
+    // https://dev.mysql.com/doc/refman/8.0/en/implicit-commit.html
+    //
+    // To somewhat try to detect this, we first insert the migration into the migration table with
+    // `success=FALSE` and later modify the flag.
+    //
+    // language=MySQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?, ?, FALSE, ?, -1 )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .execute(&mut *tx)
+    .await?;
+
+    // statements are executed one at a time, in file order, to report progress or the last
+    // checkpoint passed if one fails; SQL which cannot be split safely is executed whole,
+    // without reporting progress
+    let statements = migration.sql_checkpoints("mysql")?;
+    let has_checkpoints = statements
+        .iter()
+        .flatten()
+        .any(|(checkpoint, _)| checkpoint.is_some());
+
+    if let Some(statements) = statements.filter(|_| progress.is_some() || has_checkpoints) {
+        for (i, (checkpoint, statement)) in statements.iter().enumerate() {
+            if let Some(progress) = progress.as_mut() {
+                progress(i + 1, statements.len());
+            }
+
+            let res = tx.execute(*statement).await;
+            if has_checkpoints {
+                let _ = res.map_err(|e| migration.checkpoint_error(*checkpoint, e))?;
+            } else {
+                let _ = res?;
+            }
+        }
+    } else {
+        let _ = tx.execute(sql).await?;
+    }
+
+    // language=MySQL
+    let _ = query(&format!(
+        r#"
+    UPDATE {migration_table}
+    SET success = TRUE
+    WHERE version = ?
+        "#
+    ))
+    .bind(migration.version)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // Update `elapsed_time`.
+    // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
+    //       this small risk since this value is not super important.
+
+    let elapsed = start.elapsed();
+
+    let _ = query(&format!(
+        r#"
+    UPDATE {migration_table}
+    SET execution_time = ?
+    WHERE version = ?
+        "#
+    ))
+    .bind(elapsed.as_nanos() as i64)
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+impl Migrate for MySqlConnection {
+    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(&*create_migrations_table(&migration_table))
+                .await?;
+
+            let (schema, table_name) = match migration_table.split_once('.') {
+                Some((schema, name)) => (Some(schema), name),
+                None => (None, &*migration_table),
+            };
+
+            // a no-op for a table created above; upgrades the layout of an older one
+            // language=MySQL
+            let columns: Vec<String> = query_scalar(
+                "SELECT column_name FROM information_schema.columns \
+                 WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ?",
             )
+            .bind(schema)
+            .bind(table_name)
+            .fetch_all(&mut *self)
             .await?;
 
+            for (column, definition) in UPGRADE_COLUMNS {
+                if !columns.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+                    self.execute(&*format!(
+                        "ALTER TABLE {migration_table} ADD COLUMN {column} {definition}"
+                    ))
+                    .await?;
+                }
+            }
+
             Ok(())
         })
     }
 
+    fn ensure_installed_on_index(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            let (schema, table_name) = match migration_table.split_once('.') {
+                Some((schema, name)) => (Some(schema), name),
+                None => (None, &*migration_table),
+            };
+            let index = format!("{table_name}_installed_on_idx");
+
+            // MySQL has no `CREATE INDEX IF NOT EXISTS`
+            // language=MySQL
+            let (count,): (i64,) = query_as(
+                "SELECT COUNT(*) FROM information_schema.statistics \
+                 WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ? AND index_name = ?",
+            )
+            .bind(schema)
+            .bind(table_name)
+            .bind(index.as_str())
+            .fetch_one(&mut *self)
+            .await?;
+
+            if count == 0 {
+                self.execute(&*create_installed_on_index(&migration_table))
+                    .await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn migrations_table_exists(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<bool, MigrateError>> {
+        Box::pin(async move {
+            let (schema, table_name) = match migration_table.split_once('.') {
+                Some((schema, name)) => (Some(schema), name),
+                None => (None, &*migration_table),
+            };
+
+            // language=MySQL
+            let (count,): (i64,) = query_as(
+                "SELECT COUNT(*) FROM information_schema.tables \
+                 WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ?",
+            )
+            .bind(schema)
+            .bind(table_name)
+            .fetch_one(self)
+            .await?;
+
+            Ok(count > 0)
+        })
+    }
+
     fn dirty_version<'a>(&mut self, migration_table: String) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
         Box::pin(async move {
             // language=SQL
@@ -109,6 +426,86 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn dirty_record(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<DirtyRecord>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let row: Option<(i64, String, i64)> = query_as(&format!(
+                "SELECT version, description, execution_time FROM {migration_table} \
+                 WHERE success = false ORDER BY version LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, description, execution_time)| {
+                DirtyRecord::from_row(version, description, execution_time)
+            }))
+        })
+    }
+
+    fn clear_dirty_version(
+        &mut self,
+        version: i64,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=MySQL
+            let _ = query(&format!(
+                "DELETE FROM {migration_table} WHERE version = ? AND success = false"
+            ))
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn update_checksum(
+        &mut self,
+        version: i64,
+        checksum: Vec<u8>,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET checksum = ? WHERE version = ?"
+            ))
+            .bind(checksum)
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn mark_dirty<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=MySQL
+            let _ = query(
+                &format!(r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?, ?, FALSE, ?, -1 )
+                "#),
+            )
+            .bind(migration.version)
+            .bind(&*migration.description)
+            .bind(&*migration.checksum)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
     fn list_applied_migrations(
         &mut self, migration_table: String
     ) -> BoxFuture<'_, Result<Vec<AppliedMigration>, MigrateError>> {
@@ -131,6 +528,24 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn latest_applied_migration(
+        &mut self, migration_table: String
+    ) -> BoxFuture<'_, Result<Option<AppliedMigration>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let row: Option<(i64, Vec<u8>)> = query_as(&format!(
+                "SELECT version, checksum FROM {migration_table} ORDER BY version DESC LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, checksum)| AppliedMigration {
+                version,
+                checksum: checksum.into(),
+            }))
+        })
+    }
+
     fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async move {
             let database_name = current_database(self).await?;
@@ -152,6 +567,30 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn lock_with_timeout(&mut self, timeout: Duration) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            let database_name = current_database(self).await?;
+            let lock_id = generate_lock_id(&database_name);
+
+            // whole seconds, rounded up
+            let seconds = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+
+            // 1 if the lock was acquired, 0 on timeout
+            // language=MySQL
+            let locked: Option<i64> = query_scalar("SELECT GET_LOCK(?, ?)")
+                .bind(lock_id)
+                .bind(seconds)
+                .fetch_one(&mut *self)
+                .await?;
+
+            if locked != Some(1) {
+                return Err(MigrateError::LockTimeout(timeout));
+            }
+
+            Ok(())
+        })
+    }
+
     fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async move {
             let database_name = current_database(self).await?;
@@ -172,69 +611,39 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration: &'m Migration,
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
-        Box::pin(async move {
-            // Use a single transaction for the actual migration script and the essential bookeeping so we never
-            // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
-            // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
-            // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
-            // and update it once the actual transaction completed.
-            let mut tx = self.begin().await?;
-            let start = Instant::now();
+        Box::pin(apply_migration(self, migration, migration_table, None))
+    }
 
-            // For MySQL we cannot really isolate migrations due to implicit commits caused by table modification, see
-            // https://dev.mysql.com/doc/refman/8.0/en/implicit-commit.html
-            //
-            // To somewhat try to detect this, we first insert the migration into the migration table with
-            // `success=FALSE` and later modify the flag.
-            //
+    fn apply_with_progress<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+        progress: &'m mut (dyn FnMut(usize, usize) + Send),
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(apply_migration(self, migration, migration_table, Some(progress)))
+    }
+
+    fn record_applied<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // Nothing is executed, so there is no execution time to record.
             // language=MySQL
             let _ = query(
                 &format!(r#"
     INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
-    VALUES ( ?, ?, FALSE, ?, -1 )
+    VALUES ( ?, ?, TRUE, ?, 0 )
                 "#),
             )
             .bind(migration.version)
             .bind(&*migration.description)
             .bind(&*migration.checksum)
-            .execute(&mut *tx)
-            .await?;
-
-            let _ = tx.execute(&*migration.sql).await?;
-
-            // language=MySQL
-            let _ = query(
-                &format!(r#"
-    UPDATE {migration_table}
-    SET success = TRUE
-    WHERE version = ?
-                "#),
-            )
-            .bind(migration.version)
-            .execute(&mut *tx)
-            .await?;
-
-            tx.commit().await?;
-
-            // Update `elapsed_time`.
-            // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
-            //       this small risk since this value is not super important.
-
-            let elapsed = start.elapsed();
-
-            let _ = query(
-                &format!(r#"
-    UPDATE {migration_table}
-    SET execution_time = ?
-    WHERE version = ?
-                "#),
-            )
-            .bind(elapsed.as_nanos() as i64)
-            .bind(migration.version)
             .execute(self)
             .await?;
 
-            Ok(elapsed)
+            Ok(())
         })
     }
 
@@ -244,6 +653,12 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
         Box::pin(async move {
+            if migration.no_tx("mysql") {
+                return revert_no_tx(self, migration, migration_table).await;
+            }
+
+            let sql = migration.sql_for("mysql")?;
+
             // Use a single transaction for the actual migration script and the essential bookeeping so we never
             // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
             let mut tx = self.begin().await?;
@@ -267,7 +682,7 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
             .execute(&mut *tx)
             .await?;
 
-            tx.execute(&*migration.sql).await?;
+            tx.execute(sql).await?;
 
             // language=SQL
             let _ = query(&format!(r#"DELETE FROM {migration_table} WHERE version = ?"#))
@@ -5,7 +5,7 @@ use std::time::Instant;
 use futures_core::future::BoxFuture;
 
 pub(crate) use sqlx_core::migrate::MigrateError;
-pub(crate) use sqlx_core::migrate::{AppliedMigration, Migration};
+pub(crate) use sqlx_core::migrate::{AppliedMigration, DirtyRecord, Migration};
 pub(crate) use sqlx_core::migrate::{Migrate, MigrateDatabase};
 
 use crate::connection::{ConnectOptions, Connection};
@@ -19,12 +19,18 @@ use crate::{PgConnectOptions, PgConnection, Postgres};
 fn parse_for_maintenance(url: &str) -> Result<(PgConnectOptions, String), Error> {
     let mut options = PgConnectOptions::from_str(url)?;
 
-    // pull out the name of the database to create
-    let database = options
-        .database
-        .as_deref()
-        .unwrap_or(&options.username)
-        .to_owned();
+    // pull out the name of the database to create; unlike when connecting, don't fall back
+    // to the username, so that a forgotten name doesn't create or drop the user's database
+    let database = match &options.database {
+        Some(database) => database.to_owned(),
+        None => {
+            return Err(Error::Configuration(
+                "DATABASE_URL does not specify a database name, \
+                 e.g. `postgres://user@localhost/my_database`"
+                    .into(),
+            ))
+        }
+    };
 
     // switch us to the maintenance database
     // use `postgres` _unless_ the database is postgres, in which case, use `template1`
@@ -108,14 +114,26 @@ impl MigrateDatabase for Postgres {
             Self::drop_database(url).await
         })
     }
+
+    fn migrations_table_ddl(
+        _url: &str,
+        migration_table: &str,
+        installed_on_index: bool,
+    ) -> Result<Vec<String>, Error> {
+        let mut ddl = vec![create_migrations_table(migration_table)];
+        if installed_on_index {
+            ddl.push(create_installed_on_index(migration_table));
+        }
+
+        Ok(ddl)
+    }
 }
 
-impl Migrate for PgConnection {
-    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
-        Box::pin(async move {
-            // language=SQL
-            self.execute(
-                format!(r#"
+// executed by `ensure_migrations_table`
+fn create_migrations_table(migration_table: &str) -> String {
+    // language=SQL
+    format!(
+        r#"
 CREATE TABLE IF NOT EXISTS {migration_table} (
     version BIGINT PRIMARY KEY,
     description TEXT NOT NULL,
@@ -124,14 +142,325 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
     checksum BYTEA NOT NULL,
     execution_time BIGINT NOT NULL
 );
-                "#).as_ref(),
+"#
+    )
+}
+
+// Columns added to the migrations table since its first layout, in order, with the definitions
+// `ensure_migrations_table` adds them to an older table with. Rows applied before the upgrade
+// get an empty checksum and an unknown execution time of -1.
+const UPGRADE_COLUMNS: &[(&str, &str)] = &[
+    ("checksum", "BYTEA NOT NULL DEFAULT ''::bytea"),
+    ("execution_time", "BIGINT NOT NULL DEFAULT -1"),
+];
+
+// executed by `ensure_installed_on_index`
+fn create_installed_on_index(migration_table: &str) -> String {
+    // the index is created in the schema of its table, so its name is unqualified
+    let table_name = migration_table.rsplit('.').next().unwrap_or(migration_table);
+
+    // language=SQL
+    format!(
+        "CREATE INDEX IF NOT EXISTS {table_name}_installed_on_idx \
+         ON {migration_table} (installed_on)"
+    )
+}
+
+// Apply a `-- sqlx:lock-timeout` directive for the rest of the migration's transaction.
+async fn set_lock_timeout(
+    conn: &mut PgConnection,
+    migration: &Migration,
+) -> Result<(), MigrateError> {
+    if let Some(timeout) = migration.lock_timeout("postgres")? {
+        // `SET` does not accept bind parameters; the value is a plain integer of milliseconds
+        // language=SQL
+        let _ = conn
+            .execute(&*format!("SET LOCAL lock_timeout = {}", timeout.as_millis()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Apply a migration with a `-- sqlx:batch-commit` directive, committing after every batch.
+///
+/// A migration with a `-- sqlx:no-transaction` directive is applied the same way, with every
+/// statement as a batch of its own executed outside of a transaction. The migration is only
+/// recorded as applied once every batch is committed.
+async fn apply_batched(
+    conn: &mut PgConnection,
+    migration: &Migration,
+    batches: Vec<String>,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    let start = Instant::now();
+
+    for (i, batch) in batches.iter().enumerate() {
+        let res = async {
+            if migration.no_tx("postgres") {
+                let _ = conn.execute(&**batch).await?;
+                return Ok(());
+            }
+
+            let mut tx = conn.begin().await?;
+            set_lock_timeout(&mut tx, migration).await?;
+            let _ = tx.execute(&**batch).await?;
+            tx.commit().await?;
+
+            Ok::<_, MigrateError>(())
+        }
+        .await;
+
+        if let Err(e) = res {
+            // the batches before are committed, and a statement outside of a transaction
+            // may be partially applied: flag the partially applied migration
+            if i > 0 || migration.no_tx("postgres") {
+                let _ = conn.mark_dirty(migration, migration_table).await;
+            }
+
+            return Err(e);
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( $1, $2, TRUE, $3, $4 )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .bind(elapsed.as_nanos() as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Revert a migration with a `-- sqlx:no-transaction` directive, executing its statements one at
+/// a time outside of a transaction.
+///
+/// If a statement fails, the statements before it stay applied: the migration's row is flagged
+/// as failed, leaving the database dirty on the partially reverted migration.
+async fn revert_no_tx(
+    conn: &mut PgConnection,
+    migration: &Migration,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    // single statements, rejecting directives which need a transaction
+    let statements = migration.sql_batches("postgres")?.unwrap_or_default();
+    let start = Instant::now();
+
+    for statement in &statements {
+        if let Err(e) = conn.execute(&**statement).await {
+            // language=SQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET success = FALSE WHERE version = $1"
+            ))
+            .bind(migration.version)
+            .execute(&mut *conn)
+            .await;
+
+            return Err(e.into());
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"DELETE FROM {migration_table} WHERE version = $1"#
+    ))
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Apply a migration in a single transaction, reporting progress before each statement if
+/// `progress` is given.
+///
+/// With `server_timed`, the execution time is measured with the server clock and recorded in
+/// the same transaction instead of being updated after commit.
+async fn apply_migration(
+    conn: &mut PgConnection,
+    migration: &Migration,
+    migration_table: String,
+    mut progress: Option<&mut (dyn FnMut(usize, usize) + Send)>,
+    server_timed: bool,
+) -> Result<Duration, MigrateError> {
+    // committed in several transactions, or none, so always timed on the client
+    if let Some(batches) = migration.sql_batches("postgres")? {
+        return apply_batched(conn, migration, batches, migration_table).await;
+    }
+
+    let mut tx = conn.begin().await?;
+    set_lock_timeout(&mut tx, migration).await?;
+    let start = Instant::now();
+
+    // Read the server clock on either side of the migration script. Unlike the client-side
+    // time, it is known before commit.
+    let started_at: Option<i64> = if server_timed {
+        // language=SQL
+        Some(
+            query_scalar("SELECT (EXTRACT(EPOCH FROM clock_timestamp()) * 1000000)::BIGINT")
+                .fetch_one(&mut *tx)
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    // Use a single transaction for the actual migration script and the essential bookeeping so we never
+    // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
+    // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
+    // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
+    // and update it once the actual transaction completed.
+
+    // statements are executed one at a time, in file order, to report progress or the last
+    // checkpoint passed if one fails; SQL which cannot be split safely is executed whole,
+    // without reporting progress
+    let statements = migration.sql_checkpoints("postgres")?;
+    let has_checkpoints = statements
+        .iter()
+        .flatten()
+        .any(|(checkpoint, _)| checkpoint.is_some());
+
+    if let Some(statements) = statements.filter(|_| progress.is_some() || has_checkpoints) {
+        for (i, (checkpoint, statement)) in statements.iter().enumerate() {
+            if let Some(progress) = progress.as_mut() {
+                progress(i + 1, statements.len());
+            }
+
+            let res = tx.execute(*statement).await;
+            if has_checkpoints {
+                let _ = res.map_err(|e| migration.checkpoint_error(*checkpoint, e))?;
+            } else {
+                let _ = res?;
+            }
+        }
+    } else {
+        let _ = tx.execute(migration.sql_for("postgres")?).await?;
+    }
+
+    let server_elapsed = match started_at {
+        Some(started_at) => {
+            // language=SQL
+            let elapsed_micros: i64 = query_scalar(
+                "SELECT (EXTRACT(EPOCH FROM clock_timestamp()) * 1000000)::BIGINT - $1",
+            )
+            .bind(started_at)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            Some(Duration::from_micros(std::cmp::max(elapsed_micros, 0) as u64))
+        }
+        None => None,
+    };
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( $1, $2, TRUE, $3, $4 )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .bind(server_elapsed.map_or(-1, |elapsed| elapsed.as_nanos() as i64))
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    if let Some(elapsed) = server_elapsed {
+        return Ok(elapsed);
+    }
+
+    // Update `elapsed_time`.
+    // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
+    //       this small risk since this value is not super important.
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    UPDATE {migration_table}
+    SET execution_time = $1
+    WHERE version = $2
+        "#
+    ))
+    .bind(elapsed.as_nanos() as i64)
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+impl Migrate for PgConnection {
+    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(&*create_migrations_table(&migration_table))
+                .await?;
+
+            // a no-op for a table created above; upgrades the layout of an older one
+            // language=SQL
+            let columns: Vec<String> = query_scalar(
+                "SELECT attname::text FROM pg_attribute \
+                 WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped",
             )
+            .bind(&migration_table)
+            .fetch_all(&mut *self)
             .await?;
 
+            for (column, definition) in UPGRADE_COLUMNS {
+                if !columns.iter().any(|c| c == column) {
+                    self.execute(&*format!(
+                        "ALTER TABLE {migration_table} ADD COLUMN {column} {definition}"
+                    ))
+                    .await?;
+                }
+            }
+
             Ok(())
         })
     }
 
+    fn ensure_installed_on_index(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(&*create_installed_on_index(&migration_table))
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn migrations_table_exists(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<bool, MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let exists: bool = query_scalar("SELECT to_regclass($1) IS NOT NULL")
+                .bind(&migration_table)
+                .fetch_one(self)
+                .await?;
+
+            Ok(exists)
+        })
+    }
+
     fn dirty_version(&mut self, migration_table: String) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
         Box::pin(async move {
             // language=SQL
@@ -145,6 +474,86 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn dirty_record(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<DirtyRecord>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let row: Option<(i64, String, i64)> = query_as(&format!(
+                "SELECT version, description, execution_time FROM {migration_table} \
+                 WHERE success = false ORDER BY version LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, description, execution_time)| {
+                DirtyRecord::from_row(version, description, execution_time)
+            }))
+        })
+    }
+
+    fn clear_dirty_version(
+        &mut self,
+        version: i64,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let _ = query(&format!(
+                "DELETE FROM {migration_table} WHERE version = $1 AND success = false"
+            ))
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn update_checksum(
+        &mut self,
+        version: i64,
+        checksum: Vec<u8>,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET checksum = $1 WHERE version = $2"
+            ))
+            .bind(checksum)
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn mark_dirty<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let _ = query(
+                &format!(r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( $1, $2, FALSE, $3, -1 )
+                "#),
+            )
+            .bind(migration.version)
+            .bind(&*migration.description)
+            .bind(&*migration.checksum)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
     fn list_applied_migrations(
         &mut self,
         migration_table: String,
@@ -168,6 +577,25 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn latest_applied_migration(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<AppliedMigration>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let row: Option<(i64, Vec<u8>)> = query_as(&format!(
+                "SELECT version, checksum FROM {migration_table} ORDER BY version DESC LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, checksum)| AppliedMigration {
+                version,
+                checksum: checksum.into(),
+            }))
+        })
+    }
+
     fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async move {
             let database_name = current_database(self).await?;
@@ -189,6 +617,34 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn lock_with_timeout(&mut self, timeout: Duration) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            let database_name = current_database(self).await?;
+            let lock_id = generate_lock_id(&database_name);
+            let deadline = Instant::now() + timeout;
+
+            // poll rather than wait in `pg_advisory_lock`, which would need `lock_timeout` to be
+            // changed for the session
+            loop {
+                // language=SQL
+                let locked: bool = query_scalar("SELECT pg_try_advisory_lock($1)")
+                    .bind(lock_id)
+                    .fetch_one(&mut *self)
+                    .await?;
+
+                if locked {
+                    return Ok(());
+                }
+
+                if Instant::now() >= deadline {
+                    return Err(MigrateError::LockTimeout(timeout));
+                }
+
+                crate::rt::sleep(Duration::from_millis(100)).await;
+            }
+        })
+    }
+
     fn unlock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async move {
             let database_name = current_database(self).await?;
@@ -209,52 +665,47 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration: &'m Migration,
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
-        Box::pin(async move {
-            let mut tx = self.begin().await?;
-            let start = Instant::now();
+        Box::pin(apply_migration(self, migration, migration_table, None, false))
+    }
 
-            // Use a single transaction for the actual migration script and the essential bookeeping so we never
-            // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
-            // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
-            // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
-            // and update it once the actual transaction completed.
-            let _ = tx.execute(&*migration.sql).await?;
+    fn apply_with_progress<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+        progress: &'m mut (dyn FnMut(usize, usize) + Send),
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(apply_migration(self, migration, migration_table, Some(progress), false))
+    }
+
+    fn apply_server_timed<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(apply_migration(self, migration, migration_table, None, true))
+    }
 
+    fn record_applied<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // Nothing is executed, so there is no execution time to record.
             // language=SQL
             let _ = query(
                 &format!(r#"
     INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
-    VALUES ( $1, $2, TRUE, $3, -1 )
+    VALUES ( $1, $2, TRUE, $3, 0 )
                 "#),
             )
             .bind(migration.version)
             .bind(&*migration.description)
             .bind(&*migration.checksum)
-            .execute(&mut *tx)
-            .await?;
-
-            tx.commit().await?;
-
-            // Update `elapsed_time`.
-            // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
-            //       this small risk since this value is not super important.
-
-            let elapsed = start.elapsed();
-
-            // language=SQL
-            let _ = query(
-                &format!(r#"
-    UPDATE {migration_table}
-    SET execution_time = $1
-    WHERE version = $2
-                "#),
-            )
-            .bind(elapsed.as_nanos() as i64)
-            .bind(migration.version)
             .execute(self)
             .await?;
 
-            Ok(elapsed)
+            Ok(())
         })
     }
 
@@ -264,12 +715,17 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
         Box::pin(async move {
+            if migration.no_tx("postgres") {
+                return revert_no_tx(self, migration, migration_table).await;
+            }
+
             // Use a single transaction for the actual migration script and the essential bookeeping so we never
             // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
             let mut tx = self.begin().await?;
+            set_lock_timeout(&mut tx, migration).await?;
             let start = Instant::now();
 
-            let _ = tx.execute(&*migration.sql).await?;
+            let _ = tx.execute(migration.sql_for("postgres")?).await?;
 
             // language=SQL
             let _ = query(&format!(r#"DELETE FROM {migration_table} WHERE version = $1"#))
@@ -16,6 +16,7 @@ use sqlx_core::connection::{ConnectOptions, Connection};
 use sqlx_core::database::Database;
 use sqlx_core::describe::Describe;
 use sqlx_core::executor::Executor;
+use sqlx_core::row::Row;
 use sqlx_core::transaction::TransactionManager;
 
 sqlx_core::declare_driver_with_optional_migrate!(DRIVER = Sqlite);
@@ -179,7 +180,17 @@ impl<'a> TryFrom<&'a SqliteRow> for AnyRow {
     type Error = sqlx_core::Error;
 
     fn try_from(row: &'a SqliteRow) -> Result<Self, Self::Error> {
-        AnyRow::map_from(row, row.column_names.clone())
+        let mut any_row = AnyRow::map_from(row, row.column_names.clone())?;
+
+        // SQLite reports every integer value as `Int`, which `map_from` reads as an `i32`, but
+        // stores them in up to 64 bits
+        for (i, value) in any_row.values.iter_mut().enumerate() {
+            if let AnyValueKind::Integer(_) = value.kind {
+                value.kind = AnyValueKind::BigInt(row.try_get(i)?);
+            }
+        }
+
+        Ok(any_row)
     }
 }
 
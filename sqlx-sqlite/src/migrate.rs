@@ -3,7 +3,7 @@ use crate::error::Error;
 use crate::executor::Executor;
 use crate::fs;
 use crate::migrate::MigrateError;
-use crate::migrate::{AppliedMigration, Migration};
+use crate::migrate::{AppliedMigration, DirtyRecord, Migration};
 use crate::migrate::{Migrate, MigrateDatabase};
 use crate::query::query;
 use crate::query_as::query_as;
@@ -27,6 +27,15 @@ impl MigrateDatabase for Sqlite {
                 opts = opts.journal_mode(SqliteJournalMode::Wal);
             }
 
+            // SQLite creates the file but not its parent directories
+            if !opts.in_memory {
+                if let Some(parent) = opts.filename.parent() {
+                    if !parent.as_os_str().is_empty() {
+                        fs::create_dir_all(parent).await?;
+                    }
+                }
+            }
+
             // Opening a connection to sqlite creates the database
             let _ = opts
                 .connect()
@@ -62,14 +71,26 @@ impl MigrateDatabase for Sqlite {
             Ok(())
         })
     }
+
+    fn migrations_table_ddl(
+        _url: &str,
+        migration_table: &str,
+        installed_on_index: bool,
+    ) -> Result<Vec<String>, Error> {
+        let mut ddl = vec![create_migrations_table(migration_table)];
+        if installed_on_index {
+            ddl.push(create_installed_on_index(migration_table));
+        }
+
+        Ok(ddl)
+    }
 }
 
-impl Migrate for SqliteConnection {
-    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
-        Box::pin(async move {
-            // language=SQLite
-            self.execute(
-                format!(r#"
+// executed by `ensure_migrations_table`
+fn create_migrations_table(migration_table: &str) -> String {
+    // language=SQLite
+    format!(
+        r#"
 CREATE TABLE IF NOT EXISTS {migration_table} (
     version BIGINT PRIMARY KEY,
     description TEXT NOT NULL,
@@ -78,14 +99,280 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
     checksum BLOB NOT NULL,
     execution_time BIGINT NOT NULL
 );
-                "#).as_str(),
-            )
-            .await?;
+"#
+    )
+}
+
+// Columns added to the migrations table since its first layout, in order, with the definitions
+// `ensure_migrations_table` adds them to an older table with. SQLite requires a default for a
+// `NOT NULL` column added to an existing table: rows applied before the upgrade get an empty
+// checksum and an unknown execution time of -1.
+const UPGRADE_COLUMNS: &[(&str, &str)] = &[
+    ("checksum", "BLOB NOT NULL DEFAULT x''"),
+    ("execution_time", "BIGINT NOT NULL DEFAULT -1"),
+];
+
+// executed by `ensure_installed_on_index`
+fn create_installed_on_index(migration_table: &str) -> String {
+    // SQLite qualifies the index name with the schema, and not the table name
+    let (index, table_name) = match migration_table.split_once('.') {
+        Some((schema, name)) => (format!("{schema}.{name}_installed_on_idx"), name),
+        None => (format!("{migration_table}_installed_on_idx"), migration_table),
+    };
+
+    // language=SQLite
+    format!("CREATE INDEX IF NOT EXISTS {index} ON {table_name} (installed_on)")
+}
+
+/// Apply a migration with a `-- sqlx:batch-commit` directive, committing after every batch.
+///
+/// A migration with a `-- sqlx:no-transaction` directive is applied the same way, with every
+/// statement as a batch of its own executed outside of a transaction. The migration is only
+/// recorded as applied once every batch is committed.
+async fn apply_batched(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+    batches: Vec<String>,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    let start = Instant::now();
+
+    for (i, batch) in batches.iter().enumerate() {
+        let res = async {
+            if migration.no_tx("sqlite") {
+                let _ = conn.execute(&**batch).await?;
+                return Ok(());
+            }
+
+            let mut tx = conn.begin().await?;
+            let _ = tx.execute(&**batch).await?;
+            tx.commit().await?;
+
+            Ok::<_, MigrateError>(())
+        }
+        .await;
+
+        if let Err(e) = res {
+            // the batches before are committed, and a statement outside of a transaction
+            // may be partially applied: flag the partially applied migration
+            if i > 0 || migration.no_tx("sqlite") {
+                let _ = conn.mark_dirty(migration, migration_table).await;
+            }
+
+            return Err(e);
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?1, ?2, TRUE, ?3, ?4 )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .bind(elapsed.as_nanos() as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Revert a migration with a `-- sqlx:no-transaction` directive, executing its statements one at
+/// a time outside of a transaction.
+///
+/// If a statement fails, the statements before it stay applied: the migration's row is flagged
+/// as failed, leaving the database dirty on the partially reverted migration.
+async fn revert_no_tx(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+    migration_table: String,
+) -> Result<Duration, MigrateError> {
+    // single statements, rejecting directives which need a transaction
+    let statements = migration.sql_batches("sqlite")?.unwrap_or_default();
+    let start = Instant::now();
+
+    for statement in &statements {
+        if let Err(e) = conn.execute(&**statement).await {
+            // language=SQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET success = FALSE WHERE version = ?1"
+            ))
+            .bind(migration.version)
+            .execute(&mut *conn)
+            .await;
+
+            return Err(e.into());
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"DELETE FROM {migration_table} WHERE version = ?1"#
+    ))
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+/// Apply a migration in a single transaction, reporting progress before each statement if
+/// `progress` is given.
+async fn apply_migration(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+    migration_table: String,
+    mut progress: Option<&mut (dyn FnMut(usize, usize) + Send)>,
+) -> Result<Duration, MigrateError> {
+    if let Some(batches) = migration.sql_batches("sqlite")? {
+        return apply_batched(conn, migration, batches, migration_table).await;
+    }
+
+    let mut tx = conn.begin().await?;
+    let start = Instant::now();
+
+    // Use a single transaction for the actual migration script and the essential bookeeping so we never
+    // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
+    // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
+    // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
+    // and update it once the actual transaction completed.
+
+    // statements are executed one at a time, in file order, to report progress or the last
+    // checkpoint passed if one fails; SQL which cannot be split safely is executed whole,
+    // without reporting progress
+    let statements = migration.sql_checkpoints("sqlite")?;
+    let has_checkpoints = statements
+        .iter()
+        .flatten()
+        .any(|(checkpoint, _)| checkpoint.is_some());
+
+    if let Some(statements) = statements.filter(|_| progress.is_some() || has_checkpoints) {
+        for (i, (checkpoint, statement)) in statements.iter().enumerate() {
+            if let Some(progress) = progress.as_mut() {
+                progress(i + 1, statements.len());
+            }
+
+            let res = tx.execute(*statement).await;
+            if has_checkpoints {
+                let _ = res.map_err(|e| migration.checkpoint_error(*checkpoint, e))?;
+            } else {
+                let _ = res?;
+            }
+        }
+    } else {
+        let _ = tx.execute(migration.sql_for("sqlite")?).await?;
+    }
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?1, ?2, TRUE, ?3, -1 )
+        "#
+    ))
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // Update `elapsed_time`.
+    // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
+    //       this small risk since this value is not super important.
+
+    let elapsed = start.elapsed();
+
+    // language=SQL
+    let _ = query(&format!(
+        r#"
+    UPDATE {migration_table}
+    SET execution_time = ?1
+    WHERE version = ?2
+        "#
+    ))
+    .bind(elapsed.as_nanos() as i64)
+    .bind(migration.version)
+    .execute(conn)
+    .await?;
+
+    Ok(elapsed)
+}
+
+impl Migrate for SqliteConnection {
+    fn ensure_migrations_table(&mut self, migration_table: String) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(&*create_migrations_table(&migration_table))
+                .await?;
+
+            let (schema, table_name) = match migration_table.split_once('.') {
+                Some((schema, name)) => (schema, name),
+                None => ("main", &*migration_table),
+            };
+
+            // a no-op for a table created above; upgrades the layout of an older one
+            // language=SQLite
+            let columns: Vec<(String,)> = query_as("SELECT name FROM pragma_table_info(?, ?)")
+                .bind(table_name)
+                .bind(schema)
+                .fetch_all(&mut *self)
+                .await?;
+
+            for (column, definition) in UPGRADE_COLUMNS {
+                if !columns.iter().any(|(c,)| c.eq_ignore_ascii_case(column)) {
+                    self.execute(&*format!(
+                        "ALTER TABLE {migration_table} ADD COLUMN {column} {definition}"
+                    ))
+                    .await?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn ensure_installed_on_index(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            self.execute(&*create_installed_on_index(&migration_table))
+                .await?;
 
             Ok(())
         })
     }
 
+    fn migrations_table_exists(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<bool, MigrateError>> {
+        Box::pin(async move {
+            let (schema, table_name) = match migration_table.split_once('.') {
+                Some((schema, name)) => (schema, name),
+                None => ("main", &*migration_table),
+            };
+
+            // a table without columns cannot exist
+            // language=SQLite
+            let (count,): (i64,) = query_as("SELECT COUNT(*) FROM pragma_table_info(?, ?)")
+                .bind(table_name)
+                .bind(schema)
+                .fetch_one(self)
+                .await?;
+
+            Ok(count > 0)
+        })
+    }
+
     fn dirty_version(&mut self, migration_table: String) -> BoxFuture<'_, Result<Option<i64>, MigrateError>> {
         Box::pin(async move {
             // language=SQLite
@@ -99,6 +386,86 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn dirty_record(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<DirtyRecord>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQLite
+            let row: Option<(i64, String, i64)> = query_as(&format!(
+                "SELECT version, description, execution_time FROM {migration_table} \
+                 WHERE success = false ORDER BY version LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, description, execution_time)| {
+                DirtyRecord::from_row(version, description, execution_time)
+            }))
+        })
+    }
+
+    fn clear_dirty_version(
+        &mut self,
+        version: i64,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQLite
+            let _ = query(&format!(
+                "DELETE FROM {migration_table} WHERE version = ?1 AND success = false"
+            ))
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn update_checksum(
+        &mut self,
+        version: i64,
+        checksum: Vec<u8>,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQL
+            let _ = query(&format!(
+                "UPDATE {migration_table} SET checksum = ?1 WHERE version = ?2"
+            ))
+            .bind(checksum)
+            .bind(version)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn mark_dirty<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // language=SQLite
+            let _ = query(
+                &format!(r#"
+    INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
+    VALUES ( ?1, ?2, FALSE, ?3, -1 )
+                "#),
+            )
+            .bind(migration.version)
+            .bind(&*migration.description)
+            .bind(&*migration.checksum)
+            .execute(self)
+            .await?;
+
+            Ok(())
+        })
+    }
+
     fn list_applied_migrations(
         &mut self,
         migration_table: String,
@@ -122,6 +489,25 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         })
     }
 
+    fn latest_applied_migration(
+        &mut self,
+        migration_table: String,
+    ) -> BoxFuture<'_, Result<Option<AppliedMigration>, MigrateError>> {
+        Box::pin(async move {
+            // language=SQLite
+            let row: Option<(i64, Vec<u8>)> = query_as(&format!(
+                "SELECT version, checksum FROM {migration_table} ORDER BY version DESC LIMIT 1"
+            ))
+            .fetch_optional(self)
+            .await?;
+
+            Ok(row.map(|(version, checksum)| AppliedMigration {
+                version,
+                checksum: checksum.into(),
+            }))
+        })
+    }
+
     fn lock(&mut self) -> BoxFuture<'_, Result<(), MigrateError>> {
         Box::pin(async move { Ok(()) })
     }
@@ -135,52 +521,39 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration: &'m Migration,
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
-        Box::pin(async move {
-            let mut tx = self.begin().await?;
-            let start = Instant::now();
+        Box::pin(apply_migration(self, migration, migration_table, None))
+    }
 
-            // Use a single transaction for the actual migration script and the essential bookeeping so we never
-            // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
-            // The `execution_time` however can only be measured for the whole transaction. This value _only_ exists for
-            // data lineage and debugging reasons, so it is not super important if it is lost. So we initialize it to -1
-            // and update it once the actual transaction completed.
-            let _ = tx.execute(&*migration.sql).await?;
+    fn apply_with_progress<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+        progress: &'m mut (dyn FnMut(usize, usize) + Send),
+    ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
+        Box::pin(apply_migration(self, migration, migration_table, Some(progress)))
+    }
 
-            // language=SQL
+    fn record_applied<'e: 'm, 'm>(
+        &'e mut self,
+        migration: &'m Migration,
+        migration_table: String,
+    ) -> BoxFuture<'m, Result<(), MigrateError>> {
+        Box::pin(async move {
+            // Nothing is executed, so there is no execution time to record.
+            // language=SQLite
             let _ = query(
                 &format!(r#"
     INSERT INTO {migration_table} ( version, description, success, checksum, execution_time )
-    VALUES ( ?1, ?2, TRUE, ?3, -1 )
+    VALUES ( ?1, ?2, TRUE, ?3, 0 )
                 "#),
             )
             .bind(migration.version)
             .bind(&*migration.description)
             .bind(&*migration.checksum)
-            .execute(&mut *tx)
-            .await?;
-
-            tx.commit().await?;
-
-            // Update `elapsed_time`.
-            // NOTE: The process may disconnect/die at this point, so the elapsed time value might be lost. We accept
-            //       this small risk since this value is not super important.
-
-            let elapsed = start.elapsed();
-
-            // language=SQL
-            let _ = query(
-                &format!(r#"
-    UPDATE {migration_table}
-    SET execution_time = ?1
-    WHERE version = ?2
-                "#),
-            )
-            .bind(elapsed.as_nanos() as i64)
-            .bind(migration.version)
             .execute(self)
             .await?;
 
-            Ok(elapsed)
+            Ok(())
         })
     }
 
@@ -190,12 +563,16 @@ CREATE TABLE IF NOT EXISTS {migration_table} (
         migration_table: String,
     ) -> BoxFuture<'m, Result<Duration, MigrateError>> {
         Box::pin(async move {
+            if migration.no_tx("sqlite") {
+                return revert_no_tx(self, migration, migration_table).await;
+            }
+
             // Use a single transaction for the actual migration script and the essential bookeeping so we never
             // execute migrations twice. See https://github.com/launchbadge/sqlx/issues/1966.
             let mut tx = self.begin().await?;
             let start = Instant::now();
 
-            let _ = tx.execute(&*migration.sql).await?;
+            let _ = tx.execute(migration.sql_for("sqlite")?).await?;
 
             // language=SQL
             let _ = query(&format!(r#"DELETE FROM {migration_table} WHERE version = ?1"#))
@@ -211,6 +211,20 @@ impl SqliteConnectOptions {
         self
     }
 
+    /// Gets the name of the database file.
+    ///
+    /// A relative path is resolved against the current directory when connecting.
+    /// For an in-memory database, this is a generated name which does not refer to a file.
+    pub fn get_filename(&self) -> &Path {
+        &self.filename
+    }
+
+    /// Returns `true` if this is an in-memory database, e.g. from `sqlite::memory:`
+    /// or `?mode=memory`.
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory
+    }
+
     /// Set the enforcement of [foreign key constraints](https://www.sqlite.org/pragma.html#pragma_foreign_keys).
     ///
     /// SQLx chooses to enable this by default so that foreign keys function as expected,
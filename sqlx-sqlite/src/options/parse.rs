@@ -169,3 +169,21 @@ fn test_parse_shared_in_memory() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_parse_relative_filename() -> Result<(), Error> {
+    let options: SqliteConnectOptions = "sqlite://file.db".parse()?;
+    assert!(!options.in_memory);
+    assert_eq!(options.get_filename(), Path::new("file.db"));
+
+    let options: SqliteConnectOptions = "sqlite://./sub/file.db?mode=rwc".parse()?;
+    assert!(!options.in_memory);
+    assert!(options.create_if_missing);
+    assert_eq!(options.get_filename(), Path::new("./sub/file.db"));
+
+    let options: SqliteConnectOptions = "sqlite:file.db?mode=ro".parse()?;
+    assert!(options.read_only);
+    assert_eq!(options.get_filename(), Path::new("file.db"));
+
+    Ok(())
+}
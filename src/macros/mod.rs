@@ -741,6 +741,26 @@ macro_rules! query_file_scalar_unchecked (
 ///
 /// See [MigrationSource][crate::migrate::MigrationSource] for details on structure of the ./migrations directory.
 ///
+/// ## Migrations Table
+/// Applied migrations are recorded in `_sqlx_migrations` by default. Since a `static` migrator
+/// can't be modified, a different table, e.g. the one passed to `sqlx migrate run
+/// --migration-table`, is given to the macro instead:
+///
+/// ```rust,ignore
+/// use sqlx::migrate::Migrator;
+///
+/// static MIGRATOR: Migrator = sqlx::migrate!("db/migrations", migration_table = "app_migrations");
+/// ```
+///
+/// ## Versioning Schemes
+/// Pass `strict_versions = true` to refuse running migrations which mix sequential versions
+/// (e.g. `0003`) with timestamp versions (e.g. `20240101120000`), as
+/// [`Migrator::set_strict_versions`][crate::migrate::Migrator::set_strict_versions] does:
+///
+/// ```rust,ignore
+/// static MIGRATOR: Migrator = sqlx::migrate!("db/migrations", strict_versions = true);
+/// ```
+///
 /// ## Triggering Recompilation on Migration Changes
 /// In some cases when making changes to embedded migrations, such as adding a new migration without
 /// changing any Rust source files, you might find that `cargo build` doesn't actually do anything,
@@ -809,6 +829,14 @@ macro_rules! migrate {
         $crate::sqlx_macros::migrate!($dir)
     }};
 
+    ($dir:literal, $($key:ident = $value:literal),+ $(,)?) => {{
+        $crate::sqlx_macros::migrate!($dir, $($key = $value),+)
+    }};
+
+    ($($key:ident = $value:literal),+ $(,)?) => {{
+        $crate::sqlx_macros::migrate!("./migrations", $($key = $value),+)
+    }};
+
     () => {{
         $crate::sqlx_macros::migrate!("./migrations")
     }};
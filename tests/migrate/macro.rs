@@ -1,9 +1,13 @@
-use sqlx::migrate::Migrator;
+use sqlx::migrate::{MigrateError, Migration, MigrationDiff, MigrationType, Migrator};
 use std::path::Path;
+use std::time::Duration;
 
 static EMBEDDED_SIMPLE: Migrator = sqlx::migrate!("tests/migrate/migrations_simple");
 static EMBEDDED_REVERSIBLE: Migrator = sqlx::migrate!("tests/migrate/migrations_reversible");
 static EMBEDDED_SYMLINK: Migrator = sqlx::migrate!("tests/migrate/migrations_symlink");
+static EMBEDDED_DIALECTS: Migrator = sqlx::migrate!("tests/migrate/migrations_dialects");
+static EMBEDDED_MIXED: Migrator =
+    sqlx::migrate!("tests/migrate/migrations_mixed", strict_versions = true);
 
 #[sqlx_macros::test]
 async fn same_output() -> anyhow::Result<()> {
@@ -11,10 +15,103 @@ async fn same_output() -> anyhow::Result<()> {
     let runtime_reversible =
         Migrator::new(Path::new("tests/migrate/migrations_reversible"), Some(String::from("sqlx_migration"))).await?;
     let runtime_symlink = Migrator::new(Path::new("tests/migrate/migrations_symlink"), Some(String::from("sqlx_migration"))).await?;
+    let runtime_dialects =
+        Migrator::new(Path::new("tests/migrate/migrations_dialects"), None).await?;
 
     assert_same(&EMBEDDED_SIMPLE, &runtime_simple);
     assert_same(&EMBEDDED_REVERSIBLE, &runtime_reversible);
     assert_same(&EMBEDDED_SYMLINK, &runtime_symlink);
+    assert_same(&EMBEDDED_DIALECTS, &runtime_dialects);
+
+    Ok(())
+}
+
+#[sqlx_macros::test]
+async fn diff_against_embedded() -> anyhow::Result<()> {
+    let mut runtime = Migrator::new(Path::new("tests/migrate/migrations_simple"), None).await?;
+    assert_eq!(EMBEDDED_SIMPLE.diff(&runtime), vec![]);
+
+    // edit the first migration, remove the second and add a new one
+    let migrations = runtime.migrations.to_mut();
+    migrations[0] = Migration::new(
+        20220721115250,
+        "add test table".into(),
+        MigrationType::Simple,
+        "CREATE TABLE edited ( id INTEGER );".into(),
+    );
+    migrations.pop();
+    migrations.push(Migration::new(
+        20230101000000,
+        "added later".into(),
+        MigrationType::Simple,
+        "SELECT 1;".into(),
+    ));
+
+    assert_eq!(
+        EMBEDDED_SIMPLE.diff(&runtime),
+        vec![
+            MigrationDiff::ChecksumMismatch {
+                version: 20220721115250,
+                migration_type: MigrationType::Simple,
+            },
+            MigrationDiff::OnlyInSelf {
+                version: 20220721115524,
+                migration_type: MigrationType::Simple,
+            },
+            MigrationDiff::OnlyInOther {
+                version: 20230101000000,
+                migration_type: MigrationType::Simple,
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[sqlx_macros::test]
+async fn dialect_variants() -> anyhow::Result<()> {
+    let migrator = Migrator::new(Path::new("tests/migrate/migrations_dialects"), None).await?;
+    let migrations: Vec<_> = migrator.iter().collect();
+
+    // the variants are one logical migration
+    assert_eq!(migrations.len(), 2);
+    assert_eq!(migrations[0].description, "add test table");
+    assert_eq!(migrations[0].dialects().len(), 2);
+
+    assert!(migrations[0].sql_for("postgres")?.contains("BIGSERIAL"));
+    assert!(migrations[0].sql_for("sqlite")?.contains("AUTOINCREMENT"));
+    assert!(matches!(
+        migrations[0].sql_for("mysql"),
+        Err(MigrateError::DialectMissing(20230101000000, _))
+    ));
+
+    // without variants, every dialect gets the same SQL
+    assert_eq!(migrations[1].sql_for("mysql")?, &*migrations[1].sql);
+
+    // directives are read from the variant run against the database
+    assert_eq!(
+        migrations[0].lock_timeout("postgres")?,
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(migrations[0].lock_timeout("sqlite")?, None);
+
+    Ok(())
+}
+
+#[sqlx_macros::test]
+async fn mixed_version_schemes() -> anyhow::Result<()> {
+    // only refused when running or reverting with `strict_versions`
+    let runtime = Migrator::new(Path::new("tests/migrate/migrations_mixed"), None).await?;
+    assert!(!runtime.strict_versions);
+    assert!(EMBEDDED_MIXED.strict_versions);
+
+    for migrator in [&runtime, &EMBEDDED_MIXED] {
+        let res = migrator.validate_version_schemes();
+        assert!(
+            matches!(res, Err(MigrateError::MixedVersionSchemes(1, 20240101120000))),
+            "expected MixedVersionSchemes, got {res:?}"
+        );
+    }
 
     Ok(())
 }
@@ -27,6 +124,7 @@ fn assert_same(embedded: &Migrator, runtime: &Migrator) {
         assert_eq!(e.description, r.description);
         assert_eq!(e.migration_type, r.migration_type);
         assert_eq!(e.sql, r.sql);
+        assert_eq!(e.dialects(), r.dialects());
         assert_eq!(e.checksum, r.checksum);
     }
 }
@@ -0,0 +1,5 @@
+-- sqlx:lock-timeout 10s
+CREATE TABLE migrations_dialects_test (
+    some_id BIGSERIAL PRIMARY KEY,
+    some_payload BIGINT NOT NULL
+);
@@ -0,0 +1,4 @@
+CREATE TABLE migrations_dialects_test (
+    some_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    some_payload BIGINT NOT NULL
+);
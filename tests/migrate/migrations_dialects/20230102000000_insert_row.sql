@@ -0,0 +1,2 @@
+INSERT INTO migrations_dialects_test (some_payload)
+VALUES (100);
@@ -0,0 +1,3 @@
+CREATE TABLE migrations_mixed_test (
+    some_id BIGINT NOT NULL PRIMARY KEY
+);
@@ -0,0 +1 @@
+ALTER TABLE migrations_mixed_test ADD COLUMN some_payload BIGINT;
@@ -1,4 +1,4 @@
-use sqlx::migrate::Migrator;
+use sqlx::migrate::{MigrateDatabase, Migrator};
 use sqlx::mysql::{MySql, MySqlConnection};
 use sqlx::pool::PoolConnection;
 use sqlx::Executor;
@@ -66,6 +66,20 @@ async fn reversible(mut conn: PoolConnection<MySql>) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx_macros::test]
+async fn url_without_database_name() -> anyhow::Result<()> {
+    // nothing listens on port 1, so these would fail differently if they tried to connect
+    for url in ["mysql://root@localhost:1", "mysql://root@localhost:1/"] {
+        let res = <MySql as MigrateDatabase>::database_exists(url).await;
+        assert!(matches!(res, Err(sqlx::Error::Configuration(_))), "{res:?}");
+
+        let res = <MySql as MigrateDatabase>::create_database(url).await;
+        assert!(matches!(res, Err(sqlx::Error::Configuration(_))), "{res:?}");
+    }
+
+    Ok(())
+}
+
 /// Ensure that we have a clean initial state.
 async fn clean_up(conn: &mut MySqlConnection) -> anyhow::Result<()> {
     conn.execute("DROP TABLE migrations_simple_test").await.ok();
@@ -1,4 +1,4 @@
-use sqlx::migrate::Migrator;
+use sqlx::migrate::{Migrate, MigrateDatabase, Migrator};
 use sqlx::pool::PoolConnection;
 use sqlx::postgres::{PgConnection, Postgres};
 use sqlx::Executor;
@@ -66,12 +66,58 @@ async fn reversible(mut conn: PoolConnection<Postgres>) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx::test(migrations = false)]
+async fn no_transaction(mut conn: PoolConnection<Postgres>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator =
+        Migrator::new(Path::new("tests/postgres/migrations_no_transaction"), None).await?;
+    migrator.run(&mut conn).await?;
+
+    let valid: bool = conn
+        .fetch_one(
+            "SELECT indisvalid FROM pg_index \
+             WHERE indexrelid = 'migrations_no_transaction_test_email_idx'::regclass",
+        )
+        .await?
+        .get(0);
+    assert!(valid);
+    assert_eq!(
+        conn.list_applied_migrations(String::from("_sqlx_migrations"))
+            .await?
+            .len(),
+        2
+    );
+
+    Ok(())
+}
+
+#[sqlx_macros::test]
+async fn url_without_database_name() -> anyhow::Result<()> {
+    // nothing listens on port 1, so these would fail differently if they tried to connect
+    for url in [
+        "postgres://root@localhost:1",
+        "postgres://root@localhost:1/",
+    ] {
+        let res = <Postgres as MigrateDatabase>::database_exists(url).await;
+        assert!(matches!(res, Err(sqlx::Error::Configuration(_))), "{res:?}");
+
+        let res = <Postgres as MigrateDatabase>::create_database(url).await;
+        assert!(matches!(res, Err(sqlx::Error::Configuration(_))), "{res:?}");
+    }
+
+    Ok(())
+}
+
 /// Ensure that we have a clean initial state.
 async fn clean_up(conn: &mut PgConnection) -> anyhow::Result<()> {
     conn.execute("DROP TABLE migrations_simple_test").await.ok();
     conn.execute("DROP TABLE migrations_reversible_test")
         .await
         .ok();
+    conn.execute("DROP TABLE migrations_no_transaction_test")
+        .await
+        .ok();
     conn.execute("DROP TABLE _sqlx_migrations").await.ok();
 
     Ok(())
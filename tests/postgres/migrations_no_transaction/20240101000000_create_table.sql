@@ -0,0 +1 @@
+CREATE TABLE migrations_no_transaction_test ( id BIGINT PRIMARY KEY, email TEXT NOT NULL );
@@ -0,0 +1,4 @@
+-- sqlx:no-transaction
+-- cannot run inside a transaction block
+CREATE INDEX CONCURRENTLY migrations_no_transaction_test_email_idx
+    ON migrations_no_transaction_test (email);
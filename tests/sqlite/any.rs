@@ -16,3 +16,17 @@ async fn it_encodes_bool_with_any() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[sqlx_macros::test]
+async fn it_decodes_big_integers_with_any() -> anyhow::Result<()> {
+    sqlx::any::install_default_drivers();
+    let mut conn = new::<Any>().await?;
+
+    // above `i32::MAX`, though SQLite reports it with the same type as smaller integers
+    let value: i64 = sqlx::query_scalar("SELECT 2147483648")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(value, i64::from(i32::MAX) + 1);
+
+    Ok(())
+}
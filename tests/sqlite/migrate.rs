@@ -1,8 +1,9 @@
-use sqlx::migrate::Migrator;
+use sqlx::migrate::{Migrate, MigrateError, Migration, MigrationType, Migrator};
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::{Sqlite, SqliteConnection};
-use sqlx::Executor;
 use sqlx::Row;
+use sqlx::{Connection, Executor};
+use std::io;
 use std::path::Path;
 
 #[sqlx::test(migrations = false)]
@@ -66,13 +67,412 @@ async fn reversible(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx::test(migrations = false)]
+async fn has_pending(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+
+    // nothing applied yet
+    assert!(migrator.has_pending(&[]));
+
+    migrator.run(&mut conn).await?;
+
+    let applied = conn
+        .list_applied_migrations(String::from("_sqlx_migrations"))
+        .await?;
+    assert!(!migrator.has_pending(&applied));
+
+    // only the first migration applied
+    assert!(migrator.has_pending(&applied[..1]));
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn latest_applied_migration(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+
+    conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+        .await?;
+    assert!(conn
+        .latest_applied_migration(String::from("_sqlx_migrations"))
+        .await?
+        .is_none());
+
+    migrator.run(&mut conn).await?;
+
+    let latest = conn
+        .latest_applied_migration(String::from("_sqlx_migrations"))
+        .await?
+        .unwrap();
+    let applied = conn
+        .list_applied_migrations(String::from("_sqlx_migrations"))
+        .await?;
+    assert_eq!(latest.version, 20220721115524);
+    assert_eq!(latest.checksum, applied.last().unwrap().checksum);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn installed_on_index(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let mut migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+    migrator.set_installed_on_index(true);
+
+    migrator.run(&mut conn).await?;
+
+    // creating the index is idempotent
+    migrator.run(&mut conn).await?;
+
+    let indexes: i64 = conn
+        .fetch_one(
+            "SELECT COUNT(*) FROM sqlite_master \
+             WHERE type = 'index' AND name = '_sqlx_migrations_installed_on_idx'",
+        )
+        .await?
+        .get(0);
+    assert_eq!(indexes, 1);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn apply_error_source(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_invalid"), None).await?;
+
+    let err = migrator.run(&mut conn).await.unwrap_err();
+
+    let source = std::error::Error::source(&err).expect("error should have a source");
+    assert!(matches!(
+        source.downcast_ref::<sqlx::Error>(),
+        Some(sqlx::Error::Database(_))
+    ));
+    assert!(err.as_database_error().is_some());
+
+    // also reachable once converted into `sqlx::Error`
+    assert!(sqlx::Error::from(err).as_database_error().is_some());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn dirty_migration(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+
+    // only reads the migrations table, and does not create it
+    assert!(migrator.dirty_migration(&mut conn).await?.is_none());
+    assert!(
+        !conn
+            .migrations_table_exists(String::from("_sqlx_migrations"))
+            .await?
+    );
+
+    migrator.run(&mut conn).await?;
+
+    assert!(migrator.dirty_migration(&mut conn).await?.is_none());
+
+    conn.execute("UPDATE _sqlx_migrations SET success = FALSE WHERE version = 20220721115524")
+        .await?;
+
+    let dirty = migrator
+        .dirty_migration(&mut conn)
+        .await?
+        .expect("database should be dirty");
+    assert_eq!(dirty.version, 20220721115524);
+    assert_eq!(
+        dirty.migration.map(|m| m.description()),
+        Some("convert type")
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn batch_commit(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_batched"), None).await?;
+    migrator.run(&mut conn).await?;
+
+    let rows: i64 = conn
+        .fetch_one("SELECT COUNT(*) FROM migrations_batched_test")
+        .await?
+        .get(0);
+    assert_eq!(rows, 3);
+    assert_eq!(
+        conn.dirty_version(String::from("_sqlx_migrations")).await?,
+        None
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn batch_commit_failure(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator =
+        Migrator::new(Path::new("tests/sqlite/migrations_batched_invalid"), None).await?;
+    assert!(migrator.run(&mut conn).await.is_err());
+
+    // the first batch stays committed, and the database is dirty
+    let rows: i64 = conn
+        .fetch_one("SELECT COUNT(*) FROM migrations_batched_test")
+        .await?
+        .get(0);
+    assert_eq!(rows, 1);
+    assert_eq!(
+        conn.dirty_version(String::from("_sqlx_migrations")).await?,
+        Some(20240101000000)
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn progress_with_trigger(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_trigger"), None).await?;
+    let migration = migrator.iter().next().unwrap();
+
+    conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+        .await?;
+
+    let mut reported = Vec::new();
+    conn.apply_with_progress(
+        migration,
+        String::from("_sqlx_migrations"),
+        &mut |k, total| reported.push((k, total)),
+    )
+    .await?;
+
+    // the body of the trigger is part of its statement
+    assert_eq!(reported, [(1, 4), (2, 4), (3, 4), (4, 4)]);
+
+    let updates: i64 = conn
+        .fetch_one("SELECT updates FROM migrations_trigger_test WHERE id = 2")
+        .await?
+        .get(0);
+    assert_eq!(updates, 1);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn checkpoint_in_unsplittable_sql(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    // the trigger is missing its `END`, so the checkpoint cannot be placed
+    let migration = Migration::new(
+        1,
+        "unsplittable".into(),
+        MigrationType::Simple,
+        "CREATE TABLE migrations_trigger_test ( id INTEGER PRIMARY KEY );\n\
+         -- sqlx:checkpoint created\n\
+         CREATE TRIGGER t_inserted AFTER INSERT ON migrations_trigger_test\n\
+         BEGIN SELECT 1;\n"
+            .into(),
+    );
+
+    conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+        .await?;
+
+    let err = conn
+        .apply(&migration, String::from("_sqlx_migrations"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MigrateError::InvalidDirective(1, _)), "{err}");
+
+    // nothing was executed
+    let tables: i64 = conn
+        .fetch_one(
+            "SELECT COUNT(*) FROM sqlite_master \
+             WHERE type = 'table' AND name = 'migrations_trigger_test'",
+        )
+        .await?
+        .get(0);
+    assert_eq!(tables, 0);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn embedded_migration_table(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    static MIGRATOR: Migrator = sqlx::migrate!(
+        "tests/sqlite/migrations_reversible",
+        migration_table = "app_migrations"
+    );
+
+    clean_up(&mut conn).await?;
+
+    MIGRATOR.run(&mut conn).await?;
+
+    let applied = conn
+        .list_applied_migrations(String::from("app_migrations"))
+        .await?;
+    assert_eq!(applied.len(), 2);
+
+    let default_table: i64 = conn
+        .fetch_one(
+            "SELECT COUNT(*) FROM sqlite_master \
+             WHERE type = 'table' AND name = '_sqlx_migrations'",
+        )
+        .await?
+        .get(0);
+    assert_eq!(default_table, 0);
+
+    MIGRATOR.undo(&mut conn, 20220721124650).await?;
+
+    let applied = conn
+        .list_applied_migrations(String::from("app_migrations"))
+        .await?;
+    assert_eq!(applied.len(), 1);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn upgrade_migrations_table(mut conn: PoolConnection<Sqlite>) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    // the layout before checksums and execution times were recorded
+    conn.execute(
+        "CREATE TABLE _sqlx_migrations (\
+             version BIGINT PRIMARY KEY, \
+             description TEXT NOT NULL, \
+             installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, \
+             success BOOLEAN NOT NULL\
+         )",
+    )
+    .await?;
+    conn.execute(
+        "INSERT INTO _sqlx_migrations (version, description, success) VALUES (1, 'old', TRUE)",
+    )
+    .await?;
+
+    conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+        .await?;
+    // idempotent once upgraded
+    conn.ensure_migrations_table(String::from("_sqlx_migrations"))
+        .await?;
+
+    let applied = conn
+        .list_applied_migrations(String::from("_sqlx_migrations"))
+        .await?;
+    assert_eq!(applied.len(), 1);
+    assert_eq!(applied[0].version, 1);
+    assert!(applied[0].checksum.is_empty());
+
+    let execution_time: i64 = conn
+        .fetch_one("SELECT execution_time FROM _sqlx_migrations WHERE version = 1")
+        .await?
+        .get(0);
+    assert_eq!(execution_time, -1);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn run_after_upgrading_migrations_table(
+    mut conn: PoolConnection<Sqlite>,
+) -> anyhow::Result<()> {
+    clean_up(&mut conn).await?;
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+    migrator.run(&mut conn).await?;
+
+    // recorded again in the layout before checksums and execution times were
+    conn.execute("DROP TABLE _sqlx_migrations").await?;
+    conn.execute(
+        "CREATE TABLE _sqlx_migrations (\
+             version BIGINT PRIMARY KEY, \
+             description TEXT NOT NULL, \
+             installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, \
+             success BOOLEAN NOT NULL\
+         )",
+    )
+    .await?;
+    for migration in migrator.iter() {
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success) VALUES (?, ?, TRUE)",
+        )
+        .bind(migration.version)
+        .bind(migration.description())
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    // the empty checksums of the upgraded rows are unknown, not mismatched
+    migrator.run(&mut conn).await?;
+
+    let applied = conn
+        .list_applied_migrations(String::from("_sqlx_migrations"))
+        .await?;
+    assert_eq!(applied.len(), migrator.iter().count());
+
+    Ok(())
+}
+
+#[sqlx_macros::test]
+async fn run_with_connector_reconnects() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let path = dir.path().join("connector.db");
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+
+    let migrator = Migrator::new(Path::new("tests/sqlite/migrations_simple"), None).await?;
+
+    // the first connection fails, e.g. with credentials which were just rotated
+    let mut attempts = 0;
+    migrator
+        .run_with_connector(|| {
+            attempts += 1;
+            let (url, fail) = (url.clone(), attempts == 1);
+
+            async move {
+                if fail {
+                    return Err(io::Error::from(io::ErrorKind::ConnectionReset).into());
+                }
+
+                SqliteConnection::connect(&url).await
+            }
+        })
+        .await?;
+    assert_eq!(attempts, 2);
+
+    let mut conn = SqliteConnection::connect(&url).await?;
+    let res: String = conn
+        .fetch_one("SELECT some_payload FROM migrations_simple_test")
+        .await?
+        .get(0);
+    assert_eq!(res, "110_suffix");
+
+    Ok(())
+}
+
 /// Ensure that we have a clean initial state.
 async fn clean_up(conn: &mut SqliteConnection) -> anyhow::Result<()> {
     conn.execute("DROP TABLE migrations_simple_test").await.ok();
     conn.execute("DROP TABLE migrations_reversible_test")
         .await
         .ok();
+    conn.execute("DROP TABLE migrations_batched_test")
+        .await
+        .ok();
+    conn.execute("DROP TABLE migrations_trigger_test")
+        .await
+        .ok();
     conn.execute("DROP TABLE _sqlx_migrations").await.ok();
+    conn.execute("DROP TABLE app_migrations").await.ok();
 
     Ok(())
 }
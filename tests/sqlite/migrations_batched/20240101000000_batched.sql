@@ -0,0 +1,5 @@
+-- sqlx:batch-commit 2
+CREATE TABLE migrations_batched_test ( id INTEGER PRIMARY KEY );
+INSERT INTO migrations_batched_test VALUES (1);
+INSERT INTO migrations_batched_test VALUES (2);
+INSERT INTO migrations_batched_test VALUES (3);
@@ -0,0 +1 @@
+SELECT * FROM migrations_invalid_does_not_exist;
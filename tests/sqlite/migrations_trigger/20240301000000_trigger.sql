@@ -0,0 +1,15 @@
+CREATE TABLE migrations_trigger_test (
+    id INTEGER PRIMARY KEY,
+    updates INTEGER NOT NULL DEFAULT 0
+);
+
+-- the semicolons in the body of the trigger do not end the statement
+CREATE TRIGGER migrations_trigger_test_updated
+AFTER UPDATE OF id ON migrations_trigger_test
+BEGIN
+    UPDATE migrations_trigger_test SET updates = updates + 1 WHERE id = new.id;
+END;
+
+INSERT INTO migrations_trigger_test (id) VALUES (1);
+
+UPDATE migrations_trigger_test SET id = 2 WHERE id = 1;